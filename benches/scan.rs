@@ -0,0 +1,77 @@
+use criterion::{criterion_group, criterion_main, Criterion};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const DIRECTORIES: usize = 200;
+const FILES_PER_DIRECTORY: usize = 100;
+
+/// Builds a root holding `DIRECTORIES * FILES_PER_DIRECTORY` past migrations,
+/// which is roughly the size of the large repositories the scan has to handle.
+fn synthetic_root() -> PathBuf {
+    let root = Path::new(env!("CARGO_TARGET_TMPDIR")).join("synthetic_root");
+    if root.join(".gen_root").exists() {
+        return root;
+    }
+    for directory in 0..DIRECTORIES {
+        let directory_path = root.join(format!("service_{directory:03}"));
+        fs::create_dir_all(&directory_path).unwrap();
+        for file in 0..FILES_PER_DIRECTORY {
+            let day = 1 + file % 28;
+            let index = 1 + file / 28;
+            let year = 2000 + directory % 20;
+            let name =
+                format!("{year}01{day:02}{index:02} - create table t_{directory}_{file}.sql");
+            fs::write(directory_path.join(name), "").unwrap();
+        }
+    }
+    fs::write(root.join(".gen_root"), "").unwrap();
+    root
+}
+
+fn generate(c: &mut Criterion) {
+    let root = synthetic_root();
+    let output = root.join("output");
+    fs::create_dir_all(&output).unwrap();
+    // Migrations earlier runs left at the root would use up the day's
+    // indexes.
+    for entry in fs::read_dir(&root).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|x| x == "sql") {
+            fs::remove_file(path).unwrap();
+        }
+    }
+
+    for (name, args) in [
+        (
+            "generate in synthetic root",
+            &["script", "--name", "bench", "--here"][..],
+        ),
+        (
+            "generate in synthetic root without the cache",
+            &["script", "--name", "bench", "--here", "--no-cache"],
+        ),
+    ] {
+        c.bench_function(name, |b| {
+            b.iter(|| {
+                let status = Command::new(env!("CARGO_BIN_EXE_thing"))
+                    .args(args)
+                    .current_dir(&output)
+                    .output()
+                    .unwrap()
+                    .status;
+                assert!(status.success());
+                for entry in fs::read_dir(&output).unwrap() {
+                    fs::remove_file(entry.unwrap().path()).unwrap();
+                }
+            })
+        });
+    }
+}
+
+criterion_group! {
+    name = benches;
+    config = Criterion::default().sample_size(20);
+    targets = generate
+}
+criterion_main!(benches);
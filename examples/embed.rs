@@ -0,0 +1,23 @@
+//! Generating migrations from another program, here a scaffolder previewing
+//! a table's migration before writing it.
+//!
+//! ```sh
+//! cargo run --example embed -- users
+//! ```
+
+use std::env;
+use thing::{find_root, Generator, Operation};
+
+fn main() -> Result<(), thing::GenError> {
+    let table = env::args().nth(1).unwrap_or_else(|| "users".to_owned());
+    let root = find_root(&env::current_dir()?)?;
+    let generator = Generator::new(&root)
+        .operation(Operation::CreateTable)
+        .name(&table)
+        .column("id:bigint:primary key, email:text:not null");
+    for (path, content) in generator.clone().dry_run(true).generate_files()? {
+        println!("{}:\n{content}", path.display());
+    }
+    println!("wrote {}", generator.generate()?.display());
+    Ok(())
+}
@@ -0,0 +1,68 @@
+//! A plugin adding a `create-view` operation, an `author` token and an
+//! `upper` helper; see `include/gen_plugin.h`.
+//!
+//! Build it with `cargo build --example plugin --features plugins` and copy
+//! the library from `target/debug/examples` into the root's `plugins/`.
+
+use std::ffi::{c_char, CStr, CString};
+
+const MANIFEST: &CStr = cr#"{
+    "abi": 1,
+    "name": "views",
+    "operations": [{
+        "name": "create-view",
+        "file_name": "create view {table_name}",
+        "template": "-- author: {author}\n-- branch: {branch}\nCREATE VIEW {schema_name}{dot}{table_name | upper} AS\nSELECT\n"
+    }],
+    "tokens": ["author"],
+    "helpers": ["upper"]
+}"#;
+
+#[unsafe(no_mangle)]
+pub extern "C" fn gen_plugin_manifest() -> *const c_char {
+    MANIFEST.as_ptr()
+}
+
+/// # Safety
+///
+/// Both arguments are NUL terminated strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gen_plugin_token(
+    token: *const c_char,
+    _context: *const c_char,
+) -> *mut c_char {
+    match unsafe { CStr::from_ptr(token) }.to_bytes() {
+        b"author" => into_raw(std::env::var("USER").unwrap_or_default()),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// # Safety
+///
+/// Both arguments are NUL terminated strings.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gen_plugin_helper(
+    helper: *const c_char,
+    value: *const c_char,
+) -> *mut c_char {
+    let value = unsafe { CStr::from_ptr(value) }.to_string_lossy();
+    match (
+        unsafe { CStr::from_ptr(helper) }.to_bytes(),
+        serde_json::from_str(&value),
+    ) {
+        (b"upper", Ok(serde_json::Value::String(value))) => into_raw(value.to_uppercase()),
+        _ => std::ptr::null_mut(),
+    }
+}
+
+/// # Safety
+///
+/// `value` was returned by one of the functions above.
+#[unsafe(no_mangle)]
+pub unsafe extern "C" fn gen_plugin_free(value: *mut c_char) {
+    drop(unsafe { CString::from_raw(value) });
+}
+
+fn into_raw(value: String) -> *mut c_char {
+    CString::new(value).map_or(std::ptr::null_mut(), CString::into_raw)
+}
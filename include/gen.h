@@ -0,0 +1,47 @@
+/* C interface to the migration naming and rendering core.
+ *
+ * Build with `cargo build --release --no-default-features --features ffi`
+ * and link against the resulting cdylib.
+ *
+ * Strings returned by this library are owned by the caller and must be
+ * released with gen_string_free(). All strings are UTF-8.
+ */
+#ifndef GEN_H
+#define GEN_H
+
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Computes the file name of a migration dated `date` ("YYYY-MM-DD") with the
+ * given daily index, e.g. "2024061201 - add column email to users.sql".
+ * `operation` is a kebab-case operation name such as "add-column".
+ * `column` may be NULL. Returns NULL on failure; see gen_last_error(). */
+char *gen_compute_filename(const char *operation,
+                           const char *name,
+                           const char *column,
+                           const char *date,
+                           int32_t index);
+
+/* Renders the body of a migration. `schema` and `column` may be NULL.
+ * Returns NULL both on failure and for operations without a template;
+ * gen_last_error() is NULL in the latter case. */
+char *gen_render_operation(const char *operation,
+                           const char *name,
+                           const char *schema,
+                           const char *column);
+
+/* Message of the last failure on the calling thread, or NULL. Valid until the
+ * next call into this library on the same thread. */
+const char *gen_last_error(void);
+
+/* Releases a string returned by this library. NULL is ignored. */
+void gen_string_free(char *value);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* GEN_H */
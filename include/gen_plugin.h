@@ -0,0 +1,52 @@
+/* Interface a plugin library exports to add operations, template tokens and
+ * template helpers to gen.
+ *
+ * Plugins are dynamic libraries placed in the root's plugins/ directory and
+ * are only loaded by builds with the `plugins` feature. All strings are UTF-8
+ * and NUL terminated.
+ */
+#ifndef GEN_PLUGIN_H
+#define GEN_PLUGIN_H
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* Required. Returns a JSON manifest that stays valid while the library is
+ * loaded:
+ *
+ *   {
+ *     "abi": 1,
+ *     "name": "views",
+ *     "operations": [{
+ *       "name": "create-view",
+ *       "file_name": "create view {table_name}",
+ *       "template": "CREATE VIEW {table_name} AS\n",
+ *       "requires_column": false
+ *     }],
+ *     "tokens": ["author"],
+ *     "helpers": ["upper"]
+ *   }
+ *
+ * `file_name` and `template` are tinytemplate templates rendered with the
+ * same context as the built-in ones plus every plugin's tokens. Everything
+ * but "abi" and "name" may be omitted. */
+const char *gen_plugin_manifest(void);
+
+/* Required when the manifest lists tokens. Returns the value of `token` for
+ * the migration described by `context`, a JSON object with "operation",
+ * "name", "column" and "schema", or NULL to leave the token unset. */
+char *gen_plugin_token(const char *token, const char *context);
+
+/* Required when the manifest lists helpers. Formats `value`, a JSON value,
+ * for a template's `{value | helper}`, or returns NULL to fail rendering. */
+char *gen_plugin_helper(const char *helper, const char *value);
+
+/* Required with either of the two above. Releases a string they returned. */
+void gen_plugin_free(char *value);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* GEN_PLUGIN_H */
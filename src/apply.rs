@@ -0,0 +1,231 @@
+//! Running the migrations a database has not seen yet, in the order their
+//! names give, and recording each in a tracking table.
+
+use crate::config::Config;
+use crate::db::Database;
+use crate::dialect::Dialect;
+use crate::error::GenError;
+use crate::index::MigrationIndex;
+use crate::language::Language;
+use crate::manifest::{checksum, relative_path};
+use crate::naming::parse_file_name;
+use crate::operation::NO_TRANSACTION;
+use crate::seed::literal;
+use serde::Serialize;
+use sqlx::AssertSqlSafe;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The table recording the migrations applied to a database.
+pub const TRACKING_TABLE: &str = "_gen_migrations";
+
+/// Where a migration stands with a database.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum State {
+    Applied,
+    /// Applied, but the file changed since.
+    Changed,
+    Pending,
+    /// Applied, but the file is gone.
+    Missing,
+}
+
+impl std::fmt::Display for State {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            State::Applied => "applied",
+            State::Changed => "changed",
+            State::Pending => "pending",
+            State::Missing => "missing",
+        })
+    }
+}
+
+/// The SQL migrations of `index`, by date and then index. Rollbacks and
+/// files that do not follow the naming scheme are left out.
+pub fn migrations(root: &Path, config: &Config, index: &MigrationIndex) -> Vec<PathBuf> {
+    let mut migrations = index
+        .files()
+        .filter(|x| !config.is_rollback(root, x))
+        .filter_map(|path| {
+            let name = parse_file_name(path.file_name()?.to_str()?)?;
+            (name.language == Language::Sql).then(|| {
+                (
+                    (name.date, name.index, path.to_path_buf()),
+                    path.to_path_buf(),
+                )
+            })
+        })
+        .collect::<Vec<_>>();
+    migrations.sort();
+    migrations.into_iter().map(|(_, path)| path).collect()
+}
+
+/// The migrations of `index` that are not among `applied`, in the order they
+/// run.
+pub fn pending(
+    root: &Path,
+    config: &Config,
+    index: &MigrationIndex,
+    applied: &BTreeMap<String, String>,
+) -> Vec<PathBuf> {
+    let mut migrations = migrations(root, config, index);
+    migrations.retain(|x| !applied.contains_key(&relative_path(root, x)));
+    migrations
+}
+
+/// Where each migration of `index` stands with the database that applied
+/// `applied`, in the order they run, followed by the applied ones that are
+/// gone. Paths are relative to `root`.
+pub fn states(
+    root: &Path,
+    config: &Config,
+    index: &MigrationIndex,
+    applied: &BTreeMap<String, String>,
+) -> Result<Vec<(String, State)>, GenError> {
+    let mut states = Vec::new();
+    for path in migrations(root, config, index) {
+        let relative = relative_path(root, &path);
+        let state = match applied.get(&relative) {
+            None => State::Pending,
+            Some(sum) if *sum == checksum(&fs::read(&path)?) => State::Applied,
+            Some(_) => State::Changed,
+        };
+        states.push((relative, state));
+    }
+    let missing = applied
+        .keys()
+        .filter(|x| !states.iter().any(|(path, _)| path == *x))
+        .map(|x| (x.clone(), State::Missing))
+        .collect::<Vec<_>>();
+    states.extend(missing);
+    Ok(states)
+}
+
+/// The dialect of the database at `url`, for quoting what is recorded.
+pub fn dialect(url: &str) -> Dialect {
+    match url.split(':').next() {
+        Some("mysql" | "mariadb") => Dialect::Mysql,
+        Some("sqlite") => Dialect::Sqlite,
+        _ => Dialect::Postgres,
+    }
+}
+
+/// The checksums of the migrations recorded as applied to `database`, by
+/// their paths relative to the root, creating the tracking table first if
+/// need be.
+pub async fn applied(database: &Database) -> Result<BTreeMap<String, String>, GenError> {
+    database
+        .execute(&format!(
+            "CREATE TABLE IF NOT EXISTS {TRACKING_TABLE} (\
+             path VARCHAR(255) NOT NULL PRIMARY KEY, \
+             checksum VARCHAR(64) NOT NULL, \
+             applied_at VARCHAR(32) NOT NULL)"
+        ))
+        .await?;
+    let query = format!("SELECT path, checksum FROM {TRACKING_TABLE}");
+    let rows = sqlx::query_as::<_, (String, String)>(AssertSqlSafe(query));
+    let rows = database.timed(rows.fetch_all(database.pool())).await?;
+    Ok(rows.into_iter().collect())
+}
+
+/// Whether `sql` opens a transaction of its own, on a `BEGIN;` or `START
+/// TRANSACTION;` line as the data migration templates do. The `BEGIN` of a
+/// function or trigger body has no `;`.
+fn controls_transaction(sql: &str) -> bool {
+    sql.lines().any(|line| {
+        let line = line.trim().to_ascii_uppercase();
+        matches!(
+            line.as_str(),
+            "BEGIN;" | "BEGIN TRANSACTION;" | "BEGIN WORK;" | "START TRANSACTION;"
+        )
+    })
+}
+
+/// Runs the migration at `path` against `database` and records it, both in
+/// one transaction unless the migration starts with `-- no-transaction` or
+/// opens one itself.
+pub async fn apply(
+    database: &Database,
+    dialect: Dialect,
+    root: &Path,
+    path: &Path,
+) -> Result<(), GenError> {
+    let content = fs::read(path)?;
+    let sql = String::from_utf8_lossy(&content);
+    let record = format!(
+        "INSERT INTO {TRACKING_TABLE} (path, checksum, applied_at) VALUES ({}, {}, {})",
+        literal(dialect, Some(&relative_path(root, path))),
+        literal(dialect, Some(&checksum(&content))),
+        literal(dialect, Some(&chrono::Utc::now().to_rfc3339())),
+    );
+    if sql.trim_start().starts_with(NO_TRANSACTION) || controls_transaction(&sql) {
+        database.execute(&sql).await?;
+        database.execute(&record).await?;
+        return Ok(());
+    }
+    let mut transaction = database.timed(database.pool().begin()).await?;
+    for statement in [sql.into_owned(), record] {
+        let statement = sqlx::raw_sql(AssertSqlSafe(statement));
+        database.timed(statement.execute(&mut *transaction)).await?;
+    }
+    database.timed(transaction.commit()).await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn states_follow_the_tracking_table() {
+        let root = std::env::temp_dir().join(format!("thing-apply-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let files = [
+            "2024061201 - create table users.sql",
+            "2024061201 - create table users.down.sql",
+            "2024061202 - create table orders.sql",
+            "2024061203 - create table items.sql",
+        ];
+        for name in files {
+            fs::write(root.join(name), name).unwrap();
+        }
+        let applied = BTreeMap::from([
+            (files[0].to_owned(), checksum(files[0].as_bytes())),
+            (files[2].to_owned(), checksum(b"edited since")),
+            (
+                "2024061101 - create table gone.sql".to_owned(),
+                String::new(),
+            ),
+        ]);
+        let index = MigrationIndex::scan(&root);
+        let states = states(&root, &Config::default(), &index, &applied).unwrap();
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(
+            states,
+            [
+                (files[0].to_owned(), State::Applied),
+                (files[2].to_owned(), State::Changed),
+                (files[3].to_owned(), State::Pending),
+                (
+                    "2024061101 - create table gone.sql".to_owned(),
+                    State::Missing
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn transaction_control_is_found_on_its_own_line() {
+        assert!(controls_transaction(
+            "BEGIN;\nUPDATE users SET x = 1;\nCOMMIT;\n"
+        ));
+        assert!(controls_transaction("start transaction;\nCOMMIT;\n"));
+        assert!(!controls_transaction(
+            "CREATE FUNCTION f() RETURNS trigger AS $$\nBEGIN\n  RETURN NEW;\nEND\n$$;\n"
+        ));
+    }
+}
@@ -0,0 +1,157 @@
+//! Bundles of old migrations, so a repository can stop checking out years of
+//! files it never changes again while keeping them reproducible.
+
+use crate::error::GenError;
+use crate::index::MigrationIndex;
+use crate::manifest::{checksum, relative_path};
+use crate::naming::parse_file_name;
+use chrono::Datelike;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+
+/// Directory under the root that bundles are written to.
+pub const ARCHIVE_DIR: &str = "archive";
+
+/// Name of the entry in every bundle that lists the migrations it holds.
+pub const BUNDLE_MANIFEST: &str = "manifest.json";
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BundleManifest {
+    pub year: i32,
+    pub migrations: Vec<BundleEntry>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BundleEntry {
+    /// Relative to the root, with `/` separators.
+    pub path: String,
+    /// SHA-256 of the file, in lowercase hex.
+    pub checksum: String,
+}
+
+/// Writes every migration dated in `year` into `archive/<year>.tar.zst` and,
+/// unless `keep` is set, removes the originals. Returns the bundle's path, or
+/// `None` when there was nothing to archive.
+///
+/// Bundles are plain zstd compressed tarballs, so `tar --zstd -xf` restores
+/// them as well as `extract` does.
+pub fn archive(
+    root: &Path,
+    index: &MigrationIndex,
+    year: i32,
+    keep: bool,
+) -> Result<Option<PathBuf>, GenError> {
+    let archive_dir = root.join(ARCHIVE_DIR);
+    let mut files = index
+        .files()
+        .filter(|x| !x.starts_with(&archive_dir))
+        .filter_map(|path| {
+            let name = parse_file_name(path.file_name()?.to_str()?)?;
+            (name.date.year() == year).then(|| (name.date, name.index, path.to_path_buf()))
+        })
+        .collect::<Vec<_>>();
+    if files.is_empty() {
+        return Ok(None);
+    }
+    files.sort();
+
+    let mut contents = Vec::new();
+    for (_, _, path) in &files {
+        contents.push((relative_path(root, path), fs::read(path)?));
+    }
+    let manifest = BundleManifest {
+        year,
+        migrations: contents
+            .iter()
+            .map(|(path, content)| BundleEntry {
+                path: path.clone(),
+                checksum: checksum(content),
+            })
+            .collect(),
+    };
+    let manifest = serde_json::to_vec_pretty(&manifest).map_err(std::io::Error::other)?;
+
+    let bundle = archive_dir.join(format!("{year}.tar.zst"));
+    if bundle.exists() {
+        return Err(GenError::Archive(format!(
+            "{} already exists",
+            bundle.display()
+        )));
+    }
+    fs::create_dir_all(&archive_dir)?;
+    let temporary = PathBuf::from(format!("{}.tmp", bundle.display()));
+    let encoder = zstd::Encoder::new(File::create(&temporary)?, 0)?;
+    let mut builder = tar::Builder::new(encoder);
+    append(&mut builder, BUNDLE_MANIFEST, &manifest)?;
+    for (path, content) in &contents {
+        append(&mut builder, path, content)?;
+    }
+    builder.into_inner()?.finish()?.sync_all()?;
+    fs::rename(&temporary, &bundle)?;
+
+    if !keep {
+        for (_, _, path) in &files {
+            fs::remove_file(path)?;
+        }
+    }
+    Ok(Some(bundle))
+}
+
+/// Restores the migrations in `bundle` under `root`, checking each against
+/// the bundle's manifest. Existing files are never overwritten.
+pub fn extract(root: &Path, bundle: &Path) -> Result<Vec<PathBuf>, GenError> {
+    let mut archive = tar::Archive::new(zstd::Decoder::new(File::open(bundle)?)?);
+    let mut manifest: Option<BundleManifest> = None;
+    let mut extracted = Vec::new();
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.into_owned();
+        let mut content = Vec::new();
+        entry.read_to_end(&mut content)?;
+
+        if path == Path::new(BUNDLE_MANIFEST) {
+            manifest = Some(serde_json::from_slice(&content).map_err(|x| {
+                GenError::Archive(format!("{}: bad manifest: {x}", bundle.display()))
+            })?);
+            continue;
+        }
+        if !path.components().all(|x| matches!(x, Component::Normal(_))) {
+            return Err(GenError::Archive(format!("{path:?} leaves the root")));
+        }
+        let expected = manifest
+            .as_ref()
+            .and_then(|x| {
+                let path = relative_path(Path::new(""), &path);
+                x.migrations.iter().find(|x| x.path == path)
+            })
+            .ok_or_else(|| GenError::Archive(format!("{path:?} is not in the manifest")))?;
+        if expected.checksum != checksum(&content) {
+            return Err(GenError::Archive(format!(
+                "{path:?} does not match its checksum"
+            )));
+        }
+
+        let target = root.join(&path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        File::create_new(&target)?.write_all(&content)?;
+        extracted.push(target);
+    }
+    Ok(extracted)
+}
+
+fn append<W: Write>(
+    builder: &mut tar::Builder<W>,
+    path: &str,
+    content: &[u8],
+) -> Result<(), GenError> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(content.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, path, content)?;
+    Ok(())
+}
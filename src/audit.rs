@@ -0,0 +1,62 @@
+//! An append-only record of every change gen makes to the set of migrations,
+//! for change management.
+
+use crate::error::GenError;
+use crate::git;
+use crate::manifest::relative_path;
+use chrono::Local;
+use serde::Serialize;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// File at the root that events are appended to, one JSON object per line.
+pub const AUDIT_FILE: &str = "gen.audit.jsonl";
+
+#[derive(Serialize)]
+struct Event<'a> {
+    action: &'a str,
+    who: String,
+    when: String,
+    /// Relative to the root.
+    files: Vec<String>,
+    /// What the files were called before, for renames.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    previous: Vec<String>,
+    flags: &'a [String],
+}
+
+/// Appends that `action` (e.g. `generate`) touched `files`, previously named
+/// `previous` when they were renamed, and was asked for with `flags`. The
+/// person is the git identity in `root`, or the login name outside a
+/// repository.
+pub fn record(
+    root: &Path,
+    action: &str,
+    files: &[PathBuf],
+    previous: &[PathBuf],
+    flags: &[String],
+) -> Result<(), GenError> {
+    let relative = |x: &[PathBuf]| x.iter().map(|x| relative_path(root, x)).collect();
+    let event = Event {
+        action,
+        who: git::author(root)
+            .or_else(|| std::env::var("USER").ok())
+            .or_else(|| std::env::var("USERNAME").ok())
+            .unwrap_or_default(),
+        when: Local::now().to_rfc3339(),
+        files: relative(files),
+        previous: relative(previous),
+        flags,
+    };
+    let mut line = serde_json::to_string(&event).map_err(std::io::Error::other)?;
+    line.push('\n');
+    // A single append of the whole line keeps concurrent runs from
+    // interleaving.
+    OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(root.join(AUDIT_FILE))?
+        .write_all(line.as_bytes())?;
+    Ok(())
+}
@@ -0,0 +1,226 @@
+//! Columns as `--column` spells them: a name, optionally followed by the
+//! column's type and then its constraints, e.g. `age:int:not null default 0`.
+//! Several are separated by commas.
+
+use crate::dialect::Dialect;
+use crate::language::Language;
+use serde::{Deserialize, Serialize};
+
+/// A column of `--column`, split into its parts.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Column {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub data_type: Option<String>,
+    /// Everything after the type, as written, e.g. `not null default 0`.
+    pub constraints: Option<String>,
+}
+
+impl Column {
+    /// Splits `spec` at its first two colons. Constraints can hold colons
+    /// of their own, as in `default now()::date`.
+    pub fn parse(spec: &str) -> Column {
+        let mut parts = spec.splitn(3, ':').map(str::trim);
+        let name = parts.next().unwrap_or_default().to_owned();
+        let mut part = || {
+            parts
+                .next()
+                .filter(|x| !x.is_empty())
+                .map(ToOwned::to_owned)
+        };
+        Column {
+            name,
+            data_type: part(),
+            constraints: part(),
+        }
+    }
+}
+
+/// The kinds of keys, serialized as their keyword for templates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum KeyKind {
+    #[serde(rename = "PRIMARY KEY")]
+    PrimaryKey,
+    #[serde(rename = "UNIQUE")]
+    Unique,
+    #[serde(rename = "FOREIGN KEY")]
+    ForeignKey,
+}
+
+/// A primary key, unique or foreign key constraint of `create-table`.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Key {
+    pub kind: KeyKind,
+    pub columns: Vec<String>,
+    /// The table and column a foreign key references.
+    pub referenced_table: Option<String>,
+    pub referenced_column: Option<String>,
+}
+
+impl Key {
+    /// The primary key on the comma separated `columns`.
+    pub fn primary_key(columns: &str) -> Key {
+        Key::new(KeyKind::PrimaryKey, columns)
+    }
+
+    /// A unique constraint on the comma separated `columns`.
+    pub fn unique(columns: &str) -> Key {
+        Key::new(KeyKind::Unique, columns)
+    }
+
+    /// The foreign key `column:table.column`, referencing `id` when the
+    /// referenced column is left out, e.g. `org_id:orgs`.
+    pub fn foreign_key(spec: &str) -> Option<Key> {
+        let (column, reference) = spec.split_once(':')?;
+        let (table, referenced_column) = reference.split_once('.').unwrap_or((reference, "id"));
+        if column.trim().is_empty() || table.trim().is_empty() {
+            return None;
+        }
+        Some(Key {
+            referenced_table: Some(table.trim().to_owned()),
+            referenced_column: Some(referenced_column.trim().to_owned()),
+            ..Key::new(KeyKind::ForeignKey, column)
+        })
+    }
+
+    fn new(kind: KeyKind, columns: &str) -> Key {
+        Key {
+            kind,
+            columns: split_columns(columns),
+            referenced_table: None,
+            referenced_column: None,
+        }
+    }
+}
+
+/// The `created_at`, `updated_at` and `created_by` columns `--audit` adds
+/// to a new table, or `None` where they have no types.
+#[cfg_attr(not(feature = "fs"), allow(dead_code))]
+pub(crate) fn audit_columns(language: Language, dialect: Dialect) -> Option<[&'static str; 3]> {
+    let columns = match (language, dialect) {
+        (Language::Sql, Dialect::Postgres) => [
+            "created_at:TIMESTAMPTZ:NOT NULL DEFAULT now()",
+            "updated_at:TIMESTAMPTZ:NOT NULL DEFAULT now()",
+            "created_by:TEXT",
+        ],
+        (Language::Sql, Dialect::Mysql) => [
+            "created_at:TIMESTAMP:NOT NULL DEFAULT CURRENT_TIMESTAMP",
+            "updated_at:TIMESTAMP:NOT NULL DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP",
+            "created_by:VARCHAR(255)",
+        ],
+        (Language::Sql, Dialect::Mssql) => [
+            "created_at:DATETIME2:NOT NULL DEFAULT SYSUTCDATETIME()",
+            "updated_at:DATETIME2:NOT NULL DEFAULT SYSUTCDATETIME()",
+            "created_by:NVARCHAR(255)",
+        ],
+        (Language::Sql, Dialect::Sqlite) => [
+            "created_at:TEXT:NOT NULL DEFAULT CURRENT_TIMESTAMP",
+            "updated_at:TEXT:NOT NULL DEFAULT CURRENT_TIMESTAMP",
+            "created_by:TEXT",
+        ],
+        (Language::Cql, _) => [
+            "created_at:timestamp",
+            "updated_at:timestamp",
+            "created_by:text",
+        ],
+        (Language::Surql, _) => [
+            "created_at:datetime:DEFAULT time::now()",
+            "updated_at:datetime:VALUE time::now()",
+            "created_by:option<string>",
+        ],
+        (Language::Cypher, _) => return None,
+    };
+    Some(columns)
+}
+
+/// The columns of a comma separated `--column`. Commas in parentheses or
+/// quotes, as in `numeric(10,2)` or `default 'a,b'`, are part of a column.
+pub(crate) fn split_columns(column: &str) -> Vec<String> {
+    let mut columns = Vec::new();
+    let (mut depth, mut quoted, mut start) = (0, false, 0);
+    for (i, c) in column.char_indices() {
+        match c {
+            '\'' => quoted = !quoted,
+            '(' if !quoted => depth += 1,
+            ')' if !quoted => depth -= 1,
+            ',' if !quoted && depth == 0 => {
+                columns.push(&column[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    columns.push(&column[start..]);
+    columns
+        .into_iter()
+        .map(str::trim)
+        .filter(|x| !x.is_empty())
+        .map(ToOwned::to_owned)
+        .collect()
+}
+
+/// The name of the column `spec`, without its type and constraints.
+pub(crate) fn column_name(spec: &str) -> &str {
+    spec.split_once(':').map_or(spec, |(name, _)| name).trim()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn specs_split_into_name_type_and_constraints() {
+        assert_eq!(
+            Column::parse("age:int:not null default 0"),
+            Column {
+                name: "age".to_owned(),
+                data_type: Some("int".to_owned()),
+                constraints: Some("not null default 0".to_owned()),
+            }
+        );
+        assert_eq!(
+            Column::parse("day : date : default now()::date").constraints,
+            Some("default now()::date".to_owned())
+        );
+        assert_eq!(Column::parse("email").data_type, None);
+        assert_eq!(Column::parse("email::unique").data_type, None);
+        assert_eq!(column_name("age:int"), "age");
+        assert_eq!(column_name("email"), "email");
+    }
+
+    #[test]
+    fn foreign_keys_reference_id_by_default() {
+        let key = Key::foreign_key("org_id:orgs").unwrap();
+        assert_eq!(key.kind, KeyKind::ForeignKey);
+        assert_eq!(key.columns, ["org_id"]);
+        assert_eq!(key.referenced_table.as_deref(), Some("orgs"));
+        assert_eq!(key.referenced_column.as_deref(), Some("id"));
+        let key = Key::foreign_key("owner:users.uuid").unwrap();
+        assert_eq!(key.referenced_column.as_deref(), Some("uuid"));
+        assert_eq!(Key::foreign_key("org_id"), None);
+        assert_eq!(Key::foreign_key(":orgs"), None);
+        assert_eq!(Key::primary_key("a, b").columns, ["a", "b"]);
+    }
+
+    #[test]
+    fn audit_columns_have_types_where_they_exist() {
+        for dialect in Dialect::ALL {
+            let columns = audit_columns(Language::Sql, dialect).unwrap();
+            let columns = columns.map(Column::parse);
+            assert!(columns.iter().all(|x| x.data_type.is_some()));
+            assert_eq!(
+                columns.map(|x| x.name),
+                ["created_at", "updated_at", "created_by"]
+            );
+        }
+        assert_eq!(audit_columns(Language::Cypher, Dialect::Postgres), None);
+    }
+
+    #[test]
+    fn commas_in_types_and_defaults_do_not_split() {
+        assert_eq!(
+            split_columns("price:numeric(10,2), tag:text:default 'a,b',,name"),
+            ["price:numeric(10,2)", "tag:text:default 'a,b'", "name"]
+        );
+    }
+}
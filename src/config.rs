@@ -0,0 +1,677 @@
+use crate::dialect::Dialect;
+use crate::error::GenError;
+use crate::identifier::{Case, Quoting};
+use crate::language::Language;
+use crate::Operation;
+use chrono::{FixedOffset, Local, NaiveDate, NaiveDateTime, Utc};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The marker file that makes a directory a gen root. Its content, if any, is
+/// the root's TOML configuration, unless `gen.toml` next to it holds it.
+pub const ROOT_FILE: &str = ".gen_root";
+
+/// Subdirectories of the output directory that forward and rollback
+/// migrations go into with `up_down_directories`.
+pub const UP_DIR: &str = "up";
+pub const DOWN_DIR: &str = "down";
+
+/// What ends the file stem of a rollback written next to its migration,
+/// as in `... - create table users.down.sql`.
+pub const DOWN_SUFFIX: &str = ".down";
+
+/// What ends the file stem of a forward migration named the migrate way, as
+/// in `20240612093005_create_table_users.up.sql`.
+pub const UP_SUFFIX: &str = ".up";
+
+/// File next to the marker that can hold the root's configuration instead.
+pub const CONFIG_FILE: &str = "gen.toml";
+
+/// Directory under the root whose templates replace the built-in ones, laid
+/// out like them: `create_table.tmpl` for SQL, `cql/create_table.tmpl` for
+/// CQL and `pgtap/create_table.tmpl` for tests.
+pub const TEMPLATES_DIR: &str = "templates";
+
+/// Digits of the index when the root does not set `index_width`.
+pub const DEFAULT_INDEX_WIDTH: usize = 2;
+
+/// Default directory for pgTAP tests.
+pub const TEST_DIR: &str = "test";
+
+/// Settings shared by everyone generating migrations under a root.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// Directory, relative to the root, that migrations are written to. The
+    /// root itself when unset.
+    pub output_dir: Option<PathBuf>,
+    pub layout: Layout,
+    /// Schema of the migrations generated without `--schema`.
+    pub schema: Option<String>,
+    /// The time zone migrations are dated in: `local` unless set, `UTC` or
+    /// an offset like `+05:30`. Named zones change their offset over the
+    /// year and are not supported.
+    pub timezone: Timezone,
+    /// What new migrations are written in, `sql` unless set.
+    pub language: Language,
+    /// What SQL migrations are written for, `postgres` unless set.
+    pub dialect: Dialect,
+    /// Templates shared between roots, used where the root has none of its
+    /// own in `templates/`: a directory relative to the root or a git URL,
+    /// cloned into `.gen_packs/` the first time it is needed and refreshed
+    /// with `template update`.
+    pub template_pack: Option<String>,
+    /// Write forward migrations under `up/` and a rollback with the same file
+    /// name under `down/`.
+    pub up_down_directories: bool,
+    /// Render a rollback with every migration, as with `--with-down`: into
+    /// `down/` with `up_down_directories`, next to the migration as
+    /// `<name>.down.<extension>` otherwise.
+    pub with_down: bool,
+    /// Give every new table `created_at`, `updated_at` and `created_by`
+    /// columns, as with `--audit`.
+    pub audit_columns: bool,
+    /// Put migrations generated with a schema into a subdirectory named after
+    /// it.
+    pub schema_directories: bool,
+    pub numbering: Numbering,
+    /// Digits of the index after the date, 2 to 4; 2 when unset. A day has
+    /// at most as many migrations as the digits hold.
+    pub index_width: Option<usize>,
+    pub naming: Naming,
+    /// The name of new migrations, a template with the migration's tokens and
+    /// `--var` variables plus `date`, `index` (`index_width` digits),
+    /// `operation`, `description` and `extension`, e.g.
+    /// `{date}{index}_{description | snake}.{extension}`.
+    /// It has to start with `{date}{index}` and a space, `_`, `-` or `.` so
+    /// the migrations can be numbered; `{date}{index} - {description}.{extension}`
+    /// when unset. Migrate names ignore it.
+    pub file_name_format: Option<String>,
+    /// Services of a monorepo that migrations can be generated for. Every
+    /// service is numbered on its own; any name is accepted when empty.
+    pub services: Vec<String>,
+    pub service_prefix: ServicePrefix,
+    /// Record every generated migration and its checksum in `migrations.json`
+    /// at the root, for `verify --checksums`.
+    pub manifest: bool,
+    /// Regenerate `MIGRATIONS.md` at the root after every generation.
+    pub index_document: bool,
+    /// Keep a `LATEST` pointer at the root to the newest migration.
+    pub latest: Option<Latest>,
+    /// Stage every file a generation writes with `git add`.
+    pub git_add: bool,
+    /// A regular expression finding the ticket a migration is for in the
+    /// name of the branch checked out, e.g. `[A-Z]+-[0-9]+`, when not given
+    /// with `--ticket`. Its first group is the ticket if it has one.
+    pub ticket_pattern: Option<String>,
+    /// Fail generations without a ticket, given or found in the branch name.
+    pub require_ticket: bool,
+    /// How table, schema and column names are cased before migrations are
+    /// named and rendered: `snake_case`, `lower` or `preserve`, as given,
+    /// unless set.
+    pub identifier_case: Case,
+    /// Which identifiers SQL migrations quote: `always`, `when-needed` for
+    /// reserved keywords and names with upper case letters in PostgreSQL,
+    /// or `never`. Unset, PostgreSQL quotes none and the other dialects all
+    /// of them.
+    pub quoting: Option<Quoting>,
+    /// Commands run in the root on every file a generation creates, in turn,
+    /// with `{file}` replaced by its quoted path, e.g. `sqlfluff fix {file}`.
+    /// One failing fails the generation, after the files were written.
+    pub post_create: Vec<String>,
+    /// Open every generated migration in the editor when run in a terminal,
+    /// as with `--edit`.
+    pub open_editor: bool,
+    /// The command migrations are opened with, their paths appended, e.g.
+    /// `code --wait`; `$VISUAL` or else `$EDITOR` when unset.
+    pub editor: Option<String>,
+    /// Message of the commit `--commit` makes, a template with the tokens of
+    /// the migration's own plus `description`, the descriptive part of its
+    /// file name. `migration: {description}` when unset.
+    pub commit_message: Option<String>,
+    /// Also write a pgTAP test for every SQL migration whose operation has
+    /// one, as with `--with-test`.
+    pub with_test: bool,
+    /// Directory, relative to the root, that pgTAP tests go into; `test`
+    /// when unset, where `pg_prove -r test` finds them.
+    pub test_dir: Option<PathBuf>,
+    /// Append every generation, renumbering and archiving to
+    /// `gen.audit.jsonl` at the root.
+    pub audit: bool,
+    /// Composite operations `preset` generates into one migration, by
+    /// name.
+    pub presets: BTreeMap<String, Preset>,
+    /// How `--env` restricts a migration to some environments.
+    pub env_guard: EnvGuard,
+    /// The setting `env_guard = "setting"` reads the environment from;
+    /// `app.env` when unset.
+    pub env_setting: Option<String>,
+}
+
+impl Config {
+    /// Reads the root's configuration, from `gen.toml` when there is one and
+    /// from the marker file otherwise. An empty marker file configures
+    /// nothing.
+    pub fn load(root: &Path) -> Result<Self, GenError> {
+        let marker_path = root.join(ROOT_FILE);
+        let marker = table(&marker_path, &fs::read_to_string(&marker_path)?)?;
+        let path = root.join(CONFIG_FILE);
+        let (path, settings) = match fs::read_to_string(&path) {
+            Ok(content) if marker.is_empty() => {
+                let settings = table(&path, &content)?;
+                (path, settings)
+            }
+            Ok(_) => {
+                return Err(GenError::MalformedConfig(
+                    path,
+                    format!("{ROOT_FILE} has settings too, keep them in one of the two"),
+                ))
+            }
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => (marker_path, marker),
+            Err(error) => return Err(error.into()),
+        };
+        let config = Config::deserialize(settings)
+            .map_err(|x| GenError::MalformedConfig(path.clone(), x.to_string()))?;
+        if !(2..=4).contains(&config.index_width()) {
+            return Err(GenError::MalformedConfig(
+                path,
+                "index_width has to be 2, 3 or 4".to_owned(),
+            ));
+        }
+        Ok(config)
+    }
+
+    /// Digits of the index after the date.
+    pub fn index_width(&self) -> usize {
+        self.index_width.unwrap_or(DEFAULT_INDEX_WIDTH)
+    }
+
+    /// The directory under `output_dir` that a migration dated `day` for
+    /// `schema` goes into.
+    pub fn directory(&self, output_dir: &Path, day: NaiveDate, schema: Option<&str>) -> PathBuf {
+        match self.schema_directory(output_dir, schema) {
+            Some(directory) => self.layout.directory(&directory, day),
+            None => self.layout.directory(output_dir, day),
+        }
+    }
+
+    /// Where forward migrations are laid out, given the output directory.
+    pub fn up_directory(&self, output_dir: &Path) -> PathBuf {
+        if self.up_down_directories {
+            output_dir.join(UP_DIR)
+        } else {
+            output_dir.to_path_buf()
+        }
+    }
+
+    /// Where rollbacks are laid out, if they are written at all.
+    pub fn down_directory(&self, output_dir: &Path) -> Option<PathBuf> {
+        self.up_down_directories.then(|| output_dir.join(DOWN_DIR))
+    }
+
+    /// Whether `path`, a file under `root`, is a rollback rather than a
+    /// forward migration. Services keep their own `down/`, so any `down`
+    /// directory counts, as does any `.down` file.
+    pub fn is_rollback(&self, root: &Path, path: &Path) -> bool {
+        let relative = path.strip_prefix(root).unwrap_or(path);
+        let stem = path.file_stem().and_then(|x| x.to_str()).unwrap_or("");
+        stem.ends_with(DOWN_SUFFIX)
+            || self.up_down_directories && relative.components().any(|x| x.as_os_str() == DOWN_DIR)
+    }
+
+    /// The rollback that goes with the forward migration at `path`, a file
+    /// under `root`: its twin under down/ when the root keeps them in up/
+    /// and down/, and its `.down` sibling otherwise. Services have their
+    /// own, so this looks at the path rather than at the output directory.
+    /// A `.up` migration's rollback is always its `.down` sibling.
+    pub fn rollback(&self, root: &Path, path: &Path) -> Option<PathBuf> {
+        let stem = path.file_stem()?.to_str()?;
+        let up = stem.strip_suffix(UP_SUFFIX);
+        if !self.up_down_directories || up.is_some() {
+            let stem = up.unwrap_or(stem);
+            let extension = path.extension()?.to_str()?;
+            return Some(path.with_file_name(format!("{stem}{DOWN_SUFFIX}.{extension}")));
+        }
+        let relative = path.strip_prefix(root).ok()?;
+        let components = relative.components().collect::<Vec<_>>();
+        let up = components.iter().rposition(|x| x.as_os_str() == UP_DIR)?;
+        let mut rollback = root.to_path_buf();
+        for (position, component) in components.iter().enumerate() {
+            match position == up {
+                true => rollback.push(DOWN_DIR),
+                false => rollback.push(component),
+            }
+        }
+        Some(rollback)
+    }
+
+    /// The directory whose migrations a new one for `schema` is numbered
+    /// against, and whose counter file it uses.
+    pub fn numbering_root(&self, root: &Path, output_dir: &Path, schema: Option<&str>) -> PathBuf {
+        match self.numbering {
+            Numbering::Root => root.to_path_buf(),
+            Numbering::PerSchema => self
+                .schema_directory(output_dir, schema)
+                .unwrap_or_else(|| root.to_path_buf()),
+            Numbering::PerDirectory => self
+                .schema_directory(output_dir, schema)
+                .unwrap_or_else(|| output_dir.to_path_buf()),
+        }
+    }
+
+    /// Where pgTAP tests are written.
+    pub fn test_directory(&self, root: &Path) -> PathBuf {
+        root.join(self.test_dir.as_deref().unwrap_or(Path::new(TEST_DIR)))
+    }
+
+    fn schema_directory(&self, output_dir: &Path, schema: Option<&str>) -> Option<PathBuf> {
+        let separate = self.schema_directories
+            || self.layout == Layout::BySchema
+            || self.numbering == Numbering::PerSchema;
+        schema
+            .filter(|_| separate)
+            .map(|schema| output_dir.join(schema))
+    }
+}
+
+fn table(path: &Path, content: &str) -> Result<toml::Table, GenError> {
+    toml::from_str(content).map_err(|x| GenError::MalformedConfig(path.to_owned(), x.to_string()))
+}
+
+/// The time zone migrations are dated in.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "String")]
+pub enum Timezone {
+    /// The machine's own.
+    #[default]
+    Local,
+    Utc,
+    Fixed(FixedOffset),
+}
+
+impl Timezone {
+    /// The current date and time in the time zone.
+    pub fn now(self) -> NaiveDateTime {
+        match self {
+            Timezone::Local => Local::now().naive_local(),
+            Timezone::Utc => Utc::now().naive_utc(),
+            Timezone::Fixed(offset) => Utc::now().with_timezone(&offset).naive_local(),
+        }
+    }
+}
+
+impl TryFrom<String> for Timezone {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        match value.as_str() {
+            "local" => Ok(Timezone::Local),
+            "UTC" | "utc" | "Z" => Ok(Timezone::Utc),
+            offset => offset
+                .parse::<FixedOffset>()
+                .map(Timezone::Fixed)
+                .map_err(|_| format!("{offset:?} is neither local, UTC nor an offset like +05:30")),
+        }
+    }
+}
+
+/// Several steps generated as one migration for a single table, e.g. a
+/// `new-entity` that creates the table, adds audit columns and grants access:
+///
+/// ```toml
+/// [presets.new-entity]
+/// steps = [
+///     { operation = "create-table" },
+///     { operation = "add-column", column = "created_at" },
+///     { template = "GRANT SELECT ON {schema_name}{dot}{table_name} TO reader;" },
+/// ]
+/// ```
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Preset {
+    /// Rendered in order, separated by blank lines.
+    pub steps: Vec<Step>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged, deny_unknown_fields)]
+pub enum Step {
+    /// A built-in operation, on the step's own column or else the one given
+    /// on the command line.
+    Operation {
+        operation: Operation,
+        column: Option<String>,
+    },
+    /// A template with the tokens of an operation's.
+    Template { template: String },
+}
+
+/// How a service's migrations are told apart from the others'.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ServicePrefix {
+    /// In a directory named after the service, e.g. `auth/2024061201 - ...`.
+    #[default]
+    Directory,
+    /// With the service in front of the file name, e.g.
+    /// `auth-2024061201 - ...`.
+    FileName,
+    /// Both of the above.
+    Both,
+}
+
+impl ServicePrefix {
+    pub fn directory(self) -> bool {
+        matches!(self, ServicePrefix::Directory | ServicePrefix::Both)
+    }
+
+    pub fn file_name(self) -> bool {
+        matches!(self, ServicePrefix::FileName | ServicePrefix::Both)
+    }
+}
+
+/// How a migration generated for some environments only is marked.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum EnvGuard {
+    /// Between `-- env: prod` and `-- end env` comments, for a runner that
+    /// picks the blocks of its environment.
+    #[default]
+    Comment,
+    /// In a PL/pgSQL block that only runs when the database's setting names
+    /// one of the environments. Other languages fall back to comments.
+    Setting,
+}
+
+impl EnvGuard {
+    /// Wraps `body`, written in `language`, so it only applies in `envs`.
+    /// Without environments it is left as it is.
+    pub fn wrap(self, language: Language, setting: &str, envs: &[String], body: &str) -> String {
+        if envs.is_empty() {
+            return body.to_owned();
+        }
+        match self {
+            EnvGuard::Setting if language == Language::Sql => {
+                let envs = envs
+                    .iter()
+                    .map(|x| format!("'{}'", x.replace('\'', "''")))
+                    .collect::<Vec<_>>();
+                let body = body
+                    .lines()
+                    .map(|x| match x.is_empty() {
+                        true => String::new(),
+                        false => format!("        {x}"),
+                    })
+                    .collect::<Vec<_>>();
+                format!(
+                    "DO $env$\nBEGIN\n    IF current_setting('{setting}', true) IN ({}) THEN\n{}\n    END IF;\nEND\n$env$;\n",
+                    envs.join(", "),
+                    body.join("\n")
+                )
+            }
+            _ => {
+                let comment = language.comment();
+                let body = body.trim_end_matches('\n');
+                format!(
+                    "{comment} env: {}\n{body}\n{comment} end env\n",
+                    envs.join(", ")
+                )
+            }
+        }
+    }
+}
+
+/// How `LATEST` points at the newest migration.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Latest {
+    /// A file holding the migration's path relative to the root.
+    File,
+    /// A relative symlink, or a file where symlinks are not supported.
+    Symlink,
+}
+
+/// Which migrations share a daily index space.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Numbering {
+    /// Every migration under the root.
+    #[default]
+    Root,
+    /// Each schema's directory on its own, which implies
+    /// `schema_directories`. Migrations without a schema are still numbered
+    /// against the whole root.
+    PerSchema,
+    /// Each output directory on its own, be it the root's, a service's or
+    /// one given with `--out` or `--here`, and each schema's directory in
+    /// it. The layout's date subdirectories are part of their directory.
+    PerDirectory,
+}
+
+/// How new migrations are named.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Naming {
+    /// `YYYYMMDDNN - description.sql`, numbered within the day.
+    #[default]
+    Gen,
+    /// `YYYYMMDDHHMMSS_description.up.sql`, stamped to the second, with a
+    /// `.down.sql` rollback next to it, as golang-migrate and dbmate read
+    /// them.
+    Migrate,
+}
+
+impl Naming {
+    pub fn name(self) -> &'static str {
+        match self {
+            Naming::Gen => "gen",
+            Naming::Migrate => "migrate",
+        }
+    }
+}
+
+/// Where inside the output directory a new migration is written.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Layout {
+    /// Directly in the output directory.
+    #[default]
+    Flat,
+    /// In a `YYYY` subdirectory for the migration's year.
+    ByYear,
+    /// In a `YYYY/MM` subdirectory for the migration's month.
+    ByMonth,
+    /// In a subdirectory named after the migration's schema, as with
+    /// `schema_directories`.
+    BySchema,
+}
+
+impl Layout {
+    /// The subdirectory of `output_dir` a migration dated `day` goes into.
+    /// Numbering is unaffected: the root is always scanned as a whole.
+    pub fn directory(self, output_dir: &Path, day: NaiveDate) -> PathBuf {
+        match self {
+            Layout::Flat | Layout::BySchema => output_dir.to_path_buf(),
+            Layout::ByYear => output_dir.join(day.format("%Y").to_string()),
+            Layout::ByMonth => output_dir
+                .join(day.format("%Y").to_string())
+                .join(day.format("%m").to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layout() {
+        let config: Config = toml::from_str("layout = \"by-month\"").unwrap();
+        assert_eq!(config.layout, Layout::ByMonth);
+        assert_eq!(toml::from_str::<Config>("").unwrap().layout, Layout::Flat);
+
+        let day = NaiveDate::from_ymd_opt(2024, 6, 12).unwrap();
+        assert_eq!(
+            Layout::ByMonth.directory(Path::new("root"), day),
+            Path::new("root/2024/06")
+        );
+        assert_eq!(
+            Layout::ByYear.directory(Path::new("root"), day),
+            Path::new("root/2024")
+        );
+        assert_eq!(
+            Layout::Flat.directory(Path::new("root"), day),
+            Path::new("root")
+        );
+    }
+
+    #[test]
+    fn test_timezone() {
+        let config: Config = toml::from_str("timezone = \"UTC\"").unwrap();
+        assert_eq!(config.timezone, Timezone::Utc);
+        let config: Config = toml::from_str("timezone = \"-08:00\"").unwrap();
+        let offset = FixedOffset::west_opt(8 * 3600).unwrap();
+        assert_eq!(config.timezone, Timezone::Fixed(offset));
+        assert_eq!(Config::default().timezone, Timezone::Local);
+        assert!(toml::from_str::<Config>("timezone = \"Europe/Berlin\"").is_err());
+    }
+
+    #[test]
+    fn test_presets() {
+        let config: Config = toml::from_str(
+            "[presets.new-entity]\n\
+             steps = [\n\
+                 { operation = \"create-table\" },\n\
+                 { operation = \"add-column\", column = \"created_at\" },\n\
+                 { template = \"GRANT SELECT ON {table_name} TO reader;\" },\n\
+             ]",
+        )
+        .unwrap();
+        assert_eq!(
+            config.presets["new-entity"].steps,
+            [
+                Step::Operation {
+                    operation: Operation::CreateTable,
+                    column: None
+                },
+                Step::Operation {
+                    operation: Operation::AddColumn,
+                    column: Some("created_at".to_owned())
+                },
+                Step::Template {
+                    template: "GRANT SELECT ON {table_name} TO reader;".to_owned()
+                },
+            ]
+        );
+        assert!(
+            toml::from_str::<Config>("[presets.x]\nsteps = [{ operation = \"nope\" }]").is_err()
+        );
+    }
+
+    #[test]
+    fn test_env_guard() {
+        let envs = ["prod".to_owned(), "staging".to_owned()];
+        let body = "CREATE TABLE users(\n\n);";
+        assert_eq!(
+            EnvGuard::Comment.wrap(Language::Sql, "app.env", &envs, body),
+            "-- env: prod, staging\nCREATE TABLE users(\n\n);\n-- end env\n"
+        );
+        assert_eq!(
+            EnvGuard::Setting.wrap(Language::Sql, "app.env", &envs, body),
+            "DO $env$\nBEGIN\n    IF current_setting('app.env', true) IN ('prod', 'staging') THEN\n\
+             \x20       CREATE TABLE users(\n\n        );\n    END IF;\nEND\n$env$;\n"
+        );
+        assert_eq!(
+            EnvGuard::Setting.wrap(Language::Cypher, "app.env", &envs[..1], "RETURN 1;"),
+            "// env: prod\nRETURN 1;\n// end env\n"
+        );
+        assert_eq!(
+            EnvGuard::Setting.wrap(Language::Sql, "app.env", &[], body),
+            body
+        );
+    }
+
+    #[test]
+    fn test_schema_directories() {
+        let day = NaiveDate::from_ymd_opt(2024, 6, 12).unwrap();
+        let config = Config {
+            layout: Layout::ByMonth,
+            schema_directories: true,
+            ..Config::default()
+        };
+        assert_eq!(
+            config.directory(Path::new("root"), day, Some("app")),
+            Path::new("root/app/2024/06")
+        );
+        assert_eq!(
+            config.directory(Path::new("root"), day, None),
+            Path::new("root/2024/06")
+        );
+        let config = Config {
+            layout: Layout::BySchema,
+            ..Config::default()
+        };
+        assert_eq!(
+            config.directory(Path::new("root"), day, Some("app")),
+            Path::new("root/app")
+        );
+    }
+
+    #[test]
+    fn test_numbering_root() {
+        let root = Path::new("root");
+        let output_dir = Path::new("root/migrations");
+        let mut config = Config::default();
+        assert_eq!(config.numbering_root(root, output_dir, Some("app")), root);
+
+        config.numbering = Numbering::PerSchema;
+        assert_eq!(
+            config.numbering_root(root, output_dir, Some("app")),
+            Path::new("root/migrations/app")
+        );
+        assert_eq!(config.numbering_root(root, output_dir, None), root);
+        assert_eq!(
+            config.directory(output_dir, NaiveDate::MIN, Some("app")),
+            Path::new("root/migrations/app")
+        );
+
+        config.numbering = Numbering::PerDirectory;
+        assert_eq!(config.numbering_root(root, output_dir, None), output_dir);
+        assert_eq!(
+            config.numbering_root(root, output_dir, Some("app")),
+            output_dir
+        );
+        config.schema_directories = true;
+        assert_eq!(
+            config.numbering_root(root, output_dir, Some("app")),
+            Path::new("root/migrations/app")
+        );
+    }
+
+    #[test]
+    fn test_rollbacks() {
+        let root = Path::new("root");
+        let migration = Path::new("root/up/2024061201 - create table users.sql");
+        let sibling = Path::new("root/up/2024061201 - create table users.down.sql");
+        let mut config = Config::default();
+        assert_eq!(config.rollback(root, migration).unwrap(), sibling);
+        assert!(config.is_rollback(root, sibling));
+        assert!(!config.is_rollback(root, Path::new("root/down/x.sql")));
+
+        config.up_down_directories = true;
+        assert_eq!(
+            config.rollback(root, migration).unwrap(),
+            Path::new("root/down/2024061201 - create table users.sql")
+        );
+        assert!(config.is_rollback(root, Path::new("root/down/x.sql")));
+        assert!(!config.is_rollback(root, migration));
+        assert_eq!(
+            config
+                .rollback(root, Path::new("root/up/20240612093005_x.up.sql"))
+                .unwrap(),
+            Path::new("root/up/20240612093005_x.down.sql")
+        );
+    }
+}
@@ -0,0 +1,61 @@
+use crate::error::GenError;
+use chrono::NaiveDate;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File at the root that records the last allocated `YYYYMMDDNN` prefix.
+pub const COUNTER_FILE: &str = ".gen_counter";
+
+/// The last allocated date and index, as tracked by the counter file.
+///
+/// Keeping this in a committed file lets generation skip scanning the tree
+/// entirely, which matters for repositories with many thousands of migrations.
+pub struct Counter {
+    day: NaiveDate,
+    index: i32,
+}
+
+impl Counter {
+    pub fn new(day: NaiveDate, index: i32) -> Self {
+        Counter { day, index }
+    }
+
+    /// Reads the counter file, or returns `None` if the root does not use one.
+    pub fn load(root: &Path) -> Result<Option<Self>, GenError> {
+        let path = root.join(COUNTER_FILE);
+        if !path.exists() {
+            return Ok(None);
+        }
+        let content = fs::read_to_string(&path)?;
+        let content = content.trim();
+        let counter = content
+            .get(..8)
+            .and_then(|x| NaiveDate::parse_from_str(x, "%Y%m%d").ok())
+            .zip(content.get(8..).and_then(|x| x.parse::<i32>().ok()))
+            .map(|(day, index)| Counter { day, index })
+            .ok_or(GenError::MalformedCounter(path))?;
+        Ok(Some(counter))
+    }
+
+    /// Same contract as `MigrationIndex::last_index_for`.
+    pub fn last_index_for(&self, day: NaiveDate) -> Result<Option<i32>, GenError> {
+        match self.day.cmp(&day) {
+            std::cmp::Ordering::Less => Ok(None),
+            std::cmp::Ordering::Equal => Ok(Some(self.index)),
+            std::cmp::Ordering::Greater => Err(GenError::FutureDatedFile(self.day)),
+        }
+    }
+
+    /// Writes the counter through a temporary file and a rename, so a reader
+    /// never observes a partially written value.
+    pub fn store(&self, root: &Path) -> Result<(), GenError> {
+        let path = root.join(COUNTER_FILE);
+        let temporary = PathBuf::from(format!("{}.tmp", path.display()));
+        fs::write(
+            &temporary,
+            format!("{}{:02}\n", self.day.format("%Y%m%d"), self.index),
+        )?;
+        fs::rename(&temporary, &path)?;
+        Ok(())
+    }
+}
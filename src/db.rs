@@ -0,0 +1,86 @@
+//! Async access to a live database for the features that need one. Local
+//! generation never goes through here and stays synchronous.
+
+use crate::error::GenError;
+use sqlx::any::AnyPoolOptions;
+use sqlx::{AnyPool, AssertSqlSafe};
+use std::future::Future;
+use std::time::Duration;
+
+/// How connections to the database are pooled and bounded in time.
+pub struct DatabaseOptions {
+    pub max_connections: u32,
+    /// How long to wait for a connection from the pool, including connecting.
+    pub connect_timeout: Duration,
+    /// How long a single statement may run.
+    pub query_timeout: Duration,
+}
+
+impl Default for DatabaseOptions {
+    fn default() -> Self {
+        DatabaseOptions {
+            max_connections: 4,
+            connect_timeout: Duration::from_secs(10),
+            query_timeout: Duration::from_secs(60),
+        }
+    }
+}
+
+/// A pool of connections to the database at a `postgres://`, `mysql://` or
+/// `sqlite:` URL.
+pub struct Database {
+    pool: AnyPool,
+    query_timeout: Duration,
+}
+
+impl Database {
+    pub async fn connect(url: &str, options: &DatabaseOptions) -> Result<Self, GenError> {
+        sqlx::any::install_default_drivers();
+        let pool = AnyPoolOptions::new()
+            .max_connections(options.max_connections)
+            .acquire_timeout(options.connect_timeout)
+            .connect(url)
+            .await?;
+        Ok(Database {
+            pool,
+            query_timeout: options.query_timeout,
+        })
+    }
+
+    pub fn pool(&self) -> &AnyPool {
+        &self.pool
+    }
+
+    /// Runs a database call, failing with `GenError::Timeout` if it takes
+    /// longer than the configured query timeout.
+    pub async fn timed<T>(
+        &self,
+        call: impl Future<Output = Result<T, sqlx::Error>>,
+    ) -> Result<T, GenError> {
+        match tokio::time::timeout(self.query_timeout, call).await {
+            Ok(result) => Ok(result?),
+            Err(_) => Err(GenError::Timeout(self.query_timeout)),
+        }
+    }
+
+    /// Executes `sql`, which may hold several statements, and returns the
+    /// number of affected rows. The text comes from migration files and is run
+    /// as written.
+    pub async fn execute(&self, sql: &str) -> Result<u64, GenError> {
+        let sql = sqlx::raw_sql(AssertSqlSafe(sql.to_owned()));
+        let result = self.timed(sql.execute(&self.pool)).await?;
+        Ok(result.rows_affected())
+    }
+
+    pub async fn close(self) {
+        self.pool.close().await;
+    }
+}
+
+/// Drives an async database call to completion from synchronous code.
+pub fn block_on<F: Future>(future: F) -> Result<F::Output, GenError> {
+    let runtime = tokio::runtime::Builder::new_multi_thread()
+        .enable_all()
+        .build()?;
+    Ok(runtime.block_on(future))
+}
@@ -0,0 +1,50 @@
+//! The SQL databases migrations can be written for. They differ in quoting,
+//! in which statements take `IF NOT EXISTS` and in how identity columns are
+//! declared, so each has its own SQL templates; the other languages have one
+//! set each.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Dialect {
+    /// PostgreSQL, the only one with pgTAP tests.
+    #[default]
+    Postgres,
+    /// MySQL and MariaDB.
+    Mysql,
+    /// Microsoft SQL Server.
+    Mssql,
+    Sqlite,
+}
+
+impl Dialect {
+    pub const ALL: [Dialect; 4] = [
+        Dialect::Postgres,
+        Dialect::Mysql,
+        Dialect::Mssql,
+        Dialect::Sqlite,
+    ];
+
+    /// The name the dialect is given on the command line and in the
+    /// configuration, e.g. `mssql`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Dialect::Postgres => "postgres",
+            Dialect::Mysql => "mysql",
+            Dialect::Mssql => "mssql",
+            Dialect::Sqlite => "sqlite",
+        }
+    }
+
+    /// `identifier` quoted the dialect's way, e.g. `[order]` for SQL Server.
+    pub fn quote(self, identifier: &str) -> String {
+        match self {
+            Dialect::Postgres | Dialect::Sqlite => {
+                format!("\"{}\"", identifier.replace('"', "\"\""))
+            }
+            Dialect::Mysql => format!("`{}`", identifier.replace('`', "``")),
+            Dialect::Mssql => format!("[{}]", identifier.replace(']', "]]")),
+        }
+    }
+}
@@ -0,0 +1,92 @@
+use crate::error::GenError;
+use crate::index::MigrationIndex;
+use crate::manifest::relative_path;
+use crate::naming::parse_file_name;
+use crate::operation::Operation;
+use std::fmt::Write;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Table of contents of every migration under the root, for reviewers.
+pub const INDEX_DOCUMENT: &str = "MIGRATIONS.md";
+
+/// Renders the table of contents of the migrations in `index`, oldest first.
+/// Files that do not follow the naming scheme are left out.
+pub fn render(root: &Path, index: &MigrationIndex) -> String {
+    let mut migrations = index
+        .files()
+        .filter_map(|path| {
+            let name = parse_file_name(path.file_name()?.to_str()?)?;
+            Some((
+                name.date,
+                name.index,
+                relative_path(root, path),
+                name.description,
+            ))
+        })
+        .collect::<Vec<_>>();
+    migrations.sort();
+
+    let mut document = String::from(
+        "# Migrations\n\n\
+         <!-- Generated by gen; edits are overwritten. -->\n\n\
+         | Date | Name | Operation | Objects |\n\
+         | --- | --- | --- | --- |\n",
+    );
+    for (date, _, path, description) in migrations {
+        let (operation, table, column) = Operation::from_file_name(description);
+        let objects = match (table, column) {
+            (Some(table), Some(column)) => format!("`{table}.{column}`"),
+            (Some(table), None) => format!("`{table}`"),
+            _ => String::new(),
+        };
+        writeln!(
+            document,
+            "| {date} | [{}](<{}>) | {} | {} |",
+            escape(description),
+            escape(&path),
+            operation.name(),
+            escape(&objects)
+        )
+        .unwrap();
+    }
+    document
+}
+
+/// Renders release notes for the migrations among `files`, added between the
+/// revisions `from` and `to`, grouped by day.
+pub fn render_changelog(from: &str, to: &str, files: &[PathBuf]) -> String {
+    let mut migrations = files
+        .iter()
+        .filter_map(|path| {
+            let name = parse_file_name(path.file_name()?.to_str()?)?;
+            Some((name.date, name.index, name.description))
+        })
+        .collect::<Vec<_>>();
+    migrations.sort();
+
+    let mut document = format!("# Migrations from {from} to {to}\n");
+    if migrations.is_empty() {
+        document.push_str("\nNo migrations were added.\n");
+    }
+    let mut last_date = None;
+    for (date, _, description) in migrations {
+        if last_date != Some(date) {
+            write!(document, "\n## {date}\n\n").unwrap();
+            last_date = Some(date);
+        }
+        writeln!(document, "- {description}").unwrap();
+    }
+    document
+}
+
+/// Rewrites `MIGRATIONS.md` at the root and returns its path.
+pub fn write(root: &Path, index: &MigrationIndex) -> Result<PathBuf, GenError> {
+    let path = root.join(INDEX_DOCUMENT);
+    fs::write(&path, render(root, index))?;
+    Ok(path)
+}
+
+fn escape(text: &str) -> String {
+    text.replace('|', "\\|")
+}
@@ -0,0 +1,146 @@
+//! Entity relationship diagrams of a schema model, for documentation that
+//! follows the migrations.
+
+use crate::schema::SchemaModel;
+use std::fmt::Write;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum Format {
+    /// A mermaid `erDiagram`.
+    #[default]
+    Mermaid,
+    /// A PlantUML diagram of `entity` elements.
+    Plantuml,
+}
+
+/// Draws every table of `model` with its columns, and a many-to-one link for
+/// every foreign key.
+pub fn render(model: &SchemaModel, format: Format) -> String {
+    match format {
+        Format::Mermaid => mermaid(model),
+        Format::Plantuml => plantuml(model),
+    }
+}
+
+fn mermaid(model: &SchemaModel) -> String {
+    let mut diagram = String::from("erDiagram\n");
+    for (name, table) in &model.tables {
+        writeln!(diagram, "    {}[\"{name}\"] {{", id(name)).unwrap();
+        for column in &table.columns {
+            // Attribute types are a single word of a few characters only.
+            let data_type = match column.data_type.as_str() {
+                "" => "unknown".to_owned(),
+                data_type => data_type
+                    .chars()
+                    .filter(|x| *x != ' ')
+                    .map(|x| match x.is_alphanumeric() || "-_[]()".contains(x) {
+                        true => x,
+                        false => '_',
+                    })
+                    .collect(),
+            };
+            let key = match column.references {
+                Some(_) => " FK",
+                None => "",
+            };
+            writeln!(diagram, "        {data_type} {}{key}", id(&column.name)).unwrap();
+        }
+        diagram.push_str("    }\n");
+    }
+    for (name, column, target) in foreign_keys(model) {
+        writeln!(
+            diagram,
+            "    {} }}o--|| {} : \"{column}\"",
+            id(name),
+            id(target)
+        )
+        .unwrap();
+    }
+    diagram
+}
+
+fn plantuml(model: &SchemaModel) -> String {
+    let mut diagram = String::from("@startuml\n");
+    for (name, table) in &model.tables {
+        writeln!(diagram, "entity \"{name}\" as {} {{", id(name)).unwrap();
+        for column in &table.columns {
+            match column.data_type.as_str() {
+                "" => writeln!(diagram, "  {}", column.name),
+                data_type => writeln!(diagram, "  {} : {data_type}", column.name),
+            }
+            .unwrap();
+        }
+        diagram.push_str("}\n");
+    }
+    for (name, column, target) in foreign_keys(model) {
+        writeln!(diagram, "{} }}o--|| {} : {column}", id(name), id(target)).unwrap();
+    }
+    diagram.push_str("@enduml\n");
+    diagram
+}
+
+/// Every foreign key as its table, column and referenced table, leaving out
+/// references to tables the model does not have.
+fn foreign_keys(model: &SchemaModel) -> impl Iterator<Item = (&str, &str, &str)> {
+    model.tables.iter().flat_map(move |(name, table)| {
+        table.columns.iter().filter_map(move |column| {
+            let target = &column.references.as_ref()?.table;
+            model.tables.contains_key(target).then_some((
+                name.as_str(),
+                column.name.as_str(),
+                target.as_str(),
+            ))
+        })
+    })
+}
+
+/// `name` as an identifier both formats accept, e.g. `app_users` for
+/// `app.users`.
+fn id(name: &str) -> String {
+    name.chars()
+        .map(|x| match x.is_alphanumeric() || x == '_' {
+            true => x,
+            false => '_',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model() -> SchemaModel {
+        let mut model = SchemaModel::default();
+        model.apply(
+            "create table app.users (id bigint primary key, name varchar(255));\n\
+             create table orders (id int, user_id bigint references app.users, \
+             total numeric(10, 2), other int references missing);",
+        );
+        model
+    }
+
+    #[test]
+    fn mermaid_draws_tables_and_foreign_keys() {
+        assert_eq!(
+            render(&model(), Format::Mermaid),
+            "erDiagram\n    \
+             app_users[\"app.users\"] {\n        bigint id\n        varchar(255) name\n    }\n    \
+             orders[\"orders\"] {\n        int id\n        bigint user_id FK\n        \
+             numeric(10_2) total\n        int other FK\n    }\n    \
+             orders }o--|| app_users : \"user_id\"\n"
+        );
+    }
+
+    #[test]
+    fn plantuml_draws_tables_and_foreign_keys() {
+        assert_eq!(
+            render(&model(), Format::Plantuml),
+            "@startuml\n\
+             entity \"app.users\" as app_users {\n  id : bigint\n  name : varchar(255)\n}\n\
+             entity \"orders\" as orders {\n  id : int\n  user_id : bigint\n  \
+             total : numeric(10, 2)\n  other : int\n}\n\
+             orders }o--|| app_users : user_id\n\
+             @enduml\n"
+        );
+    }
+}
@@ -0,0 +1,97 @@
+use chrono::NaiveDate;
+use std::path::PathBuf;
+
+/// Everything the library can fail with.
+#[derive(Debug, thiserror::Error)]
+pub enum GenError {
+    #[error("Could not find any gen root")]
+    RootNotFound,
+
+    #[error("found date {0:?} in future")]
+    FutureDatedFile(NaiveDate),
+
+    #[error("{0} is required")]
+    MissingArgument(&'static str),
+
+    #[error("template variable {0:?} is required, pass it with --var")]
+    MissingVariable(String),
+
+    #[error("index {0} does not fit in {1} digits, raise index_width")]
+    IndexOverflow(i32, usize),
+
+    #[error("{0:?} already exists, pass --force to overwrite it")]
+    AlreadyExists(PathBuf),
+
+    #[error("{0:?} is held by another generation, remove it if none is running")]
+    Locked(PathBuf),
+
+    #[error("unknown partial {0:?}, add templates/_{0}.tmpl")]
+    UnknownPartial(String),
+
+    #[error("post_create hook {0:?} failed: {1}")]
+    Hook(String, String),
+
+    #[error("{0:?} is not a valid identifier: {1}")]
+    InvalidIdentifier(String, String),
+
+    #[error("{0:?} is reserved in {1}, pass --allow-keyword to quote it")]
+    ReservedKeyword(String, &'static str),
+
+    #[error("malformed counter file {0:?}")]
+    MalformedCounter(PathBuf),
+
+    #[error("malformed config {0:?}: {1}")]
+    MalformedConfig(PathBuf, String),
+
+    #[error("malformed manifest {0:?}")]
+    MalformedManifest(PathBuf),
+
+    #[error("malformed history {0:?}")]
+    MalformedHistory(PathBuf),
+
+    #[error("malformed CSV: {0}")]
+    MalformedCsv(String),
+
+    #[error("cannot undo {0:?}: {1}")]
+    Undo(PathBuf, &'static str),
+
+    #[error("unknown service {0:?}")]
+    UnknownService(String),
+
+    #[error("unknown preset {0:?}")]
+    UnknownPreset(String),
+
+    #[error("{0} does not support {1}")]
+    Unsupported(&'static str, &'static str),
+
+    #[error("{0} problems found")]
+    Verification(usize),
+
+    #[error("migrations share a date and index: {0:?}, run renumber --collisions")]
+    CollisionDetected(Vec<PathBuf>),
+
+    #[error("git failed: {0}")]
+    Git(String),
+
+    #[error("template error: {0}")]
+    Template(#[from] tinytemplate::error::Error),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[cfg(feature = "archive")]
+    #[error("archive error: {0}")]
+    Archive(String),
+
+    #[cfg(feature = "plugins")]
+    #[error("plugin error: {0}")]
+    Plugin(String),
+
+    #[cfg(feature = "db")]
+    #[error("database error: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[cfg(feature = "db")]
+    #[error("database call timed out after {0:?}")]
+    Timeout(std::time::Duration),
+}
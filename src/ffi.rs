@@ -0,0 +1,126 @@
+//! C bindings for the naming and rendering core. See `include/gen.h`.
+
+use crate::{compute_filename, render_operation, Operation};
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::ptr;
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: impl ToString) {
+    let message = CString::new(message.to_string().replace('\0', " ")).unwrap();
+    LAST_ERROR.with(|x| *x.borrow_mut() = Some(message));
+}
+
+/// Reads an optional UTF-8 argument; `Err` means the caller passed invalid UTF-8.
+unsafe fn optional_str<'a>(
+    value: *const c_char,
+    argument: &str,
+) -> Result<Option<&'a str>, String> {
+    if value.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(value)
+        .to_str()
+        .map(Some)
+        .map_err(|_| format!("{argument} is not valid UTF-8"))
+}
+
+unsafe fn required_str<'a>(value: *const c_char, argument: &str) -> Result<&'a str, String> {
+    optional_str(value, argument)?.ok_or_else(|| format!("{argument} is required"))
+}
+
+fn parse_operation(operation: &str) -> Result<Operation, String> {
+    Operation::from_str(operation, false).map_err(|_| format!("unknown operation {operation:?}"))
+}
+
+/// Hands a result over to C, recording the outcome for `gen_last_error`.
+fn into_c_string(result: Result<Option<String>, String>) -> *mut c_char {
+    match result.and_then(|x| x.map(CString::new).transpose().map_err(|x| x.to_string())) {
+        Ok(value) => {
+            LAST_ERROR.with(|x| *x.borrow_mut() = None);
+            value.map_or(ptr::null_mut(), CString::into_raw)
+        }
+        Err(error) => {
+            set_last_error(error);
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Computes the file name of a migration dated `date` (`YYYY-MM-DD`).
+///
+/// Returns `NULL` on failure; see `gen_last_error`.
+///
+/// # Safety
+///
+/// Every non-null pointer must point to a NUL-terminated string. `column` may
+/// be null. The result must be released with `gen_string_free`.
+#[no_mangle]
+pub unsafe extern "C" fn gen_compute_filename(
+    operation: *const c_char,
+    name: *const c_char,
+    column: *const c_char,
+    date: *const c_char,
+    index: i32,
+) -> *mut c_char {
+    into_c_string((|| {
+        let operation = parse_operation(required_str(operation, "operation")?)?;
+        let name = required_str(name, "name")?;
+        let column = optional_str(column, "column")?;
+        let day = NaiveDate::parse_from_str(required_str(date, "date")?, "%Y-%m-%d")
+            .map_err(|x| x.to_string())?;
+        compute_filename(&operation, name, column, day, index)
+            .map(Some)
+            .map_err(|x| x.to_string())
+    })())
+}
+
+/// Renders the body of a migration.
+///
+/// Returns `NULL` both on failure and for operations without a template; the
+/// two are told apart by `gen_last_error`, which is null after a success.
+///
+/// # Safety
+///
+/// Every non-null pointer must point to a NUL-terminated string. `schema` and
+/// `column` may be null. The result must be released with `gen_string_free`.
+#[no_mangle]
+pub unsafe extern "C" fn gen_render_operation(
+    operation: *const c_char,
+    name: *const c_char,
+    schema: *const c_char,
+    column: *const c_char,
+) -> *mut c_char {
+    into_c_string((|| {
+        let operation = parse_operation(required_str(operation, "operation")?)?;
+        let name = required_str(name, "name")?;
+        let schema = optional_str(schema, "schema")?;
+        let column = optional_str(column, "column")?;
+        render_operation(&operation, name, schema, column).map_err(|x| x.to_string())
+    })())
+}
+
+/// Returns the message of the last failure on this thread, or `NULL`. The
+/// pointer stays valid until the next call into this library on the thread.
+#[no_mangle]
+pub extern "C" fn gen_last_error() -> *const c_char {
+    LAST_ERROR.with(|x| x.borrow().as_ref().map_or(ptr::null(), |x| x.as_ptr()))
+}
+
+/// Releases a string returned by this library.
+///
+/// # Safety
+///
+/// `value` must be null or a pointer returned by this library that has not
+/// been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn gen_string_free(value: *mut c_char) {
+    if !value.is_null() {
+        drop(CString::from_raw(value));
+    }
+}
@@ -0,0 +1,1914 @@
+use crate::audit::{self, AUDIT_FILE};
+use crate::column::{audit_columns, column_name, split_columns, Key, KeyKind};
+use crate::config::{
+    Config, Naming, Preset, Step, Timezone, CONFIG_FILE, DEFAULT_INDEX_WIDTH, ROOT_FILE,
+};
+use crate::counter::{Counter, COUNTER_FILE};
+use crate::dialect::Dialect;
+use crate::document::{self, INDEX_DOCUMENT};
+use crate::error::GenError;
+use crate::git;
+use crate::history;
+use crate::identifier::{self, Case, Quoting};
+use crate::index::MigrationIndex;
+use crate::language::Language;
+use crate::latest::{self, LATEST_FILE};
+use crate::lock::Lock;
+use crate::manifest::{self, Entry, Manifest, MANIFEST_FILE};
+use crate::naming::{
+    check_index_width, index_after, migrate_file_name, parse_file_name, sanitize, with_index_width,
+};
+use crate::operation::{
+    compute_filename_in, each_column, format_file_name, frontmatter, render_operation_with,
+    render_template, split_no_transaction, Extensions, TemplateData, Variable,
+};
+#[cfg(feature = "plugins")]
+use crate::plugin::Plugins;
+use crate::schema::SchemaModel;
+use crate::seed::Seed;
+use crate::templates;
+use crate::timings::Timings;
+use crate::trigger::{self, Event, Timing};
+use crate::Operation;
+use chrono::{NaiveDate, NaiveTime, TimeDelta, Timelike};
+use log::{info, warn};
+use regex::Regex;
+use std::collections::BTreeMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "plugins")]
+use std::sync::Arc;
+
+/// What `--audit` keeps `updated_at` current with on PostgreSQL, a function
+/// shared by the tables of a schema and a trigger per table.
+const UPDATED_AT_TRIGGER: &str = include_str!("../templates/set_updated_at.tmpl");
+
+/// What git says for the `branch`, `author` and `commit` tokens and the
+/// branch's ticket, asked once per generation.
+#[derive(Clone)]
+struct GitContext {
+    branch: Option<String>,
+    author: String,
+    commit: String,
+}
+
+impl GitContext {
+    /// Asks git in `root`, for the author only when `author` does not say.
+    fn load(root: &Path, author: Option<&str>) -> GitContext {
+        GitContext {
+            branch: git::current_branch(root),
+            author: match author {
+                Some(author) => author.to_owned(),
+                None => git::author(root).unwrap_or_default(),
+            },
+            commit: git::head_commit(root).unwrap_or_default(),
+        }
+    }
+}
+
+/// Builds and writes a single migration file.
+///
+/// ```no_run
+/// use thing::{Generator, Operation};
+///
+/// let path = Generator::new("/path/to/root")
+///     .operation(Operation::AddColumn)
+///     .name("users")
+///     .column("email")
+///     .generate()?;
+/// # Ok::<(), thing::GenError>(())
+/// ```
+#[derive(Clone)]
+pub struct Generator {
+    root: PathBuf,
+    output_dir: Option<PathBuf>,
+    operation: Option<Operation>,
+    preset: Option<String>,
+    name: Option<String>,
+    column: Option<String>,
+    index_name: Option<String>,
+    constraint_name: Option<String>,
+    references: Option<(String, Option<String>)>,
+    rename_to: Option<String>,
+    trigger_name: Option<String>,
+    timing: Option<Timing>,
+    events: Vec<Event>,
+    values: Vec<String>,
+    keys: Vec<Key>,
+    seed_from: Option<PathBuf>,
+    batch_size: Option<usize>,
+    seed: Option<Seed>,
+    schema: Option<String>,
+    service: Option<String>,
+    counter: bool,
+    git_add: bool,
+    commit: bool,
+    with_test: bool,
+    with_down: bool,
+    per_file: bool,
+    dry_run: bool,
+    force: bool,
+    cache: bool,
+    audit_columns: bool,
+    author: Option<String>,
+    vars: BTreeMap<String, String>,
+    envs: Vec<String>,
+    ticket: Option<String>,
+    allow_keyword: bool,
+    ignore_existing: bool,
+    duplicate: Option<(PathBuf, Option<String>)>,
+    language: Option<Language>,
+    dialect: Option<Dialect>,
+    naming: Option<Naming>,
+    today: Option<NaiveDate>,
+    time: Option<NaiveTime>,
+    timezone: Option<Timezone>,
+    config: Option<Config>,
+    /// The templates directory of the root's template pack, once resolved.
+    template_pack: Option<PathBuf>,
+    /// What git says, once asked.
+    git: Option<GitContext>,
+    #[cfg(feature = "plugins")]
+    plugins: Option<Arc<Plugins>>,
+    #[cfg(feature = "plugins")]
+    plugin_operation: Option<String>,
+}
+
+impl Generator {
+    /// Creates a generator for the gen root at `root`, writing into the
+    /// root's configured output directory unless `output_dir` says otherwise.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Generator {
+            root: root.into(),
+            output_dir: None,
+            operation: None,
+            preset: None,
+            name: None,
+            column: None,
+            index_name: None,
+            constraint_name: None,
+            references: None,
+            rename_to: None,
+            trigger_name: None,
+            timing: None,
+            events: Vec::new(),
+            values: Vec::new(),
+            keys: Vec::new(),
+            seed_from: None,
+            batch_size: None,
+            seed: None,
+            schema: None,
+            service: None,
+            counter: false,
+            git_add: false,
+            commit: false,
+            with_test: false,
+            with_down: false,
+            per_file: false,
+            dry_run: false,
+            force: false,
+            cache: false,
+            audit_columns: false,
+            author: None,
+            vars: BTreeMap::new(),
+            envs: Vec::new(),
+            ticket: None,
+            allow_keyword: false,
+            ignore_existing: false,
+            duplicate: None,
+            language: None,
+            dialect: None,
+            naming: None,
+            today: None,
+            time: None,
+            timezone: None,
+            config: None,
+            template_pack: None,
+            git: None,
+            #[cfg(feature = "plugins")]
+            plugins: None,
+            #[cfg(feature = "plugins")]
+            plugin_operation: None,
+        }
+    }
+
+    pub fn operation(mut self, operation: Operation) -> Self {
+        self.operation = Some(operation);
+        self
+    }
+
+    /// Generates the root's preset `preset` instead of a single operation.
+    pub fn preset(mut self, preset: impl Into<String>) -> Self {
+        self.preset = Some(preset.into());
+        self
+    }
+
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.name = Some(name.into());
+        self
+    }
+
+    /// The column, as `name` or `name:type:constraints` when adding one.
+    pub fn column(mut self, column: impl Into<String>) -> Self {
+        self.column = Some(column.into());
+        self
+    }
+
+    /// Names the index of `create-index` and `drop-index` instead of
+    /// `idx_<table>_<columns>`.
+    pub fn index_name(mut self, index_name: impl Into<String>) -> Self {
+        self.index_name = Some(index_name.into());
+        self
+    }
+
+    /// Names the constraint of the foreign key and constraint operations
+    /// instead of `fk_<table>_<columns>` or `ck_<table>_<columns>`.
+    pub fn constraint_name(mut self, constraint_name: impl Into<String>) -> Self {
+        self.constraint_name = Some(constraint_name.into());
+        self
+    }
+
+    /// Makes `add-foreign-key` reference `column`, `id` unless given, of
+    /// `table` instead of the table its column is named after.
+    pub fn references(mut self, table: impl Into<String>, column: Option<String>) -> Self {
+        self.references = Some((table.into(), column));
+        self
+    }
+
+    /// The new name of the table or column `rename-table` and
+    /// `rename-column` rename.
+    pub fn rename_to(mut self, rename_to: impl Into<String>) -> Self {
+        self.rename_to = Some(rename_to.into());
+        self
+    }
+
+    /// Names the trigger of `create-trigger` and `drop-trigger` instead of
+    /// `trg_<table>`, and on PostgreSQL its function.
+    pub fn trigger_name(mut self, trigger_name: impl Into<String>) -> Self {
+        self.trigger_name = Some(trigger_name.into());
+        self
+    }
+
+    /// When the trigger of `create-trigger` fires, after the statement unless
+    /// given.
+    pub fn timing(mut self, timing: Timing) -> Self {
+        self.timing = Some(timing);
+        self
+    }
+
+    /// Makes the trigger of `create-trigger` fire on `event`, on top of the
+    /// events already given. Without any it fires on every event.
+    pub fn event(mut self, event: Event) -> Self {
+        if !self.events.contains(&event) {
+            self.events.push(event);
+        }
+        self
+    }
+
+    /// Adds a label to the enum of `create-enum` or `alter-enum`.
+    pub fn value(mut self, value: impl Into<String>) -> Self {
+        self.values.push(value.into());
+        self
+    }
+
+    /// Adds a primary, unique or foreign key to the table of `create-table`.
+    pub fn key(mut self, key: Key) -> Self {
+        self.keys.push(key);
+        self
+    }
+
+    /// The CSV file whose rows `seed` inserts.
+    pub fn seed_from(mut self, path: impl Into<PathBuf>) -> Self {
+        self.seed_from = Some(path.into());
+        self
+    }
+
+    /// Inserts at most `batch_size` rows per statement of `seed`, instead of
+    /// all of them in one, and has `data-migration` update as many at a time.
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = Some(batch_size);
+        self
+    }
+
+    pub fn schema(mut self, schema: impl Into<String>) -> Self {
+        self.schema = Some(schema.into());
+        self
+    }
+
+    /// Generates for one service of a monorepo, numbered apart from the rest.
+    pub fn service(mut self, service: impl Into<String>) -> Self {
+        self.service = Some(service.into());
+        self
+    }
+
+    pub fn output_dir(mut self, output_dir: impl Into<PathBuf>) -> Self {
+        self.output_dir = Some(output_dir.into());
+        self
+    }
+
+    /// Tracks the next index in the root's counter file instead of scanning.
+    /// A root that already has a counter file uses it regardless.
+    pub fn counter(mut self, counter: bool) -> Self {
+        self.counter = counter;
+        self
+    }
+
+    /// Stages the generated files with `git add`. A root configured with
+    /// `git_add` does so regardless.
+    pub fn git_add(mut self, git_add: bool) -> Self {
+        self.git_add = git_add;
+        self
+    }
+
+    /// Commits the generated files, and only them, with the root's
+    /// `commit_message`.
+    pub fn commit(mut self, commit: bool) -> Self {
+        self.commit = commit;
+        self
+    }
+
+    /// Also writes a pgTAP test for the migration into the root's test
+    /// directory, if the operation has one. A root configured with
+    /// `with_test` does so regardless.
+    pub fn with_test(mut self, with_test: bool) -> Self {
+        self.with_test = with_test;
+        self
+    }
+
+    /// Writes a migration per column when adding or dropping several, rather
+    /// than one with a statement each.
+    pub fn per_file(mut self, per_file: bool) -> Self {
+        self.per_file = per_file;
+        self
+    }
+
+    /// Gives the table of `create-table` `created_at`, `updated_at` and
+    /// `created_by` columns after its own, and on PostgreSQL a trigger
+    /// keeping `updated_at` current. A root configured with `audit_columns`
+    /// does so regardless.
+    pub fn audit_columns(mut self, audit_columns: bool) -> Self {
+        self.audit_columns = audit_columns;
+        self
+    }
+
+    /// Also writes a rollback undoing the migration, if the operation has
+    /// one: under `down/` when the root keeps its migrations in up/ and
+    /// down/, next to the migration as `<name>.down.<extension>` otherwise.
+    /// A root configured with `with_down` does so regardless.
+    pub fn with_down(mut self, with_down: bool) -> Self {
+        self.with_down = with_down;
+        self
+    }
+
+    /// Renders the migration without writing anything, or updating what
+    /// generation keeps up to date alongside it. `generate` returns the path
+    /// it would have written, and `generate_in` counts it in its index.
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Overwrites the migration, rollback and test if there already are files
+    /// by their names, which generation refuses to do otherwise.
+    pub fn force(mut self, force: bool) -> Self {
+        self.force = force;
+        self
+    }
+
+    /// Scans the root with its cache file, keeping it up to date, instead of
+    /// listing every directory.
+    pub fn cache(mut self, cache: bool) -> Self {
+        self.cache = cache;
+        self
+    }
+
+    /// Fills the `author` token with `author` instead of the git identity.
+    pub fn author(mut self, author: impl Into<String>) -> Self {
+        self.author = Some(author.into());
+        self
+    }
+
+    /// Copies the migration at `path`, and its rollback, instead of rendering
+    /// a template. When the generator's name differs from `table`, the table
+    /// the copied migration is about, every whole word `table` in its name
+    /// and content is replaced with the generator's name.
+    pub fn duplicate(mut self, path: impl Into<PathBuf>, table: Option<String>) -> Self {
+        self.duplicate = Some((path.into(), table));
+        self
+    }
+
+    /// Sets the template variable `name`, which templates can use as a token
+    /// like any other.
+    pub fn var(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.vars.insert(name.into(), value.into());
+        self
+    }
+
+    /// Restricts the migration to the environment `env`, guarded the way the
+    /// root's `env_guard` says. Can be given several times.
+    pub fn env(mut self, env: impl Into<String>) -> Self {
+        self.envs.push(env.into());
+        self
+    }
+
+    /// Quotes the tables and columns named after a keyword the dialect
+    /// reserves instead of failing.
+    pub fn allow_keyword(mut self, allow_keyword: bool) -> Self {
+        self.allow_keyword = allow_keyword;
+        self
+    }
+
+    /// Does not warn when `create-table` creates a table an existing
+    /// migration already creates.
+    pub fn ignore_existing(mut self, ignore_existing: bool) -> Self {
+        self.ignore_existing = ignore_existing;
+        self
+    }
+
+    /// Puts the ticket the migration is for, e.g. `DB-1234`, in its file
+    /// name and a comment at its top, instead of the one the root's
+    /// `ticket_pattern` finds in the branch name.
+    pub fn ticket(mut self, ticket: impl Into<String>) -> Self {
+        self.ticket = Some(sanitize(&ticket.into()));
+        self
+    }
+
+    /// Writes the migration in `language` instead of the root's configured
+    /// one.
+    pub fn language(mut self, language: Language) -> Self {
+        self.language = Some(language);
+        self
+    }
+
+    /// Writes SQL for `dialect` instead of the root's configured one.
+    pub fn dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = Some(dialect);
+        self
+    }
+
+    /// Names the migration the `naming` way instead of the root's
+    /// configured one.
+    pub fn naming(mut self, naming: Naming) -> Self {
+        self.naming = Some(naming);
+        self
+    }
+
+    /// Dates the migration `today` instead of the current date in the
+    /// root's time zone.
+    pub fn today(mut self, today: NaiveDate) -> Self {
+        self.today = Some(today);
+        self
+    }
+
+    /// Stamps a migrate name with `time` instead of the current time, or the
+    /// first second after it that no migration in its directory has.
+    pub fn time(mut self, time: NaiveTime) -> Self {
+        self.time = Some(time);
+        self
+    }
+
+    /// Takes the current date and time in `timezone` instead of the root's
+    /// time zone.
+    pub fn timezone(mut self, timezone: Timezone) -> Self {
+        self.timezone = Some(timezone);
+        self
+    }
+
+    /// Uses `config` instead of the configuration stored in the root.
+    pub fn config(mut self, config: Config) -> Self {
+        self.config = Some(config);
+        self
+    }
+
+    /// Applies the tokens and helpers of `plugins` to the rendered template.
+    #[cfg(feature = "plugins")]
+    pub fn plugins(mut self, plugins: Arc<Plugins>) -> Self {
+        self.plugins = Some(plugins);
+        self
+    }
+
+    /// Generates `operation` from one of the loaded plugins instead of a
+    /// built-in operation.
+    #[cfg(feature = "plugins")]
+    pub fn plugin_operation(mut self, operation: impl Into<String>) -> Self {
+        self.plugin_operation = Some(operation.into());
+        self
+    }
+
+    /// Checks that every argument the operation needs has been provided.
+    pub fn validate(&self) -> Result<(), GenError> {
+        #[cfg(feature = "plugins")]
+        if let Some(operation) = &self.plugin_operation {
+            let plugins = self
+                .plugins
+                .as_ref()
+                .ok_or(GenError::MissingArgument("plugins"))?;
+            let operation = plugins
+                .operation(operation)
+                .ok_or_else(|| GenError::Plugin(format!("no plugin provides {operation:?}")))?;
+            if self.name.is_none() {
+                return Err(GenError::MissingArgument("name"));
+            }
+            if operation.requires_column && self.column.is_none() {
+                return Err(GenError::MissingArgument("column"));
+            }
+            return Ok(());
+        }
+
+        // A preset's steps are only known once the configuration is loaded.
+        if self.preset.is_some() {
+            return match self.name {
+                Some(_) => Ok(()),
+                None => Err(GenError::MissingArgument("name")),
+            };
+        }
+
+        let operation = self
+            .operation
+            .as_ref()
+            .ok_or(GenError::MissingArgument("operation"))?;
+        if self.name.is_none() {
+            return Err(GenError::MissingArgument("name"));
+        }
+        // A copy is not rendered, and the name of an index or constraint does
+        // not say its columns, which all but new indexes and foreign keys can
+        // do without.
+        let named = !matches!(operation, Operation::CreateIndex | Operation::AddForeignKey)
+            && self.object_name(operation).is_some();
+        if operation.requires_column()
+            && self.column.is_none()
+            && self.duplicate.is_none()
+            && !named
+        {
+            return Err(GenError::MissingArgument("column"));
+        }
+        let rename = matches!(operation, Operation::RenameTable | Operation::RenameColumn);
+        if rename && self.rename_to.is_none() && self.duplicate.is_none() {
+            return Err(GenError::MissingArgument("to"));
+        }
+        if *operation == Operation::Seed && self.seed_from.is_none() && self.duplicate.is_none() {
+            return Err(GenError::MissingArgument("from"));
+        }
+        let adds_values = *operation == Operation::AlterEnum;
+        if adds_values && self.values.is_empty() && self.duplicate.is_none() {
+            return Err(GenError::MissingArgument("value"));
+        }
+        // The dialect is only known here once the configuration is loaded.
+        if let Some(dialect) = self.dialect {
+            if self.events.len() > 1 && !trigger::several_events(dialect) {
+                return Err(GenError::Unsupported(
+                    dialect.name(),
+                    "triggers on several events",
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    /// The variables the templates being rendered declare in their
+    /// frontmatter that have not been set with `var`, so a caller can ask for
+    /// them before generating.
+    pub fn missing_variables(&self) -> Result<Vec<Variable>, GenError> {
+        let config = match &self.config {
+            Some(config) => config.clone(),
+            None => Config::load(&self.root)?,
+        };
+        let language = self.language.unwrap_or(config.language);
+        let directory = language.template_directory(self.dialect.unwrap_or(config.dialect));
+        let directories =
+            templates::directories(&self.root, templates::pack(&self.root, &config)?.as_deref());
+        let mut templates = Vec::new();
+        match &self.preset {
+            Some(preset) => {
+                let preset = config
+                    .presets
+                    .get(preset)
+                    .ok_or_else(|| GenError::UnknownPreset(preset.clone()))?;
+                for step in &preset.steps {
+                    match step {
+                        Step::Operation { operation, .. } => {
+                            templates.extend(user_template(&directories, directory, operation)?);
+                        }
+                        Step::Template { template } => templates.push(template.clone()),
+                    }
+                }
+            }
+            None => {
+                if let Some(operation) = &self.operation {
+                    templates.extend(user_template(&directories, directory, operation)?);
+                }
+            }
+        }
+        let mut missing = Vec::<Variable>::new();
+        for template in &templates {
+            for variable in frontmatter(template).0 {
+                if !self.vars.contains_key(&variable.name)
+                    && !missing.iter().any(|x| x.name == variable.name)
+                {
+                    missing.push(variable);
+                }
+            }
+        }
+        Ok(missing)
+    }
+
+    /// Writes the migration and returns its path, the last one's when
+    /// writing a migration per column.
+    pub fn generate(&self) -> Result<PathBuf, GenError> {
+        self.generate_timed(&mut Timings::new(false))
+    }
+
+    /// Like `generate`, recording each phase in `timings`.
+    pub fn generate_timed(&self, timings: &mut Timings) -> Result<PathBuf, GenError> {
+        self.write_locked(None, timings, &mut Vec::new())
+    }
+
+    /// Like `generate`, returning the migrations, rollbacks and tests written,
+    /// or that would be with `dry_run`, with their content.
+    pub fn generate_files(&self) -> Result<Vec<(PathBuf, String)>, GenError> {
+        self.generate_files_timed(&mut Timings::new(false))
+    }
+
+    /// Like `generate_files`, recording each phase in `timings`.
+    pub fn generate_files_timed(
+        &self,
+        timings: &mut Timings,
+    ) -> Result<Vec<(PathBuf, String)>, GenError> {
+        let mut files = Vec::new();
+        self.write_locked(None, timings, &mut files)?;
+        Ok(files)
+    }
+
+    /// The `YYYYMMDDNN` prefix of the migration generated next for the
+    /// generator's schema, service and output directory, its index padded to
+    /// the root's `index_width`, numbered against the counter file when there
+    /// is one and `migrations` otherwise. Migrate names have no index, being
+    /// stamped with the time they are generated at, so they have none.
+    pub fn next_prefix(&self, migrations: &MigrationIndex) -> Result<Option<String>, GenError> {
+        let config = match &self.config {
+            Some(config) => config.clone(),
+            None => Config::load(&self.root)?,
+        };
+        let timezone = self.timezone.unwrap_or(config.timezone);
+        let today = self.today.unwrap_or_else(|| timezone.now().date());
+        let naming = self.naming.unwrap_or(config.naming);
+        if naming == Naming::Migrate {
+            return Ok(None);
+        }
+        let (output_dir, service_root, prefix) = self.placement(&config)?;
+        let schema = self.schema.as_deref().or(config.schema.as_deref());
+        let up_dir = config.up_directory(&output_dir);
+        let numbering_root = config.numbering_root(&service_root, &up_dir, schema);
+        let (last_index, _) = self.last_index(
+            &config,
+            naming,
+            today,
+            &numbering_root,
+            &prefix,
+            Some(migrations),
+            &mut Timings::new(false),
+        )?;
+        let index = index_after(last_index);
+        let width = config.index_width();
+        check_index_width(index, width)?;
+        Ok(Some(format!(
+            "{prefix}{}{index:0width$}",
+            today.format("%Y%m%d")
+        )))
+    }
+
+    /// The directory migrations go into, the one their service is numbered
+    /// under and the start of their file names, as the service says.
+    fn placement(&self, config: &Config) -> Result<(PathBuf, PathBuf, String), GenError> {
+        let root = &self.root;
+        let mut output_dir = match (&self.output_dir, &config.output_dir) {
+            (Some(output_dir), _) => output_dir.clone(),
+            (None, Some(output_dir)) => root.join(output_dir),
+            (None, None) => root.clone(),
+        };
+        let mut service_root = root.clone();
+        let mut prefix = String::new();
+        if let Some(service) = &self.service {
+            if !config.services.is_empty() && !config.services.contains(service) {
+                return Err(GenError::UnknownService(service.clone()));
+            }
+            if config.service_prefix.directory() {
+                output_dir.push(service);
+                service_root = output_dir.clone();
+            }
+            if config.service_prefix.file_name() {
+                prefix = format!("{service}-");
+            }
+        }
+        Ok((output_dir, service_root, prefix))
+    }
+
+    /// The last index used on `today` where `numbering_root` numbers
+    /// migrations whose names start with `prefix`, from its counter file when
+    /// there is one and from `migrations`, or a scan, otherwise, and whether
+    /// the counter file is kept up.
+    #[allow(clippy::too_many_arguments)]
+    fn last_index(
+        &self,
+        config: &Config,
+        naming: Naming,
+        today: NaiveDate,
+        numbering_root: &Path,
+        prefix: &str,
+        migrations: Option<&MigrationIndex>,
+        timings: &mut Timings,
+    ) -> Result<(Option<i32>, bool), GenError> {
+        // A counter file describes a whole directory, which services only get
+        // with service directories.
+        let counter = if self.service.is_none() || config.service_prefix.directory() {
+            Counter::load(numbering_root)?
+        } else if self.counter {
+            return Err(GenError::MalformedConfig(
+                self.root.join(ROOT_FILE),
+                "counter files need service directories".to_owned(),
+            ));
+        } else {
+            None
+        };
+        let use_counter = naming == Naming::Gen && (self.counter || counter.is_some());
+        let last_index = timings.time("scan", || match counter {
+            Some(counter) => counter.last_index_for(today),
+            None => match migrations {
+                Some(migrations) => migrations.last_index_in(numbering_root, prefix, today),
+                None => self
+                    .scan(numbering_root)
+                    .last_index_in(numbering_root, prefix, today),
+            },
+        })?;
+        Ok((last_index, use_counter))
+    }
+
+    /// Like `generate`, numbering against an already scanned index instead of
+    /// scanning the root again. The new file is added to `migrations`.
+    pub fn generate_in(&self, migrations: &mut MigrationIndex) -> Result<PathBuf, GenError> {
+        self.write_locked(Some(migrations), &mut Timings::new(false), &mut Vec::new())
+    }
+
+    /// The migrations under `directory`, one of the root's.
+    fn scan(&self, directory: &Path) -> MigrationIndex {
+        match self.cache {
+            true => MigrationIndex::scan_cached(&self.root, directory),
+            false => MigrationIndex::scan_in(&self.root, directory),
+        }
+    }
+
+    /// `write` holding the root's lock, unless nothing is written.
+    fn write_locked(
+        &self,
+        migrations: Option<&mut MigrationIndex>,
+        timings: &mut Timings,
+        files: &mut Vec<(PathBuf, String)>,
+    ) -> Result<PathBuf, GenError> {
+        let _lock = match self.dry_run {
+            true => None,
+            false => Some(timings.time("lock", || Lock::acquire(&self.root))?),
+        };
+        self.write(migrations, timings, files)
+    }
+
+    fn write(
+        &self,
+        migrations: Option<&mut MigrationIndex>,
+        timings: &mut Timings,
+        files: &mut Vec<(PathBuf, String)>,
+    ) -> Result<PathBuf, GenError> {
+        self.validate()?;
+        let root = &self.root;
+        if self.git.is_none() {
+            let mut generator = self.clone();
+            generator.git = Some(GitContext::load(root, self.author.as_deref()));
+            return generator.write(migrations, timings, files);
+        }
+
+        let columns = match (&self.operation, &self.column) {
+            (Some(operation), Some(column)) if self.per_file && operation.per_column() => {
+                split_columns(column)
+            }
+            _ => Vec::new(),
+        };
+        if columns.len() > 1 && self.duplicate.is_none() {
+            let mut scanned = None;
+            let migrations = match migrations {
+                Some(migrations) => migrations,
+                None => scanned.insert(self.scan(root)),
+            };
+            let mut paths = Vec::new();
+            for column in columns {
+                let mut generator = self.clone().column(column);
+                generator.per_file = false;
+                paths.push(generator.write(Some(migrations), timings, files)?);
+            }
+            return Ok(paths.pop().unwrap());
+        }
+
+        let config = match &self.config {
+            Some(config) => config.clone(),
+            None => Config::load(root)?,
+        };
+        let now = self.timezone.unwrap_or(config.timezone).now();
+        let today = self.today.unwrap_or(now.date());
+        let ticket = match (&self.ticket, &config.ticket_pattern) {
+            (None, Some(pattern)) => self.branch_ticket(pattern)?,
+            _ => None,
+        };
+        if config.require_ticket && self.ticket.is_none() && ticket.is_none() {
+            return Err(GenError::MissingArgument(
+                "a ticket, given with --ticket or in the branch name,",
+            ));
+        }
+        // What the root sets a default for and was not given otherwise.
+        if (self.schema.is_none() && config.schema.is_some())
+            || self.dialect.is_none()
+            || (config.audit_columns && !self.audit_columns)
+            || (config.template_pack.is_some() && self.template_pack.is_none())
+            || ticket.is_some()
+        {
+            let mut generator = self.clone();
+            generator.ticket = self.ticket.clone().or(ticket);
+            generator.template_pack = templates::pack(root, &config)?;
+            generator.schema = self.schema.clone().or(config.schema.clone());
+            generator.dialect = self.dialect.or(Some(config.dialect));
+            generator.audit_columns = self.audit_columns || config.audit_columns;
+            generator.config = Some(config);
+            return generator.write(migrations, timings, files);
+        }
+        if let (Some(path), None) = (&self.seed_from, &self.seed) {
+            let csv = fs::read_to_string(path)?;
+            let mut generator = self.clone();
+            let dialect = self.dialect.unwrap_or_default();
+            generator.seed = Some(Seed::parse(dialect, &csv, self.batch_size)?);
+            return generator.write(migrations, timings, files);
+        }
+        let (output_dir, service_root, prefix) = self.placement(&config)?;
+        let preset = match &self.preset {
+            Some(preset) => Some(
+                config
+                    .presets
+                    .get(preset)
+                    .ok_or_else(|| GenError::UnknownPreset(preset.clone()))?,
+            ),
+            None => None,
+        };
+        let schema = self.schema.as_deref();
+        let language = self.language.unwrap_or(config.language);
+        if language == Language::Sql {
+            if config.identifier_case != Case::Preserve {
+                let cased = self.cased(config.identifier_case);
+                if cased.identifiers() != self.identifiers() {
+                    return cased.write(migrations, timings, files);
+                }
+            }
+            self.check_identifiers()?;
+        }
+        if self.operation == Some(Operation::CreateTable) && !self.ignore_existing {
+            timings.time("existing", || {
+                self.warn_existing(&config, migrations.as_deref())
+            })?;
+        }
+        let up_dir = config.up_directory(&output_dir);
+        let directory = config.directory(&up_dir, today, schema);
+        let numbering_root = config.numbering_root(&service_root, &up_dir, schema);
+        let naming = self.naming.unwrap_or(config.naming);
+        let (last_index, use_counter) = self.last_index(
+            &config,
+            naming,
+            today,
+            &numbering_root,
+            &prefix,
+            migrations.as_deref(),
+            timings,
+        )?;
+
+        let mut index = index_after(last_index);
+        let width = config.index_width();
+        if naming == Naming::Gen {
+            check_index_width(index, width)?;
+        }
+        let (mut file_name, template) = timings.time("template render", || {
+            self.render(language, today, index, preset)
+        })?;
+        if let Some(ticket) = &self.ticket {
+            let name = parse_file_name(&file_name).expect("generated names parse");
+            let description = format!("{ticket} {}", name.description);
+            file_name = format_file_name(name.language, today, index, &description);
+        }
+        if naming == Naming::Migrate {
+            let name = parse_file_name(&file_name).expect("generated names parse");
+            let time = self.time.unwrap_or(now.time());
+            let mut time = today.and_time(time.with_nanosecond(0).unwrap());
+            // Two migrations stamped the same second would run in no
+            // particular order.
+            let taken = fs::read_dir(&directory)
+                .into_iter()
+                .flatten()
+                .filter_map(|x| x.ok()?.file_name().into_string().ok())
+                .collect::<Vec<_>>();
+            while taken.iter().any(|x| {
+                let stamp = format!("{prefix}{}_", time.format("%Y%m%d%H%M%S"));
+                x.starts_with(&stamp)
+            }) {
+                time += TimeDelta::seconds(1);
+            }
+            file_name = migrate_file_name(time, name.description, name.language);
+            index = (time.hour() * 10000 + time.minute() * 100 + time.second()) as i32;
+        } else if let Some(format) = &config.file_name_format {
+            file_name = self.custom_file_name(format, &file_name, language, today, index, width)?;
+        } else if width != DEFAULT_INDEX_WIDTH {
+            file_name = with_index_width(&file_name, index, width);
+        }
+        let file_name = prefix + &file_name;
+        info!("writing file {file_name}");
+
+        let path = directory.join(&file_name);
+        let test = match self.with_test || config.with_test {
+            true => self.render_test(language, preset)?,
+            false => None,
+        };
+        let test_path = test.as_ref().map(|_| {
+            let stem = file_name.strip_suffix(".sql").unwrap_or(&file_name);
+            config.test_directory(root).join(format!("{stem}.pg"))
+        });
+        let with_down = self.with_down || config.with_down || naming == Naming::Migrate;
+        let rollback = match &self.duplicate {
+            Some((source, table)) => match config.rollback(root, source) {
+                Some(source) if source.exists() => {
+                    Some(self.rename_table(&fs::read_to_string(source)?, table.as_deref()))
+                }
+                _ => None,
+            },
+            None if with_down => self.render_rollback(language, preset)?,
+            None => None,
+        };
+        let down_path = match config.down_directory(&output_dir) {
+            _ if naming == Naming::Migrate => config.rollback(root, &path),
+            Some(x) => Some(config.directory(&x, today, schema).join(&file_name)),
+            None => rollback.as_ref().and_then(|_| config.rollback(root, &path)),
+        };
+        let rollback = match (rollback, naming) {
+            (Some(rollback), _) => rollback,
+            // Every migrate migration comes with a rollback, if only one that
+            // undoes nothing.
+            (None, Naming::Migrate) => {
+                format!("{} Nothing undoes {file_name}.\n", language.comment())
+            }
+            (None, Naming::Gen) => String::new(),
+        };
+        let template = template.unwrap_or_default();
+        // The line apply and sqlx look for first stays first, before the
+        // guard, the ticket and the header.
+        let (no_transaction, content) = split_no_transaction(&template);
+        let setting = config.env_setting.as_deref().unwrap_or("app.env");
+        let content = config
+            .env_guard
+            .wrap(language, setting, &self.envs, content);
+        let content = match &self.ticket {
+            Some(ticket) => format!("{} Ticket: {ticket}\n{content}", language.comment()),
+            None => content,
+        };
+        let mut content = format!("{no_transaction}{}", self.wrap(content)?);
+        let mut rollback = match rollback.is_empty() {
+            true => rollback,
+            false => {
+                let (no_transaction, rollback) = split_no_transaction(&rollback);
+                format!("{no_transaction}{}", self.wrap(rollback.to_owned())?)
+            }
+        };
+        let mut test = test;
+        files.push((path.clone(), content.clone()));
+        files.extend(down_path.iter().map(|x| (x.clone(), rollback.clone())));
+        if let (Some(test_path), Some(test)) = (&test_path, &test) {
+            files.push((test_path.clone(), test.clone()));
+        }
+        if self.dry_run {
+            // The next migration of a dry run numbers after this one.
+            if let Some(migrations) = migrations {
+                migrations.add(path.clone());
+            }
+            return Ok(path);
+        }
+        // Everything this generation creates or updates, for `git add`.
+        let mut written = vec![path.clone()];
+        let hooked = timings.time("write", || {
+            // The migration is checked as it is created, so of two
+            // generations racing for a name one fails instead of both writing.
+            let mut others = down_path.iter().chain(test_path.as_ref());
+            if let Some(other) = others.find(|x| !self.force && x.exists()) {
+                return Err(GenError::AlreadyExists(other.clone()));
+            }
+            fs::create_dir_all(&directory)?;
+            create(&path, self.force)?.write_all(content.as_bytes())?;
+            if let Some(down_path) = &down_path {
+                fs::create_dir_all(down_path.parent().unwrap())?;
+                create(down_path, self.force)?.write_all(rollback.as_bytes())?;
+                written.push(down_path.clone());
+            }
+            if let (Some(test_path), Some(test)) = (&test_path, &test) {
+                fs::create_dir_all(test_path.parent().unwrap())?;
+                create(test_path, self.force)?.write_all(test.as_bytes())?;
+                written.push(test_path.clone());
+            }
+            // A failing hook is only reported once the migration is
+            // accounted for, so that `undo` and the next generation see it.
+            let mut hooked = Ok(());
+            if !config.post_create.is_empty() {
+                hooked = written.iter().try_for_each(|file| {
+                    config
+                        .post_create
+                        .iter()
+                        .try_for_each(|command| post_create(root, command, file))
+                });
+                // What the hooks left is what was generated.
+                content = fs::read_to_string(&path)?;
+                if let Some(down_path) = &down_path {
+                    rollback = fs::read_to_string(down_path)?;
+                }
+                if let (Some(test_path), Some(test)) = (&test_path, &mut test) {
+                    *test = fs::read_to_string(test_path)?;
+                }
+            }
+            if use_counter {
+                fs::create_dir_all(&numbering_root)?;
+                Counter::new(today, index).store(&numbering_root)?;
+                written.push(numbering_root.join(COUNTER_FILE));
+            }
+            if config.manifest {
+                let mut migrations = Manifest::load(root)?;
+                migrations.insert(Entry {
+                    path: manifest::relative_path(root, &path),
+                    date: today,
+                    index,
+                    operation: self.operation_name(),
+                    table: self.name.clone().unwrap(),
+                    column: self.column.clone(),
+                    service: self.service.clone(),
+                    checksum: manifest::checksum(content.as_bytes()),
+                });
+                migrations.store(root)?;
+                written.push(root.join(MANIFEST_FILE));
+            }
+            if let Some(kind) = config.latest {
+                latest::update(root, &path, kind)?;
+                written.push(root.join(LATEST_FILE));
+            }
+            Ok::<_, GenError>(hooked)
+        })?;
+        if !config.post_create.is_empty() {
+            let generated = [(&path, &content)]
+                .into_iter()
+                .chain(down_path.iter().map(|x| (x, &rollback)))
+                .chain(test_path.iter().zip(&test));
+            for (file, text) in generated {
+                if let Some(entry) = files.iter_mut().find(|x| x.0 == *file) {
+                    entry.1.clone_from(text);
+                }
+            }
+        }
+        match migrations {
+            Some(migrations) => {
+                migrations.refresh(&path);
+                if let Some(down_path) = &down_path {
+                    migrations.refresh(down_path);
+                }
+                if config.index_document {
+                    document::write(root, migrations)?;
+                }
+            }
+            None if config.index_document => {
+                document::write(root, &self.scan(root))?;
+            }
+            None => {}
+        }
+        if config.index_document {
+            written.push(root.join(INDEX_DOCUMENT));
+        }
+
+        let mut generated = vec![(path.clone(), content.as_bytes())];
+        generated.extend(down_path.iter().map(|x| (x.clone(), rollback.as_bytes())));
+        if let (Some(test_path), Some(test)) = (&test_path, &test) {
+            generated.push((test_path.clone(), test.as_bytes()));
+        }
+        history::record(root, &generated)?;
+        if config.audit {
+            let files = [Some(path.clone()), down_path, test_path];
+            let files = files.into_iter().flatten();
+            audit::record(
+                root,
+                "generate",
+                &files.collect::<Vec<_>>(),
+                &[],
+                &self.flags(),
+            )?;
+            written.push(root.join(AUDIT_FILE));
+        }
+        hooked?;
+
+        if self.commit {
+            let message = self.commit_message(&config, &file_name)?;
+            timings.time("git commit", || git::commit(root, &written, &message))?;
+        } else if self.git_add || config.git_add {
+            timings.time("git add", || git::add(root, &written))?;
+        }
+        Ok(path)
+    }
+
+    /// The command line arguments that would generate the same migration, for
+    /// the audit log.
+    fn flags(&self) -> Vec<String> {
+        let mut flags = match (&self.duplicate, &self.preset) {
+            (Some((source, _)), _) => vec!["duplicate".to_owned(), source.display().to_string()],
+            (None, Some(preset)) => vec!["preset".to_owned(), preset.clone()],
+            (None, None) => vec![self.operation_name()],
+        };
+        let options = [
+            ("--name", &self.name),
+            ("--column", &self.column),
+            ("--index-name", &self.index_name),
+            ("--constraint-name", &self.constraint_name),
+            ("--to", &self.rename_to),
+            ("--trigger-name", &self.trigger_name),
+            ("--schema", &self.schema),
+            ("--service", &self.service),
+            ("--author", &self.author),
+            ("--ticket", &self.ticket),
+        ];
+        for (flag, value) in options {
+            if let Some(value) = value {
+                flags.extend([flag.to_owned(), value.clone()]);
+            }
+        }
+        if let Some(timing) = self.timing {
+            flags.extend(["--timing".to_owned(), timing.name().to_owned()]);
+        }
+        for event in &self.events {
+            flags.extend(["--event".to_owned(), event.name().to_owned()]);
+        }
+        for value in &self.values {
+            flags.extend(["--value".to_owned(), value.clone()]);
+        }
+        for key in &self.keys {
+            let columns = key.columns.join(",");
+            let (flag, value) = match (key.kind, &key.referenced_table, &key.referenced_column) {
+                (KeyKind::ForeignKey, Some(table), Some(column)) => {
+                    ("--fk", format!("{columns}:{table}.{column}"))
+                }
+                (KeyKind::PrimaryKey, ..) => ("--pk", columns),
+                _ => ("--unique", columns),
+            };
+            flags.extend([flag.to_owned(), value]);
+        }
+        if let Some((table, column)) = &self.references {
+            flags.extend(["--references".to_owned(), table.clone()]);
+            if let Some(column) = column {
+                flags.extend(["--referenced-column".to_owned(), column.clone()]);
+            }
+        }
+        for env in &self.envs {
+            flags.extend(["--env".to_owned(), env.clone()]);
+        }
+        for (name, value) in &self.vars {
+            flags.extend(["--var".to_owned(), format!("{name}={value}")]);
+        }
+        if let Some(language) = self.language {
+            flags.extend(["--language".to_owned(), language.extension().to_owned()]);
+        }
+        if let Some(dialect) = self.dialect {
+            flags.extend(["--dialect".to_owned(), dialect.name().to_owned()]);
+        }
+        if let Some(naming) = self.naming {
+            flags.extend(["--naming".to_owned(), naming.name().to_owned()]);
+        }
+        if self.timezone == Some(Timezone::Utc) {
+            flags.push("--utc".to_owned());
+        }
+        if let Some(path) = &self.seed_from {
+            flags.extend(["--from".to_owned(), path.display().to_string()]);
+        }
+        if let Some(batch_size) = self.batch_size {
+            flags.extend(["--batch-size".to_owned(), batch_size.to_string()]);
+        }
+        if let Some(output_dir) = &self.output_dir {
+            flags.extend(["--out".to_owned(), output_dir.display().to_string()]);
+        }
+        let switches = [
+            ("--counter", self.counter),
+            ("--git-add", self.git_add),
+            ("--commit", self.commit),
+            ("--with-test", self.with_test),
+            ("--with-down", self.with_down),
+            ("--per-file", self.per_file),
+            ("--force", self.force),
+            ("--audit", self.audit_columns),
+            ("--allow-keyword", self.allow_keyword),
+            ("--ignore-existing", self.ignore_existing),
+        ];
+        flags.extend(switches.into_iter().filter(|x| x.1).map(|x| x.0.to_owned()));
+        flags
+    }
+
+    /// The pgTAP test of the migration, if it is a built-in SQL operation
+    /// with one. A preset's holds the tests of its steps that have one.
+    fn render_test(
+        &self,
+        language: Language,
+        preset: Option<&Preset>,
+    ) -> Result<Option<String>, GenError> {
+        #[cfg(feature = "plugins")]
+        if self.plugin_operation.is_some() {
+            return Ok(None);
+        }
+        if language != Language::Sql || self.dialect.unwrap_or_default() != Dialect::Postgres {
+            return Ok(None);
+        }
+        let name = self.name.as_deref().unwrap();
+        let schema = self.schema.as_deref();
+        let test = |operation: &Operation, column: Option<&str>| {
+            let template = user_template(&self.template_directories(), "pgtap", operation)?;
+            each_column(operation, column, |column| {
+                let data = match &template {
+                    Some(template) => Some(operation.template_data(
+                        Dialect::Postgres,
+                        name,
+                        schema,
+                        column,
+                        template.clone(),
+                    )),
+                    None => operation.get_test_template_data(name, schema, column),
+                };
+                data.map(|x| render_template(x, &self.tokens())).transpose()
+            })
+        };
+        let Some(preset) = preset else {
+            return test(self.operation.as_ref().unwrap(), self.column.as_deref());
+        };
+        let mut tests = Vec::new();
+        for step in &preset.steps {
+            if let Step::Operation { operation, column } = step {
+                let column = column.as_deref().or(self.column.as_deref());
+                tests.extend(test(operation, column)?);
+            }
+        }
+        Ok((!tests.is_empty()).then(|| tests.join("\n\n")))
+    }
+
+    /// The rollback of the migration, if it is a built-in operation with
+    /// one. A preset's undoes its steps that have one, last step first.
+    fn render_rollback(
+        &self,
+        language: Language,
+        preset: Option<&Preset>,
+    ) -> Result<Option<String>, GenError> {
+        #[cfg(feature = "plugins")]
+        if self.plugin_operation.is_some() {
+            return Ok(None);
+        }
+        let name = self.name.as_deref().unwrap();
+        let schema = self.schema.as_deref();
+        let dialect = self.dialect.unwrap_or_default();
+        let directory = Path::new(language.template_directory(dialect)).join("down");
+        let rollback = |operation: &Operation, column: Option<&str>| {
+            let template = user_template(&self.template_directories(), &directory, operation)?;
+            each_column(operation, column, |column| {
+                let data = match &template {
+                    Some(template) => Some(operation.template_data(
+                        dialect,
+                        name,
+                        schema,
+                        column,
+                        template.clone(),
+                    )),
+                    None => {
+                        operation.get_down_template_data(language, dialect, name, schema, column)
+                    }
+                };
+                data.map(|x| render_template(x, &self.tokens())).transpose()
+            })
+        };
+        let Some(preset) = preset else {
+            return rollback(self.operation.as_ref().unwrap(), self.column.as_deref());
+        };
+        let mut rollbacks = Vec::new();
+        for step in preset.steps.iter().rev() {
+            if let Step::Operation { operation, column } = step {
+                let column = column.as_deref().or(self.column.as_deref());
+                rollbacks.extend(rollback(operation, column)?);
+            }
+        }
+        Ok((!rollbacks.is_empty()).then(|| rollbacks.join("\n\n")))
+    }
+
+    /// `body` after the root's `header` partial and before its `footer`, for
+    /// a standard banner on every migration and rollback. Copies are left
+    /// alone, as they already have them.
+    fn wrap(&self, body: String) -> Result<String, GenError> {
+        if self.duplicate.is_some() {
+            return Ok(body);
+        }
+        let extensions = &self.tokens();
+        if !["header", "footer"]
+            .iter()
+            .any(|x| extensions.partials.contains_key(*x))
+        {
+            return Ok(body);
+        }
+        let render = |name: &str| match extensions.partials.contains_key(name) {
+            true => {
+                let data = TemplateData::new(
+                    self.name.as_deref().unwrap(),
+                    self.schema.as_deref(),
+                    self.column.as_deref(),
+                    format!("{{{{> {name}}}}}"),
+                );
+                render_template(data, extensions).map(|x| Some(x.trim_end().to_owned()))
+            }
+            false => Ok(None),
+        };
+        let parts = [render("header")?, Some(body), render("footer")?];
+        Ok(parts.into_iter().flatten().collect::<Vec<_>>().join("\n"))
+    }
+
+    /// The message `--commit` commits the migration named `file_name` with.
+    fn commit_message(&self, config: &Config, file_name: &str) -> Result<String, GenError> {
+        let template = config
+            .commit_message
+            .as_deref()
+            .unwrap_or("migration: {description}");
+        let data = TemplateData::new(
+            self.name.as_deref().unwrap(),
+            self.schema.as_deref(),
+            self.column.as_deref(),
+            template.to_owned(),
+        );
+        let description = parse_file_name(file_name).map_or(file_name, |x| x.description);
+        let mut extensions = self.tokens();
+        extensions.tokens.extend([
+            ("description".to_owned(), description.to_owned()),
+            ("operation".to_owned(), self.operation_name()),
+        ]);
+        render_template(data, &extensions)
+    }
+
+    /// `file_name`, the migration's generated name, spelled the way the
+    /// root's `file_name_format` says.
+    fn custom_file_name(
+        &self,
+        format: &str,
+        file_name: &str,
+        language: Language,
+        today: NaiveDate,
+        index: i32,
+        width: usize,
+    ) -> Result<String, GenError> {
+        let data = TemplateData::new(
+            self.name.as_deref().unwrap(),
+            self.schema.as_deref(),
+            self.column.as_deref(),
+            format.to_owned(),
+        );
+        let description = parse_file_name(file_name).map_or(file_name, |x| x.description);
+        let date = today.format("%Y%m%d").to_string();
+        let prefix = format!("{date}{index:0width$}");
+        let mut extensions = self.tokens();
+        extensions.tokens.extend([
+            ("date".to_owned(), date),
+            ("index".to_owned(), format!("{index:0width$}")),
+            ("operation".to_owned(), self.operation_name()),
+            ("description".to_owned(), description.to_owned()),
+            ("extension".to_owned(), language.extension().to_owned()),
+        ]);
+        let custom = render_template(data, &extensions)?;
+        if !custom.starts_with(&prefix)
+            || custom.contains(['/', '\\'])
+            || parse_file_name(&custom).is_none()
+        {
+            let path = match self.root.join(CONFIG_FILE) {
+                path if path.exists() => path,
+                _ => self.root.join(ROOT_FILE),
+            };
+            return Err(GenError::MalformedConfig(
+                path,
+                format!(
+                    "file_name_format has to start with {prefix} and a space, _, - or ., \
+                     and end in .{}, not {custom:?}",
+                    language.extension()
+                ),
+            ));
+        }
+        Ok(custom)
+    }
+
+    /// The tables, schema and columns the migration names. A script's name
+    /// describes it instead, and a copy or a plugin's operation names
+    /// nothing gen writes.
+    fn identifiers(&self) -> Vec<String> {
+        #[cfg(feature = "plugins")]
+        if self.plugin_operation.is_some() {
+            return Vec::new();
+        }
+        if self.duplicate.is_some() || self.operation == Some(Operation::Script) {
+            return Vec::new();
+        }
+        let mut identifiers = Vec::new();
+        identifiers.extend(self.name.clone());
+        identifiers.extend(self.schema.clone());
+        identifiers.extend(self.rename_to.clone());
+        if let Some(column) = &self.column {
+            let columns = split_columns(column);
+            identifiers.extend(columns.iter().map(|x| column_name(x).to_owned()));
+        }
+        for key in &self.keys {
+            identifiers.extend(key.columns.iter().cloned());
+            identifiers.extend(key.referenced_table.clone());
+            identifiers.extend(key.referenced_column.clone());
+        }
+        if let Some((table, column)) = &self.references {
+            identifiers.push(table.clone());
+            identifiers.extend(column.clone());
+        }
+        identifiers
+    }
+
+    /// The generator with the identifiers [`Generator::identifiers`] lists
+    /// in `case`, the names of `--column`s without their types.
+    fn cased(&self, case: Case) -> Generator {
+        let mut generator = self.clone();
+        let apply = |x: &mut String| *x = case.apply(x);
+        generator.name.iter_mut().for_each(apply);
+        generator.schema.iter_mut().for_each(apply);
+        generator.rename_to.iter_mut().for_each(apply);
+        if let Some(column) = &mut generator.column {
+            *column = split_columns(column)
+                .iter()
+                .map(|x| {
+                    let name = column_name(x);
+                    let rest = x.split_once(':').map_or("", |(_, rest)| rest);
+                    match rest {
+                        "" => case.apply(name),
+                        rest => format!("{}:{rest}", case.apply(name)),
+                    }
+                })
+                .collect::<Vec<_>>()
+                .join(", ");
+        }
+        for key in &mut generator.keys {
+            key.columns.iter_mut().for_each(apply);
+            key.referenced_table.iter_mut().for_each(apply);
+            key.referenced_column.iter_mut().for_each(apply);
+        }
+        if let Some((table, column)) = &mut generator.references {
+            apply(table);
+            column.iter_mut().for_each(apply);
+        }
+        generator
+    }
+
+    /// Checks that every identifier of the migration can be written in the
+    /// dialect. A reserved keyword fails only where the root's `quoting`,
+    /// or the dialect's when unset, leaves it unquoted, unless quoting it is
+    /// allowed.
+    fn check_identifiers(&self) -> Result<(), GenError> {
+        let dialect = self.dialect.unwrap_or_default();
+        let quoting = self.config.as_ref().and_then(|x| x.quoting);
+        let quoting = quoting.unwrap_or(Quoting::of(dialect));
+        for name in self.identifiers() {
+            identifier::validate(dialect, &name)
+                .map_err(|x| GenError::InvalidIdentifier(name.clone(), x))?;
+            if !identifier::is_reserved(dialect, &name) {
+                continue;
+            }
+            match quoting.quotes(dialect, &name) || self.allow_keyword {
+                true => warn!("{name:?} is reserved in {}, quoting it", dialect.name()),
+                false => return Err(GenError::ReservedKeyword(name, dialect.name())),
+            }
+        }
+        Ok(())
+    }
+
+    /// Warns about every migration of the root that already creates the
+    /// table `create-table` creates: its SQL does, in the same schema, or,
+    /// without a schema, its name says so, as `create table users` does.
+    fn warn_existing(
+        &self,
+        config: &Config,
+        migrations: Option<&MigrationIndex>,
+    ) -> Result<(), GenError> {
+        let Some(name) = self.name.as_deref() else {
+            return Ok(());
+        };
+        let table = match &self.schema {
+            Some(schema) => format!("{schema}.{name}"),
+            None => name.to_owned(),
+        };
+        let described = Regex::new(&format!(
+            r"(?i)\bcreate[ _-]table[ _-]{}$",
+            regex::escape(name)
+        ))
+        .unwrap();
+        let mut scanned = None;
+        let migrations = match migrations {
+            Some(migrations) => migrations,
+            None => scanned.insert(self.scan(&self.root)),
+        };
+        for path in migrations.files() {
+            if config.is_rollback(&self.root, path) {
+                continue;
+            }
+            let Some(file_name) = path.file_name().and_then(|x| x.to_str()) else {
+                continue;
+            };
+            let Some(file_name) = parse_file_name(file_name) else {
+                continue;
+            };
+            let mut existing = self.schema.is_none() && described.is_match(file_name.description);
+            if !existing && file_name.language == Language::Sql {
+                let mut model = SchemaModel::default();
+                model.apply(&fs::read_to_string(path)?);
+                existing = model.tables.keys().any(|x| x.eq_ignore_ascii_case(&table));
+            }
+            if existing {
+                warn!(
+                    "{} already creates {table}, pass --ignore-existing if it is meant to",
+                    manifest::relative_path(&self.root, path)
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// The ticket the root's `ticket_pattern` finds in the name of the
+    /// branch checked out: what its first group matches, or all it matches
+    /// when it has none.
+    fn branch_ticket(&self, pattern: &str) -> Result<Option<String>, GenError> {
+        let regex = Regex::new(pattern).map_err(|x| {
+            let path = match self.root.join(CONFIG_FILE) {
+                path if path.exists() => path,
+                _ => self.root.join(ROOT_FILE),
+            };
+            GenError::MalformedConfig(path, format!("ticket_pattern: {x}"))
+        })?;
+        let Some(branch) = self.git().branch else {
+            return Ok(None);
+        };
+        let ticket = regex
+            .captures(&branch)
+            .and_then(|x| x.get(1).or_else(|| x.get(0)))
+            .map(|x| sanitize(x.as_str()));
+        Ok(ticket.filter(|x| !x.is_empty()))
+    }
+
+    /// `text` with every whole word `table` replaced with the generator's
+    /// name.
+    fn rename_table(&self, text: &str, table: Option<&str>) -> String {
+        let name = self.name.as_deref().unwrap();
+        match table.filter(|x| *x != name) {
+            Some(table) => {
+                let word = Regex::new(&format!(r"\b{}\b", regex::escape(table))).unwrap();
+                word.replace_all(text, regex::NoExpand(name)).into_owned()
+            }
+            None => text.to_owned(),
+        }
+    }
+
+    /// The name given to the index, constraint or trigger `operation` is
+    /// about, if it is about one.
+    fn object_name(&self, operation: &Operation) -> Option<&str> {
+        match operation.object_token()? {
+            "index_name" => self.index_name.as_deref(),
+            "trigger_name" => self.trigger_name.as_deref(),
+            _ => self.constraint_name.as_deref(),
+        }
+    }
+
+    /// The command line name of the operation being generated.
+    fn operation_name(&self) -> String {
+        if let Some(preset) = &self.preset {
+            return preset.clone();
+        }
+        #[cfg(feature = "plugins")]
+        if let Some(operation) = &self.plugin_operation {
+            return operation.clone();
+        }
+        self.operation.as_ref().unwrap().name()
+    }
+
+    /// The directories templates are looked up in, the root's own first.
+    fn template_directories(&self) -> Vec<PathBuf> {
+        templates::directories(&self.root, self.template_pack.as_deref())
+    }
+
+    /// The tokens every template can use besides the migration's own
+    /// arguments. `branch` is the sanitized git branch, `commit` the short
+    /// hash of `HEAD` and `author` the git identity unless one was given, all
+    /// empty outside a repository. Template variables come on top.
+    /// What git says, asking it unless a generation already did.
+    fn git(&self) -> GitContext {
+        match &self.git {
+            Some(git) => git.clone(),
+            None => GitContext::load(&self.root, self.author.as_deref()),
+        }
+    }
+
+    fn tokens(&self) -> Extensions {
+        let mut extensions = Extensions::default();
+        let git = self.git();
+        let branch = sanitize(&git.branch.unwrap_or_default());
+        extensions.tokens.insert("branch".to_owned(), branch);
+        extensions.tokens.insert("author".to_owned(), git.author);
+        let ticket = self.ticket.clone().unwrap_or_default();
+        extensions.tokens.insert("ticket".to_owned(), ticket);
+        extensions.tokens.insert("commit".to_owned(), git.commit);
+        let names = [
+            ("index_name", &self.index_name),
+            ("constraint_name", &self.constraint_name),
+            ("new_name", &self.rename_to),
+            ("trigger_name", &self.trigger_name),
+        ];
+        for (token, name) in names {
+            if let Some(name) = name {
+                extensions.tokens.insert(token.to_owned(), name.clone());
+            }
+        }
+        if let Some(timing) = self.timing {
+            let timing = timing.keyword().to_owned();
+            extensions.tokens.insert("timing".to_owned(), timing);
+        }
+        if !self.events.is_empty() {
+            let events = trigger::events(self.dialect.unwrap_or_default(), &self.events);
+            extensions.tokens.insert("events".to_owned(), events);
+        }
+        if let Some((table, column)) = &self.references {
+            extensions.tokens.extend([
+                ("referenced_table".to_owned(), table.clone()),
+                (
+                    "referenced_column".to_owned(),
+                    column.clone().unwrap_or_else(|| "id".to_owned()),
+                ),
+            ]);
+        }
+        if let Some(batch_size) = self.batch_size {
+            let batch_size = batch_size.to_string();
+            extensions
+                .tokens
+                .insert("batch_size".to_owned(), batch_size);
+        }
+        extensions.tokens.extend(self.vars.clone());
+        extensions.dialect = self.dialect;
+        extensions.partials = partials(&self.template_directories());
+        let language = self.language.or(self.config.as_ref().map(|x| x.language));
+        let dialect = self.dialect.unwrap_or_default();
+        if language.unwrap_or_default() == Language::Sql {
+            extensions.quoting = self.config.as_ref().and_then(|x| x.quoting);
+        }
+        if self.allow_keyword && language.unwrap_or_default() == Language::Sql {
+            let quoting = extensions.quoting.unwrap_or(Quoting::of(dialect));
+            extensions.quoted = self
+                .identifiers()
+                .into_iter()
+                .filter(|x| !quoting.quotes(dialect, x) && identifier::is_reserved(dialect, x))
+                .collect();
+        }
+        extensions.values = self.values.clone();
+        extensions.keys = self.keys.clone();
+        extensions.seed = self.seed.clone();
+        extensions
+    }
+
+    /// The file name and body of the migration numbered `index` on `today`.
+    fn render(
+        &self,
+        language: Language,
+        today: NaiveDate,
+        index: i32,
+        preset: Option<&Preset>,
+    ) -> Result<(String, Option<String>), GenError> {
+        if let Some((source, table)) = &self.duplicate {
+            let source_name = source.file_name().and_then(|x| x.to_str());
+            let description = source_name
+                .and_then(parse_file_name)
+                .map_or("copy", |x| x.description);
+            let description = self.rename_table(description, table.as_deref());
+            let content = self.rename_table(&fs::read_to_string(source)?, table.as_deref());
+            let file_name = format_file_name(language, today, index, &description);
+            return Ok((file_name, Some(content)));
+        }
+
+        #[cfg(feature = "plugins")]
+        if let Some(plugins) = &self.plugins {
+            let mut extensions = self.tokens();
+            let name = self.name.as_deref().unwrap();
+            let schema = self.schema.as_deref();
+            let column = self.column.as_deref();
+            let operation = self.operation_name();
+            extensions.extend(plugins.extensions(&operation, name, schema, column)?);
+            if self.plugin_operation.is_some() {
+                return plugins.render(&operation, name, schema, column, today, index, &extensions);
+            }
+            return self.render_builtin(language, today, index, preset, &extensions);
+        }
+
+        self.render_builtin(language, today, index, preset, &self.tokens())
+    }
+
+    fn render_builtin(
+        &self,
+        language: Language,
+        today: NaiveDate,
+        index: i32,
+        preset: Option<&Preset>,
+        extensions: &Extensions,
+    ) -> Result<(String, Option<String>), GenError> {
+        if let Some(preset) = preset {
+            return self.render_preset(language, today, index, preset, extensions);
+        }
+        let operation = self.operation.as_ref().unwrap();
+        let name = self.name.as_deref().unwrap();
+        let column = self.column.as_deref();
+        let file_name = match (self.object_name(operation), &self.rename_to) {
+            (Some(object_name), _) => {
+                let file_name_part = operation.object_file_name(object_name, name);
+                format_file_name(language, today, index, &file_name_part)
+            }
+            (None, Some(new_name)) if operation.new_name(name, column).is_some() => {
+                let file_name_part = operation.rename_file_name(name, column, new_name);
+                format_file_name(language, today, index, &file_name_part)
+            }
+            _ => compute_filename_in(language, operation, name, column, today, index)?,
+        };
+        let template = self.render_operation(language, operation, column, extensions)?;
+        Ok((file_name, template))
+    }
+
+    /// The body of `operation` on `column`, from the root's own template
+    /// when it has one.
+    fn render_operation(
+        &self,
+        language: Language,
+        operation: &Operation,
+        column: Option<&str>,
+        extensions: &Extensions,
+    ) -> Result<Option<String>, GenError> {
+        let name = self.name.as_deref().unwrap();
+        let schema = self.schema.as_deref();
+        let dialect = self.dialect.unwrap_or_default();
+        let audited = self.audit_columns && *operation == Operation::CreateTable;
+        let columns;
+        let column = match audited {
+            true => {
+                let audit = audit_columns(language, dialect)
+                    .ok_or(GenError::Unsupported(language.extension(), "audit columns"))?;
+                columns = column
+                    .into_iter()
+                    .chain(audit)
+                    .collect::<Vec<_>>()
+                    .join(",");
+                Some(columns.as_str())
+            }
+            false => column,
+        };
+        let body = match user_template(
+            &self.template_directories(),
+            language.template_directory(dialect),
+            operation,
+        )? {
+            Some(template) => each_column(operation, column, |column| {
+                let data = operation.template_data(dialect, name, schema, column, template.clone());
+                render_template(data, extensions).map(Some)
+            })?,
+            None => render_operation_with(
+                language, dialect, operation, name, schema, column, extensions,
+            )?,
+        };
+        if !audited || language != Language::Sql || dialect != Dialect::Postgres {
+            return Ok(body);
+        }
+        let trigger = TemplateData::new(name, schema, None, UPDATED_AT_TRIGGER);
+        let trigger = render_template(trigger, extensions)?;
+        Ok(Some(
+            body.into_iter()
+                .chain([trigger])
+                .collect::<Vec<_>>()
+                .join("\n\n"),
+        ))
+    }
+
+    /// A preset's steps rendered one after the other, in a file named after
+    /// the preset and the table, e.g. `2024061201 - new-entity users.sql`.
+    fn render_preset(
+        &self,
+        language: Language,
+        today: NaiveDate,
+        index: i32,
+        preset: &Preset,
+        extensions: &Extensions,
+    ) -> Result<(String, Option<String>), GenError> {
+        let name = self.name.as_deref().unwrap();
+        let schema = self.schema.as_deref();
+        let mut bodies = Vec::new();
+        for step in &preset.steps {
+            let body = match step {
+                Step::Operation { operation, column } => {
+                    let column = column.as_deref().or(self.column.as_deref());
+                    if operation.requires_column() && column.is_none() {
+                        return Err(GenError::MissingArgument("column"));
+                    }
+                    self.render_operation(language, operation, column, extensions)?
+                }
+                Step::Template { template } => {
+                    let data =
+                        TemplateData::new(name, schema, self.column.as_deref(), template.clone());
+                    Some(render_template(data, extensions)?)
+                }
+            };
+            bodies.extend(body);
+        }
+        let preset_name = self.preset.as_deref().unwrap();
+        let file_name = format_file_name(language, today, index, &format!("{preset_name} {name}"));
+        Ok((file_name, Some(bodies.join("\n\n"))))
+    }
+}
+
+/// Opens a new file at `path` for writing, failing if there already is one
+/// unless `force`, in which case it is truncated.
+fn create(path: &Path, force: bool) -> Result<File, GenError> {
+    let mut options = OpenOptions::new();
+    match force {
+        true => options.write(true).create(true).truncate(true),
+        false => options.write(true).create_new(true),
+    };
+    options.open(path).map_err(|error| match error.kind() {
+        std::io::ErrorKind::AlreadyExists => GenError::AlreadyExists(path.to_owned()),
+        _ => error.into(),
+    })
+}
+
+/// Runs the `post_create` hook `command` on `file` through the shell.
+fn post_create(root: &Path, command: &str, file: &Path) -> Result<(), GenError> {
+    let command = command.replace("{file}", &shell_quote(file));
+    let (shell, flag) = match cfg!(unix) {
+        true => ("sh", "-c"),
+        false => ("cmd", "/C"),
+    };
+    // What the hook prints goes with gen's own messages, not what gen prints.
+    let status = std::process::Command::new(shell)
+        .args([flag, &command])
+        .current_dir(root)
+        .stdout(std::io::stderr())
+        .status()
+        .map_err(|x| GenError::Hook(command.clone(), x.to_string()))?;
+    match status.success() {
+        true => Ok(()),
+        false => Err(GenError::Hook(command, status.to_string())),
+    }
+}
+
+/// `path` as one word of a shell command line.
+fn shell_quote(path: &Path) -> String {
+    let path = path.to_string_lossy();
+    match cfg!(unix) {
+        true => format!("'{}'", path.replace('\'', "'\\''")),
+        false => format!("\"{path}\""),
+    }
+}
+
+/// The partials, the templates named `_<name>.tmpl`, in the templates
+/// directories `templates`, by name. The earlier directory's win.
+fn partials(templates: &[PathBuf]) -> BTreeMap<String, String> {
+    let entries = templates
+        .iter()
+        .rev()
+        .flat_map(|x| fs::read_dir(x).into_iter().flatten());
+    entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let name = path.file_name()?.to_str()?;
+            let name = name.strip_prefix('_')?.strip_suffix(".tmpl")?.to_owned();
+            Some((name, fs::read_to_string(path).ok()?))
+        })
+        .collect()
+}
+
+/// The first template of `operation` in `directory` of the templates
+/// directories `templates`, e.g. `templates/cql/create_table.tmpl`, if any
+/// has one.
+fn user_template(
+    templates: &[PathBuf],
+    directory: impl AsRef<Path>,
+    operation: &Operation,
+) -> Result<Option<String>, GenError> {
+    for templates in templates {
+        let path = templates
+            .join(directory.as_ref())
+            .join(operation.template_file_name());
+        match fs::read_to_string(path) {
+            Ok(template) => return Ok(Some(template)),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+            Err(error) => return Err(error.into()),
+        }
+    }
+    Ok(None)
+}
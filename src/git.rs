@@ -0,0 +1,269 @@
+//! The few git queries gen needs, run through the `git` executable.
+
+use crate::error::GenError;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Runs `git` in `root` and returns its standard output.
+pub fn run<I, S>(root: &Path, args: I) -> Result<String, GenError>
+where
+    I: IntoIterator<Item = S>,
+    S: AsRef<std::ffi::OsStr>,
+{
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(root)
+        .args(args)
+        .output()?;
+    if !output.status.success() {
+        return Err(GenError::Git(
+            String::from_utf8_lossy(&output.stderr).trim().to_owned(),
+        ));
+    }
+    String::from_utf8(output.stdout).map_err(|x| GenError::Git(x.to_string()))
+}
+
+/// The branch checked out in the repository `root` belongs to, or `None`
+/// when `root` is not in a repository or `HEAD` is detached.
+pub fn current_branch(root: &Path) -> Option<String> {
+    let branch = run(root, ["rev-parse", "--abbrev-ref", "HEAD"]).ok()?;
+    let branch = branch.trim();
+    (!branch.is_empty() && branch != "HEAD").then(|| branch.to_owned())
+}
+
+/// The abbreviated hash of the commit checked out in the repository `root`
+/// belongs to, or `None` outside one or before its first commit.
+pub fn head_commit(root: &Path) -> Option<String> {
+    let commit = run(root, ["rev-parse", "--short", "HEAD"]).ok()?;
+    let commit = commit.trim();
+    (!commit.is_empty()).then(|| commit.to_owned())
+}
+
+/// The `user.name <user.email>` git commits in `root` are made as, or as much
+/// of it as is configured.
+pub fn author(root: &Path) -> Option<String> {
+    let config = |key| {
+        let value = run(root, ["config", key]).ok()?;
+        let value = value.trim();
+        (!value.is_empty()).then(|| value.to_owned())
+    };
+    match (config("user.name"), config("user.email")) {
+        (Some(name), Some(email)) => Some(format!("{name} <{email}>")),
+        (name, email) => name.or(email),
+    }
+}
+
+/// Writes the hook `name` (e.g. `pre-commit`) of the repository `root`
+/// belongs to so it runs `command` in `root`, and returns the hook's path. An
+/// existing hook is only replaced with `force`.
+pub fn install_hook(
+    root: &Path,
+    name: &str,
+    command: &str,
+    force: bool,
+) -> Result<PathBuf, GenError> {
+    let hooks = run(root, ["rev-parse", "--git-path", "hooks"])?;
+    let path = root.join(hooks.trim()).join(name);
+    if path.exists() && !force {
+        return Err(GenError::Git(format!(
+            "{} already exists, pass --force to replace it",
+            path.display()
+        )));
+    }
+    // Hooks run at the top of the work tree, which the root may be below.
+    let prefix = run(root, ["rev-parse", "--show-prefix"])?;
+    std::fs::create_dir_all(path.parent().unwrap())?;
+    std::fs::write(
+        &path,
+        format!(
+            "#!/bin/sh\n# Installed by gen.\ncd \"./{}\" || exit 1\nexec {command}\n",
+            prefix.trim()
+        ),
+    )?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755))?;
+    }
+    Ok(path)
+}
+
+/// Stages `paths` in the repository `root` belongs to.
+pub fn add(root: &Path, paths: &[PathBuf]) -> Result<(), GenError> {
+    let mut args = vec!["add".as_ref(), "--".as_ref()];
+    args.extend(paths.iter().map(|x| x.as_os_str()));
+    run(root, args)?;
+    Ok(())
+}
+
+/// How far a file is from being on the upstream branch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FileState {
+    /// Not known to git at all.
+    Untracked,
+    /// Added or changed since the last commit, staged or not.
+    Uncommitted,
+    /// Committed on a commit the upstream branch does not have.
+    Unpushed,
+}
+
+impl std::fmt::Display for FileState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.pad(match self {
+            FileState::Untracked => "untracked",
+            FileState::Uncommitted => "uncommitted",
+            FileState::Unpushed => "unpushed",
+        })
+    }
+}
+
+/// The files under `root` that are not on the upstream branch yet, by how far
+/// they are from it. Without an upstream branch nothing is `Unpushed`.
+pub fn file_states(root: &Path) -> Result<BTreeMap<PathBuf, FileState>, GenError> {
+    let mut states = BTreeMap::new();
+    let unpushed = run(
+        root,
+        [
+            "diff",
+            "--name-only",
+            "--relative",
+            "-z",
+            "@{upstream}...HEAD",
+            "--",
+        ],
+    );
+    // A repository without commits yet has nothing but uncommitted files.
+    let uncommitted = run(
+        root,
+        ["diff", "--name-only", "--relative", "-z", "HEAD", "--"],
+    )
+    .or_else(|_| run(root, ["ls-files", "-z"]))?;
+    let untracked = run(root, ["ls-files", "--others", "--exclude-standard", "-z"])?;
+    for (output, state) in [
+        (unpushed.unwrap_or_default(), FileState::Unpushed),
+        (uncommitted, FileState::Uncommitted),
+        (untracked, FileState::Untracked),
+    ] {
+        for path in paths(root, &output) {
+            states.insert(path, state);
+        }
+    }
+    Ok(states)
+}
+
+/// Commits `paths`, and nothing else that may be staged, with `message`.
+pub fn commit(root: &Path, paths: &[PathBuf], message: &str) -> Result<(), GenError> {
+    add(root, paths)?;
+    let mut args = vec!["commit".as_ref(), "--only".as_ref(), "-m".as_ref()];
+    args.push(message.as_ref());
+    args.push("--".as_ref());
+    args.extend(paths.iter().map(|x| x.as_os_str()));
+    run(root, args)?;
+    Ok(())
+}
+
+/// The files under `root` that were added between the commits `from` and
+/// `to`, relative to `root`.
+pub fn added_files(root: &Path, from: &str, to: &str) -> Result<Vec<PathBuf>, GenError> {
+    let output = run(
+        root,
+        [
+            "diff",
+            "--name-only",
+            "--relative",
+            "--no-renames",
+            "--diff-filter=A",
+            "-z",
+            from,
+            to,
+            "--",
+        ],
+    )?;
+    Ok(output
+        .split('\0')
+        .filter(|x| !x.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// The files under `root` that the current branch added since it forked from
+/// `base`, committed or not, as paths under `root`.
+pub fn branch_files(root: &Path, base: &str) -> Result<BTreeSet<PathBuf>, GenError> {
+    let fork = run(root, ["merge-base", base, "HEAD"])?;
+    let added = run(
+        root,
+        [
+            "diff",
+            "--name-only",
+            "--relative",
+            "--no-renames",
+            "--diff-filter=A",
+            "-z",
+            fork.trim(),
+            "--",
+        ],
+    )?;
+    let untracked = run(root, ["ls-files", "--others", "--exclude-standard", "-z"])?;
+    Ok(paths(root, &added).chain(paths(root, &untracked)).collect())
+}
+
+/// The files under `root` that git tracks, each with its position in the
+/// order they were first committed in, oldest first.
+pub fn commit_order(root: &Path) -> Result<BTreeMap<PathBuf, usize>, GenError> {
+    let output = run(
+        root,
+        [
+            "log",
+            "--reverse",
+            "--diff-filter=A",
+            "--no-renames",
+            "--name-only",
+            "--relative",
+            "--format=",
+            "-z",
+        ],
+    )?;
+    let mut order = BTreeMap::new();
+    for path in paths(root, &output) {
+        let position = order.len();
+        order.entry(path).or_insert(position);
+    }
+    Ok(order)
+}
+
+/// Whether `root` is in a repository.
+pub fn is_repository(root: &Path) -> bool {
+    run(root, ["rev-parse", "--git-dir"]).is_ok()
+}
+
+/// The files under `root` that git tracks, none when `root` is not in a
+/// repository.
+pub fn tracked_files(root: &Path) -> Result<BTreeSet<PathBuf>, GenError> {
+    if !is_repository(root) {
+        return Ok(BTreeSet::new());
+    }
+    let output = run(root, ["ls-files", "-z"])?;
+    Ok(paths(root, &output).collect())
+}
+
+/// Renames the tracked file `from` to `to`, staging both sides.
+pub fn mv(root: &Path, from: &Path, to: &Path) -> Result<(), GenError> {
+    run(
+        root,
+        [
+            "mv".as_ref(),
+            "--".as_ref(),
+            from.as_os_str(),
+            to.as_os_str(),
+        ],
+    )?;
+    Ok(())
+}
+
+fn paths<'a>(root: &'a Path, output: &'a str) -> impl Iterator<Item = PathBuf> + 'a {
+    output
+        .split('\0')
+        .filter(|x| !x.is_empty())
+        .map(|x| root.join(x))
+}
@@ -0,0 +1,166 @@
+//! The generations made in this checkout, so the latest ones can be undone.
+
+use crate::config::Config;
+use crate::counter::Counter;
+use crate::document;
+use crate::error::GenError;
+use crate::git;
+use crate::index::MigrationIndex;
+use crate::latest;
+use crate::manifest::{checksum, relative_path, Manifest, MANIFEST_FILE};
+use crate::naming::parse_file_name;
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File the history is kept in: in the git directory, so it belongs to the
+/// checkout and is never committed, or at the root outside a repository.
+pub const HISTORY_FILE: &str = "gen_history.jsonl";
+
+/// One generation.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Step {
+    pub when: String,
+    pub files: Vec<GeneratedFile>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct GeneratedFile {
+    /// Relative to the root.
+    pub path: String,
+    /// SHA-256 of the file as generated, like in the manifest.
+    pub checksum: String,
+}
+
+fn path(root: &Path) -> PathBuf {
+    match git::run(root, ["rev-parse", "--git-path", HISTORY_FILE]) {
+        Ok(path) => root.join(path.trim()),
+        Err(_) => root.join(format!(".{HISTORY_FILE}")),
+    }
+}
+
+/// Reads the history, oldest step first.
+pub fn load(root: &Path) -> Result<Vec<Step>, GenError> {
+    let path = path(root);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    fs::read_to_string(&path)?
+        .lines()
+        .filter(|x| !x.trim().is_empty())
+        .map(|x| serde_json::from_str(x).map_err(|_| GenError::MalformedHistory(path.clone())))
+        .collect()
+}
+
+/// Appends a step that generated `files` with the given contents.
+pub fn record(root: &Path, files: &[(PathBuf, &[u8])]) -> Result<(), GenError> {
+    let step = Step {
+        when: Local::now().to_rfc3339(),
+        files: files
+            .iter()
+            .map(|(path, content)| GeneratedFile {
+                path: relative_path(root, path),
+                checksum: checksum(content),
+            })
+            .collect(),
+    };
+    let mut steps = load(root)?;
+    steps.push(step);
+    store(root, &steps)
+}
+
+fn store(root: &Path, steps: &[Step]) -> Result<(), GenError> {
+    let path = path(root);
+    let temporary = PathBuf::from(format!("{}.tmp", path.display()));
+    let mut content = String::new();
+    for step in steps {
+        content += &serde_json::to_string(step).map_err(std::io::Error::other)?;
+        content.push('\n');
+    }
+    fs::write(&temporary, content)?;
+    fs::rename(&temporary, &path)?;
+    Ok(())
+}
+
+/// Removes the files of the last `steps` generations and their entries in
+/// the root's bookkeeping, returning the removed files. Nothing is removed
+/// if any of them was changed since or is in a commit.
+pub fn undo(root: &Path, config: &Config, steps: usize) -> Result<Vec<PathBuf>, GenError> {
+    let mut history = load(root)?;
+    let undone = history.split_off(history.len().saturating_sub(steps));
+    let files = undone
+        .iter()
+        .rev()
+        .flat_map(|x| &x.files)
+        .collect::<Vec<_>>();
+
+    let committed =
+        git::run(root, ["ls-tree", "-r", "--name-only", "-z", "HEAD"]).unwrap_or_default();
+    let committed = committed.split('\0').collect::<Vec<_>>();
+    for file in &files {
+        let path = root.join(&file.path);
+        // Gone already: nothing to protect.
+        let Ok(content) = fs::read(&path) else {
+            continue;
+        };
+        if checksum(&content) != file.checksum {
+            return Err(GenError::Undo(path, "changed since it was generated"));
+        }
+        if committed.contains(&file.path.as_str()) {
+            return Err(GenError::Undo(path, "already committed"));
+        }
+    }
+
+    let mut removed = Vec::new();
+    for file in files {
+        let path = root.join(&file.path);
+        if path.exists() {
+            fs::remove_file(&path)?;
+            removed.push(path);
+        }
+    }
+    forget(root, config, &removed)?;
+    store(root, &history)?;
+    Ok(removed)
+}
+
+/// Brings the manifest, `LATEST`, `MIGRATIONS.md` and the counter in line
+/// with `removed` being gone.
+fn forget(root: &Path, config: &Config, removed: &[PathBuf]) -> Result<(), GenError> {
+    let removed_paths = removed
+        .iter()
+        .map(|x| relative_path(root, x))
+        .collect::<Vec<_>>();
+    if root.join(MANIFEST_FILE).exists() {
+        let mut manifest = Manifest::load(root)?;
+        manifest
+            .migrations
+            .retain(|x| !removed_paths.contains(&x.path));
+        manifest.store(root)?;
+    }
+
+    let index = MigrationIndex::scan(root);
+    let newest = index
+        .files()
+        .filter_map(|x| Some((parse_file_name(x.file_name()?.to_str()?)?, x)))
+        .max_by_key(|(name, _)| (name.date, name.index))
+        .map(|(name, path)| (name.date, name.index, path.to_path_buf()));
+    if let Some(kind) = config.latest {
+        match &newest {
+            Some((_, _, path)) => latest::update(root, path, kind)?,
+            None => {
+                let _ = fs::remove_file(root.join(latest::LATEST_FILE));
+            }
+        }
+    }
+    if config.index_document {
+        document::write(root, &index)?;
+    }
+    // The counter goes back to the newest remaining migration, if it counted
+    // past it.
+    if let (Some(_), Some((date, index, _))) = (Counter::load(root)?, &newest) {
+        Counter::new(*date, *index).store(root)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,197 @@
+//! What scans skip: paths matching the root's `gen.ignore` or a
+//! `.gitignore` on the way, as git reads them. Ignored directories are not
+//! entered, so nothing in them is re-included.
+
+use regex::Regex;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// File at the root with `.gitignore` patterns of paths that are not
+/// migrations, e.g. test fixtures named like them.
+pub const IGNORE_FILE: &str = "gen.ignore";
+
+const GITIGNORE: &str = ".gitignore";
+
+/// The patterns of one ignore file, relative to its directory.
+#[derive(Debug)]
+struct Patterns {
+    base: PathBuf,
+    patterns: Vec<Pattern>,
+}
+
+#[derive(Debug)]
+struct Pattern {
+    regex: Regex,
+    negated: bool,
+    directories_only: bool,
+}
+
+impl Pattern {
+    /// The pattern of a line of an ignore file, if it has one.
+    fn parse(line: &str) -> Option<Pattern> {
+        let mut line = line.trim_end_matches(['\r', '\n']);
+        // Trailing spaces are dropped unless escaped.
+        while line.ends_with(' ') && !line.ends_with("\\ ") {
+            line = &line[..line.len() - 1];
+        }
+        if line.is_empty() || line.starts_with('#') {
+            return None;
+        }
+        let (negated, line) = match line.strip_prefix('!') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        let (directories_only, line) = match line.strip_suffix('/') {
+            Some(line) => (true, line),
+            None => (false, line),
+        };
+        // A slash anywhere but at the end anchors to the file's directory.
+        let anchored = line.contains('/');
+        let line = line.strip_prefix('/').unwrap_or(line);
+        let mut regex = String::from(match anchored {
+            true => "^",
+            false => "^(?:.*/)?",
+        });
+        let mut chars = line.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    match chars.next_if_eq(&'/') {
+                        Some(_) => regex.push_str("(?:.*/)?"),
+                        None => regex.push_str(".*"),
+                    }
+                }
+                '*' => regex.push_str("[^/]*"),
+                '?' => regex.push_str("[^/]"),
+                '[' => {
+                    let class = chars.by_ref().take_while(|x| *x != ']').collect::<String>();
+                    let class = match class.strip_prefix('!') {
+                        Some(class) => format!("^{class}"),
+                        None => class,
+                    };
+                    regex.push('[');
+                    regex.push_str(&class.replace('\\', "\\\\").replace('[', "\\["));
+                    regex.push(']');
+                }
+                '\\' => {
+                    if let Some(c) = chars.next() {
+                        regex.push_str(&regex::escape(&c.to_string()));
+                    }
+                }
+                _ => regex.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+        regex.push('$');
+        Some(Pattern {
+            regex: Regex::new(&regex).ok()?,
+            negated,
+            directories_only,
+        })
+    }
+}
+
+/// The ignore files that apply to a directory, outermost first.
+#[derive(Debug, Default, Clone)]
+pub struct Ignore {
+    files: Vec<Arc<Patterns>>,
+}
+
+impl Ignore {
+    /// The ignore files applying to `path` under `root`: the root's
+    /// `gen.ignore` and the `.gitignore` of every directory above `path`,
+    /// from the root down.
+    pub fn above(root: &Path, path: &Path) -> Ignore {
+        let mut ignore = Ignore::default();
+        ignore.read(root, IGNORE_FILE);
+        let Some(relative) = path.strip_prefix(root).ok().and_then(Path::parent) else {
+            return ignore;
+        };
+        let mut directory = root.to_path_buf();
+        ignore.read(&directory, GITIGNORE);
+        for component in relative.components() {
+            directory.push(component);
+            ignore.read(&directory, GITIGNORE);
+        }
+        ignore
+    }
+
+    /// These ignore files and `directory`'s own `.gitignore`, which apply to
+    /// what is in it.
+    pub fn descend(&self, directory: &Path) -> Ignore {
+        let mut ignore = self.clone();
+        ignore.read(directory, GITIGNORE);
+        ignore
+    }
+
+    fn read(&mut self, directory: &Path, name: &str) {
+        let Ok(content) = fs::read_to_string(directory.join(name)) else {
+            return;
+        };
+        let patterns = content
+            .lines()
+            .filter_map(Pattern::parse)
+            .collect::<Vec<_>>();
+        if !patterns.is_empty() {
+            self.files.push(Arc::new(Patterns {
+                base: directory.to_path_buf(),
+                patterns,
+            }));
+        }
+    }
+
+    /// Whether `path`, a directory when `is_directory`, is ignored. The last
+    /// pattern matching it decides, the innermost file's last.
+    pub fn is_ignored(&self, path: &Path, is_directory: bool) -> bool {
+        for file in self.files.iter().rev() {
+            let Some(relative) = path.strip_prefix(&file.base).ok().and_then(Path::to_str) else {
+                continue;
+            };
+            let relative = relative.replace(std::path::MAIN_SEPARATOR, "/");
+            let pattern = file
+                .patterns
+                .iter()
+                .rev()
+                .find(|x| (is_directory || !x.directories_only) && x.regex.is_match(&relative));
+            if let Some(pattern) = pattern {
+                return !pattern.negated;
+            }
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ignore(patterns: &str) -> Ignore {
+        Ignore {
+            files: vec![Arc::new(Patterns {
+                base: PathBuf::from("root"),
+                patterns: patterns.lines().filter_map(Pattern::parse).collect(),
+            })],
+        }
+    }
+
+    #[test]
+    fn patterns_match_as_git_reads_them() {
+        let ignore =
+            ignore("# fixtures\ntarget/\n/vendor\n*.bak.sql\ntests/**/fixtures\n!keep.bak.sql\n\n");
+        let ignored = |path: &str, is_directory| ignore.is_ignored(Path::new(path), is_directory);
+        assert!(ignored("root/target", true));
+        assert!(ignored("root/a/target", true));
+        assert!(!ignored("root/target", false));
+        assert!(ignored("root/vendor", true));
+        assert!(!ignored("root/a/vendor", true));
+        assert!(ignored("root/a/2024061201 - x.bak.sql", false));
+        assert!(!ignored("root/a/keep.bak.sql", false));
+        assert!(ignored("root/tests/fixtures", true));
+        assert!(ignored("root/tests/a/b/fixtures", true));
+        assert!(!ignored("root/2024061201 - x.sql", false));
+        assert!(!ignored("elsewhere/target", true));
+        assert!(Pattern::parse("# comment").is_none());
+        assert!(Pattern::parse("   ").is_none());
+    }
+}
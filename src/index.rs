@@ -0,0 +1,295 @@
+use crate::error::GenError;
+use crate::git;
+use crate::ignore::Ignore;
+use crate::language::Language;
+use crate::manifest::checksum;
+use crate::naming::classify_file_name;
+use chrono::NaiveDate;
+use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, SystemTime};
+
+/// File in the git directory remembering the migrations in each directory,
+/// so a scan only lists the directories modified since. Outside a
+/// repository it is `.gen_cache` at the root. Safe to delete.
+pub const CACHE_FILE: &str = "gen_cache";
+
+/// How long after its last modification a directory is cached. A file
+/// added within a filesystem's timestamp granularity of a listing could
+/// leave the modification time as cached.
+const SETTLE_TIME: Duration = Duration::from_secs(2);
+
+/// The migrations and subdirectories of a directory, as of its modification
+/// time, and whether it has a `.gitignore`. Hidden ones are left out, as
+/// scans skip them.
+#[derive(Serialize, Deserialize)]
+struct CachedDirectory {
+    modified: SystemTime,
+    files: Vec<String>,
+    directories: Vec<String>,
+    ignore_file: bool,
+}
+
+/// The migration files (`*.sql`, or another language's) found under a root,
+/// discovered once per run and shared by everything that needs to look at
+/// existing migrations. What the root ignores is left out.
+pub struct MigrationIndex {
+    root: PathBuf,
+    files: BTreeSet<PathBuf>,
+}
+
+impl MigrationIndex {
+    pub fn scan(root: &Path) -> Self {
+        MigrationIndex::scan_in(root, root)
+    }
+
+    /// Like `scan`, looking only below `directory`, one of `root`'s.
+    pub fn scan_in(root: &Path, directory: &Path) -> Self {
+        MigrationIndex {
+            root: root.to_path_buf(),
+            files: find_migration_files(root, directory).collect(),
+        }
+    }
+
+    /// Like `scan` for `directory` under `root`, reusing what the root's
+    /// cache file remembers of the directories not modified since, and
+    /// updating it.
+    pub fn scan_cached(root: &Path, directory: &Path) -> Self {
+        let Ok(start) = directory.strip_prefix(root) else {
+            return MigrationIndex::scan(directory);
+        };
+        let start = start.to_path_buf();
+        let path = cache_path(root);
+        let mut cache: BTreeMap<PathBuf, CachedDirectory> = fs::read(&path)
+            .ok()
+            .and_then(|x| serde_json::from_slice(&x).ok())
+            .unwrap_or_default();
+        let mut files = BTreeSet::new();
+        let mut scanned = BTreeMap::new();
+        let mut pending = vec![(start.clone(), Ignore::above(root, directory))];
+        while let Some((relative, ignore)) = pending.pop() {
+            let directory = root.join(&relative);
+            // Read before listing, so a change during the listing shows as
+            // a modification next time.
+            let Ok(modified) = fs::metadata(&directory).and_then(|x| x.modified()) else {
+                continue;
+            };
+            let cached = match cache.remove(&relative) {
+                Some(cached) if cached.modified == modified => cached,
+                _ => match list_directory(&directory, modified) {
+                    Ok(listed) => listed,
+                    Err(_) => continue,
+                },
+            };
+            // Ignore files are read every time, as editing one leaves its
+            // directory as cached.
+            let ignore = match cached.ignore_file {
+                true => ignore.descend(&directory),
+                false => ignore,
+            };
+            files.extend(
+                cached
+                    .files
+                    .iter()
+                    .map(|x| directory.join(x))
+                    .filter(|x| !ignore.is_ignored(x, false)),
+            );
+            pending.extend(
+                cached
+                    .directories
+                    .iter()
+                    .filter(|x| !ignore.is_ignored(&directory.join(x), true))
+                    .map(|x| (relative.join(x), ignore.clone())),
+            );
+            scanned.insert(relative, cached);
+        }
+
+        // Directories no longer there are dropped with the rest under the
+        // scanned one.
+        cache.retain(|x, _| !x.starts_with(&start));
+        cache.extend(
+            scanned
+                .into_iter()
+                .filter(|(_, x)| x.modified.elapsed().is_ok_and(|x| x > SETTLE_TIME)),
+        );
+        // A root that cannot be written to is only scanned slower.
+        if let Ok(json) = serde_json::to_vec(&cache) {
+            let temporary = PathBuf::from(format!("{}.tmp", path.display()));
+            let _ = fs::write(&temporary, json).and_then(|_| fs::rename(&temporary, &path));
+        }
+        MigrationIndex {
+            root: root.to_path_buf(),
+            files,
+        }
+    }
+
+    /// The root the index was scanned for.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(PathBuf::as_path)
+    }
+
+    /// Brings the entries at or below `path` in line with the filesystem.
+    pub fn refresh(&mut self, path: &Path) {
+        self.files.retain(|x| !x.starts_with(path));
+        if path.is_dir() {
+            self.files.extend(find_migration_files(&self.root, path));
+        } else if path.is_file()
+            && is_migration_file(path)
+            && !Ignore::above(&self.root, path).is_ignored(path, false)
+        {
+            self.files.insert(path.to_path_buf());
+        }
+    }
+
+    /// Counts `path` as a migration without looking at the filesystem.
+    pub fn add(&mut self, path: PathBuf) {
+        self.files.insert(path);
+    }
+
+    /// Returns the highest index used on `day`, failing if any migration is
+    /// dated after it.
+    pub fn last_index_for(&self, day: NaiveDate) -> Result<Option<i32>, GenError> {
+        self.last_index_matching(day, |x| x.file_name()?.to_str())
+    }
+
+    /// Like `last_index_for`, looking only at the migrations below `directory`.
+    pub fn last_index_under(
+        &self,
+        directory: &Path,
+        day: NaiveDate,
+    ) -> Result<Option<i32>, GenError> {
+        self.last_index_in(directory, "", day)
+    }
+
+    /// Like `last_index_under`, looking only at file names starting with
+    /// `prefix` and numbering what follows it.
+    pub fn last_index_in(
+        &self,
+        directory: &Path,
+        prefix: &str,
+        day: NaiveDate,
+    ) -> Result<Option<i32>, GenError> {
+        self.last_index_matching(day, |x| {
+            x.starts_with(directory)
+                .then(|| x.file_name()?.to_str()?.strip_prefix(prefix))
+                .flatten()
+        })
+    }
+
+    fn last_index_matching(
+        &self,
+        day: NaiveDate,
+        name: impl for<'a> Fn(&'a Path) -> Option<&'a str> + Sync,
+    ) -> Result<Option<i32>, GenError> {
+        let day = day.format("%Y%m%d").to_string();
+
+        // A future-dated file short-circuits the whole scan.
+        let last = self
+            .files
+            .par_iter()
+            .filter_map(|x| classify_file_name(name(x)?, &day))
+            .try_reduce_with(|a, b| Ok(a.max(b)));
+
+        match last {
+            Some(Ok(last)) => Ok(Some(last)),
+            Some(Err(date)) => Err(GenError::FutureDatedFile(date)),
+            None => Ok(None),
+        }
+    }
+}
+
+fn is_migration_file(path: &Path) -> bool {
+    path.extension()
+        .and_then(|x| x.to_str())
+        .and_then(Language::from_extension)
+        .is_some()
+}
+
+/// The migrations and subdirectories directly in `directory`. Names that
+/// are not UTF-8 could not be numbered anyway.
+fn list_directory(directory: &Path, modified: SystemTime) -> std::io::Result<CachedDirectory> {
+    let mut listed = CachedDirectory {
+        modified,
+        files: Vec::new(),
+        directories: Vec::new(),
+        ignore_file: false,
+    };
+    for entry in fs::read_dir(directory)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        if name == ".gitignore" {
+            listed.ignore_file = true;
+        }
+        if name.starts_with('.') {
+            continue;
+        } else if file_type.is_dir() {
+            listed.directories.push(name);
+        } else if file_type.is_file() && is_migration_file(Path::new(&name)) {
+            listed.files.push(name);
+        }
+    }
+    Ok(listed)
+}
+
+/// The cache file of `root`: in the git directory, with a name of its own
+/// for each root below the repository's top, or `.gen_cache` at the root
+/// outside a repository. Asking git once per root and process.
+fn cache_path(root: &Path) -> PathBuf {
+    static PATHS: LazyLock<Mutex<BTreeMap<PathBuf, PathBuf>>> = LazyLock::new(Default::default);
+    let mut paths = PATHS.lock().unwrap();
+    let path = paths.entry(root.to_path_buf()).or_insert_with(|| {
+        let output = git::run(
+            root,
+            ["rev-parse", "--show-prefix", "--git-path", CACHE_FILE],
+        );
+        let Some((prefix, path)) = output.as_deref().ok().and_then(|x| x.split_once('\n')) else {
+            return root.join(format!(".{CACHE_FILE}"));
+        };
+        let path = root.join(path.trim());
+        match prefix {
+            "" => path,
+            prefix => path.with_file_name(format!(
+                "{CACHE_FILE}-{}",
+                &checksum(prefix.as_bytes())[..16]
+            )),
+        }
+    });
+    path.clone()
+}
+
+/// The migrations below `directory`, one of `root`'s, found in parallel.
+fn find_migration_files(root: &Path, directory: &Path) -> impl Iterator<Item = PathBuf> {
+    jwalk::WalkDirGeneric::<(Ignore, ())>::new(directory)
+        .parallelism(jwalk::Parallelism::RayonDefaultPool {
+            busy_timeout: std::time::Duration::from_secs(1),
+        })
+        .root_read_dir_state(Ignore::above(root, directory))
+        .process_read_dir(|depth, path, ignore, children| {
+            // The first call is for the directory itself, not its entries.
+            if depth.is_none() {
+                return;
+            }
+            *ignore = ignore.descend(path);
+            children.retain(|x| {
+                x.as_ref().map_or(true, |x| {
+                    !ignore.is_ignored(&x.path(), x.file_type().is_dir())
+                })
+            });
+        })
+        .into_iter()
+        .filter_map(Result::ok)
+        .filter(|x| x.file_type().is_file())
+        .map(|x| x.path())
+        .filter(|x| is_migration_file(x))
+}
@@ -0,0 +1,134 @@
+//! Setting up a new gen root.
+
+use crate::config::{ROOT_FILE, TEMPLATES_DIR};
+use crate::dialect::Dialect;
+use crate::error::GenError;
+use crate::language::Language;
+use crate::Operation;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// The configuration `init --config` starts a root with: the common
+/// settings, commented out at their defaults.
+const STARTER_CONFIG: &str = r#"# Configuration of this gen root, which can move to gen.toml next to this
+# file. Every setting is optional.
+
+# Directory, relative to this file, that migrations are written to.
+# output_dir = "migrations"
+
+# "flat", "by-year" or "by-month" for YYYY or YYYY/MM subdirectories, or
+# "by-schema" for a subdirectory per schema.
+# layout = "flat"
+
+# "root" to number every migration against the whole root, "per-schema" or
+# "per-directory" to number each output or schema directory on its own.
+# numbering = "root"
+
+# Digits of the index after the date, 2 to 4.
+# index_width = 2
+
+# Time zone migrations are dated in: "local", "UTC" or an offset like "+05:30".
+# timezone = "local"
+
+# Schema of the migrations generated without --schema.
+# schema = "app"
+
+# "sql", "cql", "surql" or "cypher".
+# language = "sql"
+
+# For SQL, "postgres", "mysql", "mssql" or "sqlite".
+# dialect = "postgres"
+
+# Templates shared with other roots, a directory or a git URL, used where
+# templates/ has none.
+# template_pack = "git@github.com:org/sql-templates"
+
+# Forward migrations under up/ and an empty rollback under down/.
+# up_down_directories = false
+
+# Render a rollback with every migration, into down/ or as <name>.down.sql.
+# with_down = false
+
+# Give every new table created_at, updated_at and created_by columns.
+# audit_columns = false
+
+# Record every migration in migrations.json, regenerate MIGRATIONS.md and
+# keep a LATEST pointer to the newest migration.
+# manifest = false
+# index_document = false
+# latest = "file"
+
+# Stage what a generation writes, and the message of --commit's commit.
+# git_add = false
+# commit_message = "migration: {description}"
+
+# Where the ticket a migration is for, also given with --ticket, is found
+# in the branch name, and whether every migration needs one.
+# ticket_pattern = "[A-Z]+-[0-9]+"
+# require_ticket = false
+
+# How table, schema and column names are cased, snake_case, lower or
+# preserve, and which are quoted, always, when-needed or never.
+# identifier_case = "preserve"
+# quoting = "when-needed"
+
+# Commands run on every file a generation creates, {file} being its path.
+# post_create = ["sqlfluff fix {file}"]
+
+# Open new migrations in $VISUAL or $EDITOR, or in this command.
+# open_editor = false
+# editor = "code --wait"
+
+# Write a pgTAP test with every migration whose operation has one.
+# with_test = false
+
+# Append every change to gen.audit.jsonl.
+# audit = false
+"#;
+
+/// What `init` leaves in `templates/` to explain them.
+const TEMPLATES_README: &str = include_str!("../templates/README.md");
+
+/// Makes `directory` a gen root: writes the `.gen_root` marker, holding the
+/// starter configuration with `config`, and a README of what templates can
+/// use into `templates/`, along with the built-in SQL, rollback and pgTAP
+/// templates of `operations` to adapt. Every other template keeps falling
+/// back to the built-in one, so it gets the fixes later versions make.
+/// Files that exist are left alone; the ones written are returned.
+pub fn init(
+    directory: &Path,
+    config: bool,
+    operations: &[Operation],
+) -> Result<Vec<PathBuf>, GenError> {
+    let mut files = vec![(
+        directory.join(ROOT_FILE),
+        if config { STARTER_CONFIG } else { "" },
+    )];
+    let templates = directory.join(TEMPLATES_DIR);
+    files.push((templates.join("README.md"), TEMPLATES_README));
+    for operation in operations {
+        let file_name = operation.template_file_name();
+        if let Some(template) = Language::Sql.template(Dialect::Postgres, operation) {
+            files.push((templates.join(&file_name), template));
+        }
+        if let Some(template) = Language::Sql.down_template(Dialect::Postgres, operation) {
+            files.push((templates.join("down").join(&file_name), template));
+        }
+        if let Some(template) = operation.test_template() {
+            files.push((templates.join("pgtap").join(&file_name), template));
+        }
+    }
+
+    let mut written = Vec::new();
+    for (path, content) in files {
+        fs::create_dir_all(path.parent().unwrap())?;
+        match OpenOptions::new().write(true).create_new(true).open(&path) {
+            Ok(mut file) => file.write_all(content.as_bytes())?,
+            Err(error) if error.kind() == ErrorKind::AlreadyExists => continue,
+            Err(error) => return Err(error.into()),
+        }
+        written.push(path);
+    }
+    Ok(written)
+}
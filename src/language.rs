@@ -0,0 +1,234 @@
+//! Languages migrations can be written in. Each brings its file extension and
+//! templates; numbering ignores the language, so a root can mix them.
+
+use crate::dialect::Dialect;
+use crate::Operation;
+use serde::{Deserialize, Serialize};
+
+/// The built-in template of `$operation` among the ones listed, by the path
+/// of their file under `$directory` of `templates/`, without `.tmpl`.
+macro_rules! templates {
+    ($operation:expr, $directory:literal, { $($variant:ident: $file:literal),* $(,)? }) => {
+        match $operation {
+            $(Operation::$variant => Some(include_str!(concat!(
+                "../templates/",
+                $directory,
+                $file,
+                ".tmpl"
+            ))),)*
+            _ => None,
+        }
+    };
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Language {
+    #[default]
+    Sql,
+    /// Cassandra Query Language.
+    Cql,
+    /// SurrealQL.
+    Surql,
+    /// Neo4j's Cypher.
+    Cypher,
+}
+
+impl Language {
+    pub const ALL: [Language; 4] = [
+        Language::Sql,
+        Language::Cql,
+        Language::Surql,
+        Language::Cypher,
+    ];
+
+    /// The extension of the language's migration files, without the dot.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Language::Sql => "sql",
+            Language::Cql => "cql",
+            Language::Surql => "surql",
+            Language::Cypher => "cypher",
+        }
+    }
+
+    /// What starts a line comment.
+    pub fn comment(self) -> &'static str {
+        match self {
+            Language::Sql | Language::Cql | Language::Surql => "--",
+            Language::Cypher => "//",
+        }
+    }
+
+    pub fn from_extension(extension: &str) -> Option<Language> {
+        Language::ALL
+            .into_iter()
+            .find(|x| x.extension() == extension)
+    }
+
+    /// Where the language's templates are, in `dialect` for SQL, relative to
+    /// the directory of PostgreSQL templates.
+    #[cfg_attr(not(feature = "fs"), allow(dead_code))]
+    pub(crate) fn template_directory(self, dialect: Dialect) -> &'static str {
+        match self {
+            Language::Sql if dialect == Dialect::Postgres => "",
+            Language::Sql => dialect.name(),
+            Language::Cql => "cql",
+            Language::Surql => "surql",
+            Language::Cypher => "cypher",
+        }
+    }
+
+    /// The built-in template of `operation`, if it has one in this language,
+    /// in `dialect` for SQL.
+    pub(crate) fn template(self, dialect: Dialect, operation: &Operation) -> Option<&'static str> {
+        macro_rules! sql {
+            ($directory:literal, { $($variant:ident: $file:literal),* $(,)? }) => {
+                templates!(operation, $directory, {
+                    CreateTable: "create_table",
+                    AddColumn: "add_column",
+                    DropColumn: "drop_column",
+                    CreateIndex: "create_index",
+                    DropIndex: "drop_index",
+                    AddForeignKey: "add_foreign_key",
+                    DropForeignKey: "drop_foreign_key",
+                    AddConstraint: "add_constraint",
+                    DropConstraint: "drop_constraint",
+                    RenameTable: "rename_table",
+                    RenameColumn: "rename_column",
+                    CreateView: "create_view",
+                    AlterView: "alter_view",
+                    DropView: "drop_view",
+                    CreateTrigger: "create_trigger",
+                    DropTrigger: "drop_trigger",
+                    Seed: "seed",
+                    DataMigration: "data_migration",
+                    $($variant: $file,)*
+                })
+            };
+        }
+        macro_rules! other {
+            ($directory:literal, { $($variant:ident: $file:literal),* $(,)? }) => {
+                templates!(operation, $directory, {
+                    CreateTable: "create_table",
+                    AddColumn: "add_column",
+                    DropColumn: "drop_column",
+                    CreateIndex: "create_index",
+                    DropIndex: "drop_index",
+                    $($variant: $file,)*
+                })
+            };
+        }
+        match self {
+            Language::Sql => match dialect {
+                Dialect::Postgres => sql!("", {
+                    CreateMaterializedView: "create_materialized_view",
+                    RefreshMaterializedView: "refresh_materialized_view",
+                    CreateFunction: "create_function",
+                    AlterFunction: "alter_function",
+                    DropFunction: "drop_function",
+                    CreateProcedure: "create_procedure",
+                    AlterProcedure: "alter_procedure",
+                    DropProcedure: "drop_procedure",
+                    CreateSequence: "create_sequence",
+                    DropSequence: "drop_sequence",
+                    CreateEnum: "create_enum",
+                    AlterEnum: "alter_enum",
+                }),
+                Dialect::Mysql => sql!("mysql/", {
+                    CreateFunction: "create_function",
+                    AlterFunction: "alter_function",
+                    DropFunction: "drop_function",
+                    CreateProcedure: "create_procedure",
+                    AlterProcedure: "alter_procedure",
+                    DropProcedure: "drop_procedure",
+                }),
+                Dialect::Mssql => sql!("mssql/", {
+                    CreateFunction: "create_function",
+                    AlterFunction: "alter_function",
+                    DropFunction: "drop_function",
+                    CreateProcedure: "create_procedure",
+                    AlterProcedure: "alter_procedure",
+                    DropProcedure: "drop_procedure",
+                }),
+                // SQLite has no functions or procedures of its own.
+                Dialect::Sqlite => sql!("sqlite/", {}),
+            },
+            // Cassandra renames primary key columns only, and SurrealDB
+            // nothing.
+            Language::Cql => other!("cql/", {
+                RenameColumn: "rename_column",
+                Seed: "seed",
+            }),
+            Language::Surql => other!("surql/", {}),
+            // Only Neo4j has constraints, and no foreign keys.
+            Language::Cypher => other!("cypher/", {
+                AddConstraint: "add_constraint",
+                DropConstraint: "drop_constraint",
+                RenameTable: "rename_table",
+                RenameColumn: "rename_column",
+            }),
+        }
+    }
+
+    /// The built-in template undoing `operation`, if it has one. Only SQL
+    /// has rollback templates, in `down/` under each dialect's templates.
+    pub(crate) fn down_template(
+        self,
+        dialect: Dialect,
+        operation: &Operation,
+    ) -> Option<&'static str> {
+        macro_rules! down {
+            ($directory:literal, { $($variant:ident: $file:literal),* $(,)? }) => {
+                templates!(operation, $directory, {
+                    CreateTable: "down/create_table",
+                    DropTable: "down/drop_table",
+                    AddColumn: "down/add_column",
+                    DropColumn: "down/drop_column",
+                    CreateIndex: "down/create_index",
+                    DropIndex: "down/drop_index",
+                    AddForeignKey: "down/add_foreign_key",
+                    DropForeignKey: "down/drop_foreign_key",
+                    AddConstraint: "down/add_constraint",
+                    DropConstraint: "down/drop_constraint",
+                    RenameTable: "down/rename_table",
+                    RenameColumn: "down/rename_column",
+                    CreateView: "down/create_view",
+                    DropView: "down/drop_view",
+                    CreateTrigger: "down/create_trigger",
+                    DropTrigger: "down/drop_trigger",
+                    $($variant: $file,)*
+                })
+            };
+        }
+        match self {
+            Language::Sql => match dialect {
+                Dialect::Postgres => down!("", {
+                    CreateMaterializedView: "down/create_materialized_view",
+                    CreateFunction: "down/create_function",
+                    DropFunction: "down/drop_function",
+                    CreateProcedure: "down/create_procedure",
+                    DropProcedure: "down/drop_procedure",
+                    CreateSequence: "down/create_sequence",
+                    DropSequence: "down/drop_sequence",
+                    CreateEnum: "down/create_enum",
+                    AlterEnum: "down/alter_enum",
+                }),
+                Dialect::Mysql => down!("mysql/", {
+                    CreateFunction: "down/create_function",
+                    DropFunction: "down/drop_function",
+                    CreateProcedure: "down/create_procedure",
+                    DropProcedure: "down/drop_procedure",
+                }),
+                Dialect::Mssql => down!("mssql/", {
+                    CreateFunction: "down/create_function",
+                    DropFunction: "down/drop_function",
+                    CreateProcedure: "down/create_procedure",
+                    DropProcedure: "down/drop_procedure",
+                }),
+                Dialect::Sqlite => down!("sqlite/", {}),
+            },
+            _ => None,
+        }
+    }
+}
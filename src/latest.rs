@@ -0,0 +1,27 @@
+use crate::config::Latest;
+use crate::error::GenError;
+use crate::manifest::relative_path;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Pointer at the root to the most recently generated migration.
+pub const LATEST_FILE: &str = "LATEST";
+
+/// Points `LATEST` at `migration`, replacing the previous pointer atomically.
+/// A symlink is relative, so it survives moving the checkout; platforms
+/// without symlinks get a file holding the path instead.
+pub fn update(root: &Path, migration: &Path, kind: Latest) -> Result<(), GenError> {
+    let path = root.join(LATEST_FILE);
+    let target = relative_path(root, migration);
+    let temporary = PathBuf::from(format!("{}.tmp", path.display()));
+    if temporary.symlink_metadata().is_ok() {
+        fs::remove_file(&temporary)?;
+    }
+    match kind {
+        #[cfg(unix)]
+        Latest::Symlink => std::os::unix::fs::symlink(&target, &temporary)?,
+        _ => fs::write(&temporary, format!("{target}\n"))?,
+    }
+    fs::rename(&temporary, &path)?;
+    Ok(())
+}
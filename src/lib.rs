@@ -0,0 +1,93 @@
+#[cfg(feature = "fs")]
+use std::path::{Path, PathBuf};
+
+#[cfg(all(feature = "db", feature = "fs"))]
+pub mod apply;
+#[cfg(feature = "archive")]
+pub mod archive;
+#[cfg(feature = "fs")]
+pub mod audit;
+pub mod column;
+#[cfg(feature = "fs")]
+pub mod config;
+#[cfg(feature = "fs")]
+pub mod counter;
+#[cfg(feature = "db")]
+pub mod db;
+pub mod dialect;
+#[cfg(feature = "fs")]
+pub mod document;
+pub mod erd;
+mod error;
+#[cfg(feature = "ffi")]
+mod ffi;
+#[cfg(feature = "fs")]
+mod generator;
+#[cfg(feature = "fs")]
+pub mod git;
+#[cfg(feature = "fs")]
+pub mod history;
+pub mod identifier;
+#[cfg(feature = "fs")]
+pub mod ignore;
+#[cfg(feature = "fs")]
+pub mod index;
+#[cfg(feature = "fs")]
+pub mod init;
+pub mod language;
+#[cfg(feature = "fs")]
+pub mod latest;
+#[cfg(feature = "fs")]
+pub mod list;
+#[cfg(feature = "fs")]
+pub mod lock;
+#[cfg(feature = "fs")]
+pub mod manifest;
+mod naming;
+mod operation;
+#[cfg(feature = "plugins")]
+pub mod plugin;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "fs")]
+pub mod renumber;
+pub mod schema;
+#[cfg(feature = "fs")]
+pub mod search;
+pub mod seed;
+#[cfg(feature = "fs")]
+pub mod snapshot;
+#[cfg(feature = "fs")]
+pub mod templates;
+#[cfg(feature = "fs")]
+pub mod timings;
+pub mod trigger;
+#[cfg(feature = "fs")]
+pub mod verify;
+#[cfg(feature = "wasm")]
+mod wasm;
+
+pub use dialect::Dialect;
+pub use error::GenError;
+#[cfg(feature = "fs")]
+pub use generator::Generator;
+pub use language::Language;
+pub use naming::{index_after, last_index, next_index, parse_file_name, FileName};
+pub use operation::{compute_filename, frontmatter, render_operation, Operation, Variable};
+
+/// Walks up from `current_dir` to the nearest directory holding a `.gen_root`
+/// marker.
+#[cfg(feature = "fs")]
+pub fn find_root(current_dir: &Path) -> Result<PathBuf, GenError> {
+    let mut current_dir = current_dir.to_path_buf();
+    loop {
+        if current_dir.join(config::ROOT_FILE).exists() {
+            return Ok(current_dir.clone());
+        }
+        if current_dir.parent().is_some() {
+            current_dir.pop();
+        } else {
+            return Err(GenError::RootNotFound);
+        }
+    }
+}
@@ -0,0 +1,158 @@
+//! The migrations under a root, in the order they apply, for `list`.
+
+use crate::config::Config;
+use crate::index::MigrationIndex;
+use crate::manifest::relative_path;
+use crate::naming::parse_file_name;
+use crate::operation::Operation;
+use chrono::NaiveDate;
+use serde::Serialize;
+use std::path::Path;
+
+/// A migration as `list --json` prints it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Migration {
+    /// The path relative to the root, with forward slashes.
+    pub path: String,
+    pub service: Option<String>,
+    pub date: NaiveDate,
+    pub index: i32,
+    pub description: String,
+    pub operation: String,
+    pub table: Option<String>,
+    pub column: Option<String>,
+}
+
+/// Which migrations `list` prints; everything when left empty.
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    /// The first day to list, inclusively.
+    pub since: Option<NaiveDate>,
+    /// The last day to list, inclusively.
+    pub until: Option<NaiveDate>,
+    /// Only the migrations about this table, by their names.
+    pub table: Option<String>,
+}
+
+impl Filter {
+    fn matches(&self, migration: &Migration) -> bool {
+        self.since.is_none_or(|x| migration.date >= x)
+            && self.until.is_none_or(|x| migration.date <= x)
+            && self
+                .table
+                .as_ref()
+                .is_none_or(|x| migration.table.as_ref() == Some(x))
+    }
+}
+
+/// The migrations of `index` that `filter` lets through, by date and then
+/// index. Rollbacks and files that do not follow the naming scheme are left
+/// out.
+pub fn list(
+    root: &Path,
+    config: &Config,
+    index: &MigrationIndex,
+    filter: &Filter,
+) -> Vec<Migration> {
+    let mut migrations = index
+        .files()
+        .filter(|x| !config.is_rollback(root, x))
+        .filter_map(|path| {
+            let name = parse_file_name(path.file_name()?.to_str()?)?;
+            let (operation, table, column) = Operation::from_file_name(name.description);
+            Some(Migration {
+                path: relative_path(root, path),
+                service: name.service.map(ToOwned::to_owned),
+                date: name.date,
+                index: name.index,
+                description: name.description.to_owned(),
+                operation: operation.name(),
+                table,
+                column,
+            })
+        })
+        .filter(|x| filter.matches(x))
+        .collect::<Vec<_>>();
+    migrations.sort_by(|a, b| (a.date, a.index, &a.path).cmp(&(b.date, b.index, &b.path)));
+    migrations
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(day: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(day, "%Y%m%d").unwrap()
+    }
+
+    fn migration(date: &str, table: Option<&str>) -> Migration {
+        Migration {
+            path: format!("{date}01 - x.sql"),
+            service: None,
+            date: day(date),
+            index: 1,
+            description: "x".to_owned(),
+            operation: "script".to_owned(),
+            table: table.map(ToOwned::to_owned),
+            column: None,
+        }
+    }
+
+    #[test]
+    fn empty_filter_matches_everything() {
+        assert!(Filter::default().matches(&migration("20240612", None)));
+    }
+
+    #[test]
+    fn filter_matches_days_inclusively() {
+        let filter = Filter {
+            since: Some(day("20240612")),
+            until: Some(day("20240613")),
+            table: None,
+        };
+        assert!(!filter.matches(&migration("20240611", None)));
+        assert!(filter.matches(&migration("20240612", None)));
+        assert!(filter.matches(&migration("20240613", None)));
+        assert!(!filter.matches(&migration("20240614", None)));
+    }
+
+    #[test]
+    fn filter_matches_the_table() {
+        let filter = Filter {
+            table: Some("users".to_owned()),
+            ..Filter::default()
+        };
+        assert!(filter.matches(&migration("20240612", Some("users"))));
+        assert!(!filter.matches(&migration("20240612", Some("orders"))));
+        assert!(!filter.matches(&migration("20240612", None)));
+    }
+
+    #[test]
+    fn list_leaves_out_rollbacks_and_other_files() {
+        let root = Path::new("/nonexistent/root");
+        let mut index = MigrationIndex::scan(root);
+        for name in [
+            "2024061202 - add column email to users.sql",
+            "2024061201 - create table users.sql",
+            "2024061201 - create table users.down.sql",
+            "notes.sql",
+        ] {
+            index.add(root.join(name));
+        }
+        let migrations = list(root, &Config::default(), &index, &Filter::default());
+        let paths = migrations
+            .iter()
+            .map(|x| x.path.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            paths,
+            [
+                "2024061201 - create table users.sql",
+                "2024061202 - add column email to users.sql"
+            ]
+        );
+        assert_eq!(migrations[1].operation, "add-column");
+        assert_eq!(migrations[1].table.as_deref(), Some("users"));
+        assert_eq!(migrations[1].column.as_deref(), Some("email"));
+    }
+}
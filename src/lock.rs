@@ -0,0 +1,74 @@
+use crate::error::GenError;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// File at the root that exists while a generation allocates an index.
+pub const LOCK_FILE: &str = ".gen_root.lock";
+
+/// How long to wait for another generation to finish.
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+const RETRY_EVERY: Duration = Duration::from_millis(20);
+
+/// Held from scanning for the last index until the new migration exists, so
+/// concurrent generations in one root never pick the same index. Released
+/// when dropped.
+pub struct Lock {
+    path: PathBuf,
+}
+
+impl Lock {
+    /// Creates the lock file, waiting a while for another process holding it.
+    pub fn acquire(root: &Path) -> Result<Lock, GenError> {
+        let path = root.join(LOCK_FILE);
+        let start = Instant::now();
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    let lock = Lock { path };
+                    // Who holds it, for whoever finds it left behind.
+                    writeln!(file, "{}", std::process::id())?;
+                    return Ok(lock);
+                }
+                Err(error) if error.kind() == ErrorKind::AlreadyExists => {}
+                Err(error) => return Err(error.into()),
+            }
+            // A lock left behind by a generation that died is not taken
+            // over, as two waiters could then both remove it and both
+            // proceed.
+            if start.elapsed() > TIMEOUT {
+                return Err(GenError::Locked(path));
+            }
+            thread::sleep(RETRY_EVERY);
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lock_file_exists_while_held() {
+        let root = std::env::temp_dir().join(format!("thing-lock-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let lock = Lock::acquire(&root).unwrap();
+        let holder = fs::read_to_string(root.join(LOCK_FILE)).unwrap();
+        assert_eq!(holder.trim(), std::process::id().to_string());
+        drop(lock);
+        assert!(!root.join(LOCK_FILE).exists());
+        // Released, it can be taken again at once.
+        drop(Lock::acquire(&root).unwrap());
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -1,198 +1,1939 @@
-use chrono::{Local, NaiveDate};
-use clap::Parser;
+use chrono::NaiveDate;
+use clap::{Parser, Subcommand, ValueEnum};
 use log::{info, LevelFilter};
-use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::env;
-use std::fs::File;
-use std::io::Write;
+use std::io::{IsTerminal, Write};
 use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use thing::column::Key;
+use thing::config::{Config, Naming, Timezone};
+use thing::index::MigrationIndex;
+use thing::manifest::{relative_path, Manifest};
+use thing::schema::SchemaModel;
+use thing::templates::Source;
+use thing::timings::Timings;
+use thing::trigger::{Event, Timing};
+use thing::verify::{verify, verify_branch, verify_checksums, Problem, Rule};
+use thing::{
+    find_root, parse_file_name, Dialect, GenError, Generator, Language, Operation, Variable,
+};
+use watch::Clock;
 
-#[derive(Serialize)]
-struct TemplateData {
-    table_name: String,
-    column_name: Option<String>,
-    schema_name: Option<String>,
-    dot: Option<String>,
-    template: &'static str,
-}
-
-#[derive(Debug, clap::ValueEnum, Clone, Deserialize, Serialize)]
-#[serde(rename_all = "kebab-case")]
-enum Operation {
-    Script,
-    CreateTable,
-    AlterTable,
-    DropTable,
-    AddColumn,
-    AlterColumn,
-    DropColumn,
-}
-
-impl Operation {
-    fn to_file_name(&self, name: &str, column: Option<&str>) -> String {
-        match self {
-            Operation::Script => name.replace(' ', "_").to_string(),
-            Operation::CreateTable => format!("{} {}", "create table", name),
-            Operation::AlterTable => format!("{} {}", "alter table", name),
-            Operation::DropTable => format!("{} {}", "drop table", name),
-            Operation::AddColumn => format!("{} {} to {}", "add column", column.unwrap(), name),
-            Operation::AlterColumn => format!("{} {} in {}", "alter column", column.unwrap(), name),
-            Operation::DropColumn => format!("{} {} from {}", "drop column", column.unwrap(), name),
-        }
-    }
-
-    fn get_template_data(
-        &self,
-        name: &str,
-        schema: Option<&str>,
-        column: Option<&str>,
-    ) -> Option<TemplateData> {
-        match self {
-            Operation::Script => None,
-            Operation::CreateTable => Some(TemplateData {
-                table_name: name.to_owned(),
-                column_name: None,
-                schema_name: schema.map(ToString::to_string),
-                dot: schema.map(|_| ".".to_string()),
-                template: include_str!("../templates/create_table.tmpl"),
-            }),
-            Operation::AlterTable => None,
-            Operation::DropTable => None,
-            Operation::AddColumn => Some(TemplateData {
-                table_name: name.to_owned(),
-                column_name: column.map(ToString::to_string),
-                schema_name: schema.map(ToString::to_string),
-                dot: schema.map(|_| ".".to_string()),
-                template: include_str!("../templates/add_column.tmpl"),
-            }),
-            Operation::AlterColumn => None,
-            Operation::DropColumn => Some(TemplateData {
-                table_name: name.to_owned(),
-                column_name: column.map(ToString::to_string),
-                schema_name: schema.map(ToString::to_string),
-                dot: schema.map(|_| ".".to_string()),
-                template: include_str!("../templates/drop_column.tmpl"),
-            }),
-        }
-    }
-}
-
-#[derive(Parser, Debug, Deserialize, Serialize)]
-#[command()]
-struct Args {
-    operation: Operation,
+#[cfg(unix)]
+mod serve;
+mod watch;
+mod wizard;
 
-    #[clap(short, long)]
-    name: String,
+#[derive(Parser, Debug)]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
 
-    #[clap(short, long)]
-    column: Option<String>,
+    /// Number of threads used to scan the root for migrations
+    #[clap(short, long, global = true)]
+    jobs: Option<usize>,
+
+    /// Print how long each phase of the run took to stderr
+    #[clap(long, global = true)]
+    timings: bool,
+
+    /// Act as if it was this time, e.g. 2024-06-12T10:00:00Z or 2024-06-12,
+    /// for naming and future date checks
+    #[clap(long, global = true, env = "GEN_NOW", value_parser = parse_now)]
+    now: Option<NaiveDate>,
+
+    /// Date migrations in UTC instead of the root's configured time zone
+    #[clap(long, global = true)]
+    utc: bool,
+
+    /// List every directory of the root instead of only those modified since
+    /// its cache file was written
+    #[clap(long, global = true)]
+    no_cache: bool,
+}
+
+/// The date of `value`, an RFC 3339 time or a plain date. A time is taken in
+/// its own offset rather than the local one, so runs agree across machines.
+fn parse_now(value: &str) -> Result<NaiveDate, String> {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .map(|x| x.date_naive())
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y-%m-%d"))
+        .map_err(|_| format!("{value:?} is neither an RFC 3339 time nor a YYYY-MM-DD date"))
+}
+
+/// A `YYYYMMDD` or `YYYY-MM-DD` day.
+fn parse_day(value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value, "%Y%m%d")
+        .or_else(|_| NaiveDate::parse_from_str(value, "%Y-%m-%d"))
+        .map_err(|_| format!("{value:?} is neither YYYYMMDD nor YYYY-MM-DD"))
+}
+
+/// A `column:table.column` foreign key.
+fn parse_foreign_key(value: &str) -> Result<Key, String> {
+    Key::foreign_key(value).ok_or_else(|| format!("{value:?} is not column:table.column"))
+}
+
+/// A `key=value` template variable.
+fn parse_var(value: &str) -> Result<(String, String), String> {
+    value
+        .split_once('=')
+        .map(|(key, value)| (key.to_owned(), value.to_owned()))
+        .ok_or_else(|| format!("{value:?} is not key=value"))
+}
+
+#[derive(Subcommand, Debug)]
+enum Command {
+    #[command(flatten)]
+    Generate(Generate),
+    /// Generate one of the root's presets, a composite operation
+    Preset(PresetArgs),
+    /// Make the current directory a gen root, with templates to adapt in
+    /// templates/
+    Init {
+        /// Also write a starter configuration into .gen_root.
+        #[clap(long)]
+        config: bool,
+
+        /// Copy the built-in templates of this operation into templates/ to
+        /// adapt. Can be given several times; the built-in ones are used for
+        /// the others.
+        #[clap(long = "template", value_enum, value_name = "OPERATION")]
+        templates: Vec<Operation>,
+    },
+    /// Watch the root and report the next available number as it changes
+    Watch {
+        /// Also answer with the next number on every connection to this unix socket
+        #[clap(long)]
+        socket: Option<PathBuf>,
+
+        /// Number for this schema, as generating for it would.
+        #[clap(short, long)]
+        schema: Option<String>,
+
+        /// Number for this service of a monorepo.
+        #[clap(long)]
+        service: Option<String>,
+
+        /// Number for this output directory instead of the root's configured
+        /// one.
+        #[clap(long, value_name = "DIR")]
+        out: Option<PathBuf>,
+    },
+    /// Answer generate/list/next/lint requests as JSON-RPC on a unix socket
+    #[cfg(unix)]
+    Serve {
+        /// Path of the unix socket to listen on
+        #[clap(long, default_value = ".gen.sock")]
+        socket: PathBuf,
+    },
+    /// Summarise the migrations and list those that are not on the upstream
+    /// branch yet: untracked, uncommitted or unpushed
+    Status {
+        /// Also list which migrations the database applied, which changed
+        /// since and which are pending.
+        #[cfg(feature = "db")]
+        #[clap(long)]
+        database_url: Option<String>,
+
+        /// Print every migration as JSON, with its git state and, given a
+        /// database, its state there.
+        #[clap(long)]
+        json: bool,
+    },
+    /// List the migrations in the order they apply, oldest first
+    List {
+        /// Only those of today.
+        #[clap(long, conflicts_with = "since")]
+        today: bool,
+
+        /// Only those from this day on, e.g. 20240101 or 2024-01-01.
+        #[clap(long, value_parser = parse_day)]
+        since: Option<NaiveDate>,
+
+        /// Only those about this table.
+        #[clap(long)]
+        table: Option<String>,
+
+        /// Print them as a JSON array, with what their names say.
+        #[clap(long)]
+        json: bool,
+    },
+    /// Search the names and contents of the migrations, oldest first, failing
+    /// when nothing matches
+    Grep {
+        /// What to look for. A `table.column`, e.g. users.email, is also
+        /// found where the migrations about the table name the column.
+        pattern: String,
+
+        /// Take the pattern as a regular expression.
+        #[clap(short = 'E', long)]
+        regex: bool,
+
+        /// Match upper and lower case alike.
+        #[clap(short, long)]
+        ignore_case: bool,
+
+        /// Print the matches as a JSON array, with the date and index of
+        /// their migrations.
+        #[clap(long)]
+        json: bool,
+    },
+    /// Print the tables and columns the migrations add up to
+    Schema,
+    /// Draw the tables, columns and foreign keys the migrations add up to as
+    /// an entity relationship diagram
+    Erd {
+        #[arg(long, value_enum, default_value_t)]
+        format: thing::erd::Format,
+        /// Write the diagram to this file instead of printing it.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// List the generations made in this checkout, newest first
+    History,
+    /// Remove the files of the latest generations, unless they were changed
+    /// or committed since
+    Undo {
+        #[clap(long, default_value_t = 1)]
+        steps: usize,
+    },
+    /// Copy a migration, and its rollback, into a new one numbered as if
+    /// generated now
+    Duplicate {
+        /// The migration to copy.
+        path: PathBuf,
+        /// Rename the table the migration is about to this, in its name and
+        /// content.
+        #[clap(long)]
+        table: Option<String>,
+        /// The table the migration is about, for `--table` when its name does
+        /// not say, e.g. for a script.
+        #[clap(long, requires = "table")]
+        from: Option<String>,
+    },
+    /// Give a migration, and its rollback, a new description, keeping its
+    /// date and index
+    Rename {
+        /// The migration to rename.
+        path: PathBuf,
+
+        /// The new description, e.g. `add column email to users`.
+        #[clap(long)]
+        name: String,
+
+        /// Print the rename without making it.
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Regenerate MIGRATIONS.md, the table of contents of every migration
+    Index,
+    /// Check the migrations for colliding prefixes, future dates, names
+    /// that do not follow the scheme or are not UTF-8, and empty files
+    #[command(visible_alias = "check")]
+    Verify {
+        #[clap(long, value_enum, default_value_t)]
+        format: ReportFormat,
+
+        /// Also check the migrations against the checksums the manifest
+        /// recorded, for edits that `lock` did not record.
+        #[clap(long)]
+        checksums: bool,
+    },
+    /// Record what every migration holds now in the manifest, once they are
+    /// written, for `verify --checksums`
+    Lock,
+    /// Check the migrations the current branch added against its base, for
+    /// a pull request pipeline: what `verify` checks, plus migrations that
+    /// sort before the base's
+    CiCheck {
+        /// The branch the current one will be merged into.
+        #[clap(long, default_value = "origin/main")]
+        base: String,
+
+        #[clap(long, value_enum, default_value_t)]
+        format: ReportFormat,
+    },
+    /// Render every operation for a canned migration into __snapshots__
+    Snapshot {
+        /// Compare with the stored snapshots instead, failing on any
+        /// difference.
+        #[clap(long)]
+        check: bool,
+    },
+    /// Manage the git hooks that run `verify`
+    Hooks {
+        #[command(subcommand)]
+        command: HooksCommand,
+    },
+    /// List, print and copy out the templates operations are rendered with
+    Template {
+        #[command(subcommand)]
+        command: TemplateCommand,
+    },
+    /// Print the migrations added between two git revisions as Markdown
+    Changelog {
+        #[clap(long)]
+        from: String,
+
+        #[clap(long, default_value = "HEAD")]
+        to: String,
+    },
+    /// Renumber the migrations the current branch added so they follow the
+    /// ones on its base, e.g. after a rebase
+    Renumber {
+        /// The branch the current one forked from.
+        #[clap(long, default_value = "main")]
+        base: String,
+
+        /// Instead, give migrations that share their date and index, as
+        /// after a merge, the next free index of their day. The one committed
+        /// first keeps its index.
+        #[clap(long, conflicts_with = "base")]
+        collisions: bool,
+
+        /// Print the renames without making them.
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Run the migrations the database has not seen yet, oldest first, each
+    /// in a transaction with its record in _gen_migrations
+    #[cfg(feature = "db")]
+    Apply {
+        /// The database, e.g. postgres://localhost/app or sqlite:app.db.
+        #[clap(long, env = "DATABASE_URL")]
+        database_url: String,
+
+        /// Print the pending migrations without running them.
+        #[clap(long)]
+        dry_run: bool,
+    },
+    /// Bundle a year's migrations into archive/<year>.tar.zst and remove them
+    #[cfg(feature = "archive")]
+    Archive {
+        year: i32,
+
+        /// Leave the archived migrations in place.
+        #[clap(long)]
+        keep: bool,
+    },
+    /// Restore the migrations of a bundle made by `archive`
+    #[cfg(feature = "archive")]
+    Unarchive { bundle: PathBuf },
+    /// Generate a migration for an operation provided by a plugin
+    #[cfg(feature = "plugins")]
+    Plugin(PluginArgs),
+    /// List the loaded plugins and the operations they provide
+    #[cfg(feature = "plugins")]
+    Plugins,
+}
+
+#[derive(Subcommand, Debug)]
+enum HooksCommand {
+    /// Install a hook that runs `verify`
+    Install {
+        #[clap(long, value_enum, default_value_t = Hook::PreCommit)]
+        hook: Hook,
+
+        /// Replace an existing hook.
+        #[clap(long)]
+        force: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum TemplateCommand {
+    /// List the operations and whether the root has its own templates of
+    /// them or uses the built-in ones
+    List(TemplateKind),
+    /// Print the template an operation is rendered with
+    Show {
+        #[clap(value_enum)]
+        operation: Operation,
 
+        #[clap(flatten)]
+        kind: TemplateKind,
+    },
+    /// Copy the template an operation is rendered with, its template pack's
+    /// or the built-in one, into templates/ to adapt
+    Eject {
+        #[clap(value_enum)]
+        operation: Operation,
+
+        #[clap(flatten)]
+        kind: TemplateKind,
+
+        /// Replace the root's own template.
+        #[clap(long)]
+        force: bool,
+    },
+    /// Fetch the latest of the root's template pack when it is a git
+    /// repository
+    Update,
+}
+
+/// Which templates of the operations `template` is about.
+#[derive(clap::Args, Debug)]
+struct TemplateKind {
+    /// The templates of this language instead of the root's configured one.
+    #[clap(long, value_enum)]
+    language: Option<Language>,
+
+    /// The templates of this dialect instead of the root's configured one.
+    #[clap(long, value_enum)]
+    dialect: Option<Dialect>,
+
+    /// The rollback templates.
+    #[clap(long)]
+    down: bool,
+}
+
+impl TemplateKind {
+    /// The templates meant and the templates directory of the root's
+    /// template pack, if it has one.
+    fn kind(&self, root: &Path) -> Result<(thing::templates::Kind, Option<PathBuf>), GenError> {
+        let config = Config::load(root)?;
+        let kind = thing::templates::Kind {
+            language: self.language.unwrap_or(config.language),
+            dialect: self.dialect.unwrap_or(config.dialect),
+            down: self.down,
+        };
+        Ok((kind, thing::templates::pack(root, &config)?))
+    }
+}
+
+/// How `verify` and `ci-check` print what they find.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Default)]
+enum ReportFormat {
+    /// A line per problem.
+    #[default]
+    Text,
+    /// A JUnit report with a test case per rule, for CI.
+    Junit,
+}
+
+#[derive(clap::ValueEnum, Clone, Copy, Debug)]
+enum Hook {
+    PreCommit,
+    PrePush,
+}
+
+/// The table, or other object, a migration is about, given either first or
+/// as `--name`.
+#[derive(clap::Args, Debug)]
+struct Target {
+    /// The table, or other object, the migration is about.
+    #[clap(value_name = "NAME", required_unless_present = "name_flag")]
+    name: Option<String>,
+
+    /// The same, as a flag.
+    #[clap(
+        id = "name_flag",
+        short = 'n',
+        long = "name",
+        visible_alias = "table",
+        value_name = "NAME",
+        conflicts_with = "name"
+    )]
+    flag: Option<String>,
+}
+
+impl Target {
+    fn name(&self) -> &str {
+        self.name
+            .as_deref()
+            .or(self.flag.as_deref())
+            .expect("clap requires a name")
+    }
+}
+
+/// The arguments every operation takes.
+#[derive(clap::Args, Debug)]
+struct Common {
     #[clap(short, long)]
     schema: Option<String>,
+
+    /// Generate for this service of a monorepo.
+    #[clap(long)]
+    service: Option<String>,
+
+    /// Track the next index in a counter file at the root instead of scanning.
+    /// Once the file exists it is used without this flag.
+    #[clap(long)]
+    counter: bool,
+
+    /// Write into the current directory instead of the root's configured
+    /// output directory.
+    #[clap(long, conflicts_with = "out")]
+    here: bool,
+
+    /// Write into this directory instead of the root's configured output
+    /// directory, laid out the way the root says.
+    #[clap(long, value_name = "DIR")]
+    out: Option<PathBuf>,
+
+    /// Stage the generated files with git.
+    #[clap(long, visible_alias = "git")]
+    git_add: bool,
+
+    /// Commit the generated files, and nothing else, with the root's
+    /// `commit_message`.
+    #[clap(long)]
+    commit: bool,
+
+    /// Also write a pgTAP test asserting the migration's effect.
+    #[clap(long)]
+    with_test: bool,
+
+    /// Also write a rollback undoing the migration.
+    #[clap(long)]
+    with_down: bool,
+
+    /// Fill the `author` token with this instead of the git identity.
+    #[clap(long)]
+    author: Option<String>,
+
+    /// Write the migration in this language instead of the root's configured
+    /// one.
+    #[clap(long, value_enum)]
+    language: Option<Language>,
+
+    /// Write SQL for this database instead of the root's configured one.
+    #[clap(long, value_enum)]
+    dialect: Option<Dialect>,
+
+    /// Name the migration this way instead of the root's configured way:
+    /// `migrate` for golang-migrate and dbmate.
+    #[clap(long, value_enum)]
+    naming: Option<Naming>,
+
+    /// Set a template variable, e.g. `--var owner=billing`, for templates,
+    /// `file_name_format` and the commit message alike. Can be given several
+    /// times. Those the templates declare and are not given are asked for
+    /// when run in a terminal.
+    #[clap(long = "var", value_parser = parse_var)]
+    vars: Vec<(String, String)>,
+
+    /// Only apply the migration in this environment, e.g. `prod`. Can be
+    /// given several times.
+    #[clap(long = "env")]
+    envs: Vec<String>,
+
+    /// The ticket the migration is for, e.g. `DB-1234`, put in its file name
+    /// and a comment at its top, instead of the one the root's
+    /// `ticket_pattern` finds in the branch name.
+    #[clap(long)]
+    ticket: Option<String>,
+
+    /// Quote tables and columns named after a keyword the dialect reserves,
+    /// e.g. `order`, instead of failing.
+    #[clap(long)]
+    allow_keyword: bool,
+
+    /// Do not warn when `create-table` creates a table an existing
+    /// migration already creates.
+    #[clap(long)]
+    ignore_existing: bool,
+
+    /// Overwrite files by the names generated instead of failing.
+    #[clap(long)]
+    force: bool,
+
+    /// Print the migration's path and content, and those of its rollback and
+    /// test, instead of writing anything.
+    #[clap(long, conflicts_with = "print")]
+    dry_run: bool,
+
+    /// Print what is written, as with `--dry-run`, and write it.
+    #[clap(long)]
+    print: bool,
+
+    /// Print nothing but the migration's content, for piping into another
+    /// program, instead of writing anything.
+    #[clap(long, conflicts_with_all = ["dry_run", "print"])]
+    stdout: bool,
+
+    /// Open the migration in the root's `editor`, `$VISUAL` or `$EDITOR`
+    /// once written.
+    #[clap(long, conflicts_with_all = ["dry_run", "stdout"])]
+    edit: bool,
+}
+
+/// What `generate_migration` prints of what it generates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Print {
+    Nothing,
+    /// Every file with its path.
+    Files,
+    /// The migrations' content alone.
+    Migrations,
 }
 
-impl Args {
-    fn validate(&self) -> anyhow::Result<()> {
-        match self.operation {
-            Operation::AddColumn | Operation::AlterColumn | Operation::DropColumn
-                if self.column.is_none() =>
-            {
-                return Err(anyhow::anyhow!("column is required"))
+impl Common {
+    /// What is printed of what is generated.
+    fn print(&self) -> Print {
+        match (self.dry_run || self.print, self.stdout) {
+            (_, true) => Print::Migrations,
+            (true, false) => Print::Files,
+            (false, false) => Print::Nothing,
+        }
+    }
+
+    /// Whether to open the migration in the editor once written: always
+    /// with `--edit`, never when nothing is written, and as the root's
+    /// `open_editor` says otherwise.
+    fn edit(&self) -> Option<bool> {
+        match self.dry_run || self.stdout {
+            true => Some(false),
+            false => self.edit.then_some(true),
+        }
+    }
+
+    fn apply(&self, mut generator: Generator, current_dir: &Path) -> Generator {
+        generator = generator
+            .counter(self.counter)
+            .git_add(self.git_add)
+            .commit(self.commit)
+            .with_test(self.with_test)
+            .with_down(self.with_down)
+            .dry_run(self.dry_run || self.stdout)
+            .force(self.force)
+            .allow_keyword(self.allow_keyword)
+            .ignore_existing(self.ignore_existing);
+        if let Some(schema) = &self.schema {
+            generator = generator.schema(schema);
+        }
+        if let Some(service) = &self.service {
+            generator = generator.service(service);
+        }
+        if let Some(author) = &self.author {
+            generator = generator.author(author);
+        }
+        if let Some(language) = self.language {
+            generator = generator.language(language);
+        }
+        if let Some(dialect) = self.dialect {
+            generator = generator.dialect(dialect);
+        }
+        if let Some(naming) = self.naming {
+            generator = generator.naming(naming);
+        }
+        for (name, value) in &self.vars {
+            generator = generator.var(name, value);
+        }
+        for env in &self.envs {
+            generator = generator.env(env);
+        }
+        if let Some(ticket) = &self.ticket {
+            generator = generator.ticket(ticket);
+        }
+        if self.here {
+            generator = generator.output_dir(current_dir);
+        }
+        if let Some(out) = &self.out {
+            generator = generator.output_dir(current_dir.join(out));
+        }
+        generator
+    }
+}
+
+/// What an operation takes on top of its name and the common arguments.
+trait OperationArgs {
+    fn apply(&self, generator: Generator, current_dir: &Path) -> Generator;
+}
+
+/// The subcommands generating a migration for each operation, with what it
+/// takes, e.g. `create-table users --pk id`.
+macro_rules! operations {
+    ($($(#[doc = $doc:literal])* $variant:ident($args:ty),)*) => {
+        #[derive(Subcommand, Debug)]
+        enum Generate {
+            $(
+                $(#[doc = $doc])*
+                $variant {
+                    #[command(flatten)]
+                    target: Target,
+                    #[command(flatten)]
+                    args: $args,
+                    #[command(flatten)]
+                    common: Common,
+                },
+            )*
+        }
+
+        impl Generate {
+            fn generator(&self, root: &Path, current_dir: &Path) -> Generator {
+                let (operation, target, args, common): (_, _, &dyn OperationArgs, _) = match self {
+                    $(Generate::$variant { target, args, common } => {
+                        (Operation::$variant, target, args, common)
+                    })*
+                };
+                let generator = Generator::new(root).operation(operation).name(target.name());
+                let generator = args.apply(generator, current_dir);
+                common.apply(generator, current_dir)
+            }
+
+            fn common(&self) -> &Common {
+                match self {
+                    $(Generate::$variant { common, .. } => common,)*
+                }
             }
-            _ => {}
         }
-        Ok(())
+    };
+}
+
+operations! {
+    /// Generate an empty migration to write by hand
+    Script(NoArgs),
+    /// Create a table, with the given columns and keys
+    CreateTable(CreateTableArgs),
+    /// Alter a table
+    AlterTable(NoArgs),
+    /// Drop a table
+    DropTable(NoArgs),
+    /// Add columns, a statement each, with their types and constraints if
+    /// given
+    AddColumn(ColumnsArgs),
+    /// Alter a column
+    AlterColumn(ColumnArgs),
+    /// Drop columns, a statement each
+    DropColumn(ColumnsArgs),
+    /// Create an index on columns
+    CreateIndex(IndexArgs),
+    /// Drop the index on columns
+    DropIndex(IndexArgs),
+    /// Add a foreign key from columns
+    AddForeignKey(ForeignKeyArgs),
+    /// Drop the foreign key from columns
+    DropForeignKey(ConstraintArgs),
+    /// Add a check or unique constraint, whose definition is left to fill in
+    AddConstraint(ConstraintArgs),
+    /// Drop a check or unique constraint
+    DropConstraint(ConstraintArgs),
+    /// Rename a table
+    RenameTable(RenameTableArgs),
+    /// Rename a column
+    RenameColumn(RenameColumnArgs),
+    /// Create a view
+    CreateView(NoArgs),
+    /// Alter a view
+    AlterView(NoArgs),
+    /// Drop a view
+    DropView(NoArgs),
+    /// Create a PostgreSQL materialized view
+    CreateMaterializedView(NoArgs),
+    /// Refresh a PostgreSQL materialized view
+    RefreshMaterializedView(NoArgs),
+    /// Create a function
+    CreateFunction(RoutineArgs),
+    /// Alter a function
+    AlterFunction(RoutineArgs),
+    /// Drop a function
+    DropFunction(RoutineArgs),
+    /// Create a procedure
+    CreateProcedure(RoutineArgs),
+    /// Alter a procedure
+    AlterProcedure(RoutineArgs),
+    /// Drop a procedure
+    DropProcedure(RoutineArgs),
+    /// Create a trigger on a table, with its function on PostgreSQL
+    CreateTrigger(CreateTriggerArgs),
+    /// Drop a trigger from a table
+    DropTrigger(TriggerArgs),
+    /// Create a PostgreSQL sequence
+    CreateSequence(NoArgs),
+    /// Drop a PostgreSQL sequence
+    DropSequence(NoArgs),
+    /// Create a PostgreSQL enum type
+    CreateEnum(EnumArgs),
+    /// Add labels to a PostgreSQL enum type, outside a transaction
+    AlterEnum(AlterEnumArgs),
+    /// Insert the rows of a CSV file, whose header names the columns, into a
+    /// table
+    Seed(SeedArgs),
+    /// Update rows of a table in a transaction that checks how many it
+    /// updated
+    DataMigration(DataMigrationArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct NoArgs {}
+
+impl OperationArgs for NoArgs {
+    fn apply(&self, generator: Generator, _: &Path) -> Generator {
+        generator
     }
 }
 
-fn main() -> anyhow::Result<()> {
-    env_logger::builder().filter_level(LevelFilter::Info).init();
-    let args = Args::parse();
-    args.validate()?;
+/// The comma separated `--column`s, joined when given several times.
+fn columns(generator: Generator, columns: &[String]) -> Generator {
+    match columns {
+        [] => generator,
+        columns => generator.column(columns.join(",")),
+    }
+}
 
-    let current_dir = env::current_dir()?;
-    info!("current dir: {:?}", current_dir);
-    let root = find_root(&current_dir)?;
-    info!("root path: {:?}", root);
+#[derive(clap::Args, Debug)]
+struct CreateTableArgs {
+    /// The columns, comma separated, each optionally with its type and
+    /// constraints, e.g. `age:int:not null default 0`. Can be given several
+    /// times.
+    #[clap(short, long = "column", visible_alias = "columns")]
+    columns: Vec<String>,
 
-    let last_index = find_last_file_for_current_day(&root)?;
+    /// Give the table `created_at`, `updated_at` and `created_by` columns,
+    /// kept current by a trigger on PostgreSQL.
+    #[clap(long)]
+    audit: bool,
 
-    let current_date = Local::now().date_naive().format("%Y%m%d");
+    /// The comma separated columns of the primary key.
+    #[clap(long)]
+    pk: Option<String>,
 
-    let index = last_index.map(|index| index + 1).unwrap_or(1);
-    let file_name_part = args
-        .operation
-        .to_file_name(&args.name, args.column.as_deref());
-    let file_name = format!("{current_date}{index:02} - {file_name_part}.sql");
-    info!("writing file {file_name}");
+    /// Comma separated columns the table keeps unique. Can be given several
+    /// times.
+    #[clap(long)]
+    unique: Vec<String>,
 
-    let template = args
-        .operation
-        .get_template_data(&args.name, args.schema.as_deref(), args.column.as_deref())
-        .map(|data| render_template(&data));
+    /// A foreign key, as `column:table.column`, or `column:table` to
+    /// reference its `id`. Can be given several times.
+    #[clap(long, value_parser = parse_foreign_key)]
+    fk: Vec<Key>,
+}
 
-    let mut file = File::create(current_dir.join(file_name))?;
-    if let Some(template) = template {
-        let template = template?;
-        file.write_all(template.as_bytes())?;
+impl OperationArgs for CreateTableArgs {
+    fn apply(&self, generator: Generator, _: &Path) -> Generator {
+        let mut generator = columns(generator, &self.columns).audit_columns(self.audit);
+        if let Some(pk) = &self.pk {
+            generator = generator.key(Key::primary_key(pk));
+        }
+        for unique in &self.unique {
+            generator = generator.key(Key::unique(unique));
+        }
+        for fk in &self.fk {
+            generator = generator.key(fk.clone());
+        }
+        generator
     }
+}
 
-    Ok(())
+#[derive(clap::Args, Debug)]
+struct ColumnsArgs {
+    /// The columns, comma separated, each optionally with its type and
+    /// constraints, e.g. `age:int:not null default 0`. Can be given several
+    /// times.
+    #[clap(short, long = "column", visible_alias = "columns", required = true)]
+    columns: Vec<String>,
+
+    /// Write a migration per column.
+    #[clap(long)]
+    per_file: bool,
 }
 
-fn render_template(template_data: &TemplateData) -> anyhow::Result<String> {
-    let mut engine = tinytemplate::TinyTemplate::new();
-    engine.add_template("template", template_data.template)?;
-    Ok(engine.render("template", template_data)?)
+impl OperationArgs for ColumnsArgs {
+    fn apply(&self, generator: Generator, _: &Path) -> Generator {
+        columns(generator, &self.columns).per_file(self.per_file)
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct ColumnArgs {
+    /// The column.
+    #[clap(short, long)]
+    column: String,
+}
+
+impl OperationArgs for ColumnArgs {
+    fn apply(&self, generator: Generator, _: &Path) -> Generator {
+        generator.column(&self.column)
+    }
 }
 
-fn find_root(current_dir: &Path) -> anyhow::Result<PathBuf> {
-    let mut current_dir = current_dir.to_path_buf();
-    loop {
-        if current_dir.join(".gen_root").exists() {
-            return Ok(current_dir.clone());
+#[derive(clap::Args, Debug)]
+struct IndexArgs {
+    /// The comma separated columns. Can be given several times.
+    #[clap(short, long = "column", visible_alias = "columns", required = true)]
+    columns: Vec<String>,
+
+    /// Name the index instead of `idx_<table>_<columns>`.
+    #[clap(long)]
+    index_name: Option<String>,
+}
+
+impl OperationArgs for IndexArgs {
+    fn apply(&self, generator: Generator, _: &Path) -> Generator {
+        let generator = columns(generator, &self.columns);
+        match &self.index_name {
+            Some(index_name) => generator.index_name(index_name),
+            None => generator,
         }
-        if current_dir.parent().is_some() {
-            current_dir.pop();
-        } else {
-            return Err(anyhow::anyhow!("Could not find any gen root"));
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct ConstraintArgs {
+    /// The comma separated columns. Can be given several times.
+    #[clap(short, long = "column", visible_alias = "columns", required = true)]
+    columns: Vec<String>,
+
+    /// Name the constraint instead of `fk_<table>_<columns>` or
+    /// `ck_<table>_<columns>`.
+    #[clap(long)]
+    constraint_name: Option<String>,
+}
+
+impl OperationArgs for ConstraintArgs {
+    fn apply(&self, generator: Generator, _: &Path) -> Generator {
+        let generator = columns(generator, &self.columns);
+        match &self.constraint_name {
+            Some(constraint_name) => generator.constraint_name(constraint_name),
+            None => generator,
         }
     }
 }
 
-fn find_last_file_for_current_day(root: &Path) -> anyhow::Result<Option<i32>> {
-    let regex = regex::Regex::new("^\\d{8}(\\d{2}).*$")?;
-    let sql_files = glob::glob(&format!("{}/**/*.sql", root.to_str().unwrap()))?;
+#[derive(clap::Args, Debug)]
+struct ForeignKeyArgs {
+    #[command(flatten)]
+    constraint: ConstraintArgs,
 
-    let last = sql_files
-        .into_iter()
-        .filter_map(Result::ok)
-        .filter_map(|x| {
-            x.file_name()
-                .and_then(|x| x.to_str())
-                .and_then(|x| regex.captures(x))
-                .and_then(|x| {
-                    let date: NaiveDate = x
-                        .get(0)
-                        .and_then(|x| NaiveDate::parse_from_str(&x.as_str()[..8], "%Y%m%d").ok())?;
-
-                    let last = x.get(1).and_then(|x| x.as_str().parse::<i32>().ok())?;
-                    Some((date, last))
+    /// The table the foreign key references, instead of the one its column
+    /// is named after.
+    #[clap(long)]
+    references: Option<String>,
+
+    /// The column the foreign key references, `id` unless given.
+    #[clap(long, requires = "references")]
+    referenced_column: Option<String>,
+}
+
+impl OperationArgs for ForeignKeyArgs {
+    fn apply(&self, generator: Generator, current_dir: &Path) -> Generator {
+        let generator = self.constraint.apply(generator, current_dir);
+        match &self.references {
+            Some(references) => generator.references(references, self.referenced_column.clone()),
+            None => generator,
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct RenameTableArgs {
+    /// The new name of the table, `new_<table>` unless given.
+    #[clap(long)]
+    to: Option<String>,
+}
+
+impl OperationArgs for RenameTableArgs {
+    fn apply(&self, generator: Generator, _: &Path) -> Generator {
+        match &self.to {
+            Some(to) => generator.rename_to(to),
+            None => generator,
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct RenameColumnArgs {
+    /// The column.
+    #[clap(short, long)]
+    column: String,
+
+    /// The new name of the column, `new_<column>` unless given.
+    #[clap(long)]
+    to: Option<String>,
+}
+
+impl OperationArgs for RenameColumnArgs {
+    fn apply(&self, generator: Generator, _: &Path) -> Generator {
+        let generator = generator.column(&self.column);
+        match &self.to {
+            Some(to) => generator.rename_to(to),
+            None => generator,
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct RoutineArgs {
+    /// The language of the function or procedure on PostgreSQL, `plpgsql`
+    /// unless given. Its `--language` is the language of the migration.
+    #[clap(long)]
+    routine_language: Option<String>,
+}
+
+impl OperationArgs for RoutineArgs {
+    fn apply(&self, generator: Generator, _: &Path) -> Generator {
+        match &self.routine_language {
+            Some(language) => generator.var("routine_language", language),
+            None => generator,
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct TriggerArgs {
+    /// Name the trigger, and on PostgreSQL its function, instead of
+    /// `trg_<table>`.
+    #[clap(long)]
+    trigger_name: Option<String>,
+}
+
+impl OperationArgs for TriggerArgs {
+    fn apply(&self, generator: Generator, _: &Path) -> Generator {
+        match &self.trigger_name {
+            Some(trigger_name) => generator.trigger_name(trigger_name),
+            None => generator,
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct CreateTriggerArgs {
+    #[command(flatten)]
+    trigger: TriggerArgs,
+
+    /// When the trigger fires, after the statement unless given.
+    #[clap(long, value_enum)]
+    timing: Option<Timing>,
+
+    /// An event the trigger fires on. Can be given several times; without
+    /// any it fires on every event.
+    #[clap(long = "event", value_enum)]
+    events: Vec<Event>,
+}
+
+impl OperationArgs for CreateTriggerArgs {
+    fn apply(&self, generator: Generator, current_dir: &Path) -> Generator {
+        let mut generator = self.trigger.apply(generator, current_dir);
+        if let Some(timing) = self.timing {
+            generator = generator.timing(timing);
+        }
+        for event in &self.events {
+            generator = generator.event(*event);
+        }
+        generator
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct EnumArgs {
+    /// A label of the enum. Can be given several times.
+    #[clap(long = "value")]
+    values: Vec<String>,
+}
+
+impl OperationArgs for EnumArgs {
+    fn apply(&self, generator: Generator, _: &Path) -> Generator {
+        self.values
+            .iter()
+            .fold(generator, |generator, value| generator.value(value))
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct AlterEnumArgs {
+    /// A label to add to the enum. Can be given several times.
+    #[clap(long = "value", required = true)]
+    values: Vec<String>,
+}
+
+impl OperationArgs for AlterEnumArgs {
+    fn apply(&self, generator: Generator, _: &Path) -> Generator {
+        self.values
+            .iter()
+            .fold(generator, |generator, value| generator.value(value))
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct SeedArgs {
+    /// The CSV file to insert the rows of, its header naming the columns.
+    #[clap(long)]
+    from: PathBuf,
+
+    /// Insert at most this many rows per statement.
+    #[clap(long)]
+    batch_size: Option<usize>,
+}
+
+impl OperationArgs for SeedArgs {
+    fn apply(&self, generator: Generator, current_dir: &Path) -> Generator {
+        let generator = generator.seed_from(current_dir.join(&self.from));
+        match self.batch_size {
+            Some(batch_size) => generator.batch_size(batch_size),
+            None => generator,
+        }
+    }
+}
+
+#[derive(clap::Args, Debug)]
+struct DataMigrationArgs {
+    /// Update this many rows at a time in the batched variant, 1000 unless
+    /// given.
+    #[clap(long)]
+    batch_size: Option<usize>,
+}
+
+impl OperationArgs for DataMigrationArgs {
+    fn apply(&self, generator: Generator, _: &Path) -> Generator {
+        match self.batch_size {
+            Some(batch_size) => generator.batch_size(batch_size),
+            None => generator,
+        }
+    }
+}
+
+/// The arguments of `preset`, which generates a composite operation of the
+/// root's configuration.
+#[derive(clap::Args, Debug)]
+struct PresetArgs {
+    /// The preset, as named in the root's configuration.
+    preset: String,
+
+    #[command(flatten)]
+    target: Target,
+
+    /// The column, or comma separated columns, of the preset's steps. Can be
+    /// given several times.
+    #[clap(short, long = "column", visible_alias = "columns")]
+    columns: Vec<String>,
+
+    #[command(flatten)]
+    common: Common,
+}
+
+impl PresetArgs {
+    fn generator(&self, root: &Path, current_dir: &Path) -> Generator {
+        let generator = Generator::new(root)
+            .preset(&self.preset)
+            .name(self.target.name());
+        let generator = columns(generator, &self.columns);
+        self.common.apply(generator, current_dir)
+    }
+}
+
+/// The generation arguments with an operation from a plugin in place of the
+/// built-in ones.
+#[cfg(feature = "plugins")]
+#[derive(clap::Args, Debug)]
+struct PluginArgs {
+    operation: String,
+
+    #[clap(short, long)]
+    name: String,
+
+    #[clap(short, long)]
+    column: Option<String>,
+
+    #[command(flatten)]
+    common: Common,
+}
+
+#[cfg(feature = "plugins")]
+impl PluginArgs {
+    fn generator(&self, root: &Path, current_dir: &Path) -> Generator {
+        let mut generator = Generator::new(root)
+            .plugin_operation(&self.operation)
+            .name(&self.name);
+        if let Some(column) = &self.column {
+            generator = generator.column(column);
+        }
+        self.common.apply(generator, current_dir)
+    }
+}
+
+fn main() -> ExitCode {
+    env_logger::builder().filter_level(LevelFilter::Info).init();
+    let cli = wizard::parse();
+    match run(cli) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("Error: {error:?}");
+            ExitCode::from(exit_code(&error))
+        }
+    }
+}
+
+/// Gives every library error its own exit status so scripts can tell them
+/// apart; anything else exits with 1.
+fn exit_code(error: &anyhow::Error) -> u8 {
+    match error.downcast_ref::<GenError>() {
+        Some(GenError::MissingArgument(_)) => 2,
+        Some(GenError::RootNotFound) => 3,
+        Some(GenError::FutureDatedFile(_)) => 4,
+        Some(GenError::MalformedCounter(_)) => 5,
+        Some(GenError::MalformedConfig(..)) => 11,
+        Some(GenError::MalformedManifest(_)) => 12,
+        Some(GenError::Git(_)) => 13,
+        Some(GenError::UnknownService(_)) => 15,
+        Some(GenError::UnknownPreset(_)) => 19,
+        Some(GenError::MissingVariable(_)) => 20,
+        Some(GenError::Unsupported(..)) => 21,
+        Some(GenError::MalformedCsv(_)) => 22,
+        Some(GenError::IndexOverflow(..)) => 23,
+        Some(GenError::AlreadyExists(_)) => 24,
+        Some(GenError::Locked(_)) => 25,
+        Some(GenError::UnknownPartial(_)) => 26,
+        Some(GenError::Hook(..)) => 27,
+        Some(GenError::InvalidIdentifier(..)) => 28,
+        Some(GenError::ReservedKeyword(..)) => 29,
+        Some(GenError::CollisionDetected(_)) => 30,
+        Some(GenError::Verification(_)) => 16,
+        Some(GenError::MalformedHistory(_)) => 17,
+        Some(GenError::Undo(..)) => 18,
+        Some(GenError::Template(_)) => 6,
+        Some(GenError::Io(_)) => 7,
+        #[cfg(feature = "db")]
+        Some(GenError::Database(_)) => 8,
+        #[cfg(feature = "db")]
+        Some(GenError::Timeout(_)) => 9,
+        #[cfg(feature = "plugins")]
+        Some(GenError::Plugin(_)) => 10,
+        #[cfg(feature = "archive")]
+        Some(GenError::Archive(_)) => 14,
+        None => 1,
+    }
+}
+
+fn run(cli: Cli) -> anyhow::Result<()> {
+    let mut timings = Timings::new(cli.timings);
+
+    if let Some(jobs) = cli.jobs {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(jobs)
+            .build_global()?;
+    }
+
+    let current_dir = env::current_dir()?;
+    info!("current dir: {:?}", current_dir);
+    // The one command that runs before there is a root.
+    if let Command::Init { config, templates } = cli.command {
+        for path in thing::init::init(&current_dir, config, &templates)? {
+            info!("wrote {path:?}");
+        }
+        return Ok(());
+    }
+    let root = timings.time("root discovery", || find_root(&current_dir))?;
+    info!("root path: {:?}", root);
+    // A broken configuration is reported by the commands that need it.
+    let timezone = match cli.utc {
+        true => Timezone::Utc,
+        false => Config::load(&root).map_or_else(|_| Timezone::Local, |x| x.timezone),
+    };
+    let clock = Clock {
+        now: cli.now,
+        timezone,
+    };
+    let cache = !cli.no_cache;
+    let scan = |root: &Path| match cache {
+        true => MigrationIndex::scan_cached(root, root),
+        false => MigrationIndex::scan(root),
+    };
+
+    match cli.command {
+        Command::Watch {
+            socket,
+            schema,
+            service,
+            out,
+        } => {
+            let scope = watch::Scope {
+                schema,
+                service,
+                output_dir: out.map(|x| current_dir.join(x)),
+            };
+            watch::watch(&root, socket.as_deref(), clock, scope)
+        }
+        #[cfg(unix)]
+        Command::Serve { socket } => serve::serve(&root, &root.join(socket), clock),
+        Command::Status {
+            #[cfg(feature = "db")]
+            database_url,
+            json,
+        } => {
+            let index = timings.time("scan", || scan(&root));
+            let states = timings.time("git", || thing::git::file_states(&root))?;
+            let migrations = index
+                .files()
+                .filter(|x| {
+                    x.file_name()
+                        .and_then(|x| x.to_str())
+                        .and_then(parse_file_name)
+                        .is_some()
                 })
-        })
-        .max_by(|a, b| a.0.cmp(&b.0));
+                .collect::<Vec<_>>();
+            #[cfg(feature = "db")]
+            let applied = match &database_url {
+                Some(url) => {
+                    Some(timings.time("database", || database_states(&root, &index, url))?)
+                }
+                None => None,
+            };
+            #[cfg(not(feature = "db"))]
+            let applied: Option<Vec<(String, String)>> = None;
+            if json {
+                let mut entries = migrations
+                    .iter()
+                    .map(|path| {
+                        let path = relative_path(&root, path);
+                        (path, serde_json::Map::new())
+                    })
+                    .collect::<BTreeMap<_, _>>();
+                for (path, state) in &states {
+                    if let Some(entry) = entries.get_mut(&relative_path(&root, path)) {
+                        entry.insert("git".to_owned(), state.to_string().into());
+                    }
+                }
+                for (path, state) in applied.iter().flatten() {
+                    let entry = entries.entry(path.clone()).or_default();
+                    entry.insert("database".to_owned(), state.to_string().into());
+                }
+                let entries = entries
+                    .into_iter()
+                    .map(|(path, mut entry)| {
+                        entry.insert("path".to_owned(), path.into());
+                        entry
+                    })
+                    .collect::<Vec<_>>();
+                println!("{}", serde_json::to_string_pretty(&entries)?);
+            } else {
+                println!("{} migrations", migrations.len());
+                for path in migrations {
+                    if let Some(state) = states.get(path) {
+                        println!("{state:>11} {}", relative_path(&root, path));
+                    }
+                }
+                if let Some(applied) = &applied {
+                    println!();
+                    for (path, state) in applied {
+                        println!("{state:>11} {path}");
+                    }
+                }
+            }
+            timings.report();
+            Ok(())
+        }
+        Command::List {
+            today,
+            since,
+            table,
+            json,
+        } => {
+            let config = Config::load(&root)?;
+            let index = timings.time("scan", || scan(&root));
+            let today = today.then(|| watch::today(clock));
+            let filter = thing::list::Filter {
+                since: since.or(today),
+                until: today,
+                table,
+            };
+            let migrations = thing::list::list(&root, &config, &index, &filter);
+            if json {
+                println!("{}", serde_json::to_string_pretty(&migrations)?);
+            } else {
+                for migration in &migrations {
+                    println!("{}", migration.path);
+                }
+            }
+            timings.report();
+            Ok(())
+        }
+        Command::Grep {
+            pattern,
+            regex,
+            ignore_case,
+            json,
+        } => {
+            let query = thing::search::Query::new(&pattern, regex, ignore_case)?;
+            let index = timings.time("scan", || scan(&root));
+            let matches = timings.time("search", || thing::search::search(&root, &index, &query));
+            if json {
+                println!("{}", serde_json::to_string_pretty(&matches)?);
+            } else {
+                for found in &matches {
+                    let line = found.line.map(|x| format!(":{x}")).unwrap_or_default();
+                    println!(
+                        "{} {:02} {}{line}: {}",
+                        found.date, found.index, found.path, found.text
+                    );
+                }
+            }
+            timings.report();
+            if matches.is_empty() {
+                anyhow::bail!("no migration matches {pattern:?}");
+            }
+            Ok(())
+        }
+        Command::Schema => {
+            let config = Config::load(&root)?;
+            let index = timings.time("scan", || scan(&root));
+            let model = timings.time("parse", || SchemaModel::load(&root, &config, &index))?;
+            for (name, table) in &model.tables {
+                println!("{name}");
+                for column in &table.columns {
+                    let references = column.references.as_ref().map(|x| match &x.column {
+                        Some(column) => format!(" -> {}({column})", x.table),
+                        None => format!(" -> {}", x.table),
+                    });
+                    let line = format!("{} {}", column.name, column.data_type);
+                    println!("    {}{}", line.trim_end(), references.unwrap_or_default());
+                }
+            }
+            timings.report();
+            Ok(())
+        }
+        Command::Erd { format, output } => {
+            let config = Config::load(&root)?;
+            let index = timings.time("scan", || scan(&root));
+            let model = timings.time("parse", || SchemaModel::load(&root, &config, &index))?;
+            let diagram = thing::erd::render(&model, format);
+            match output {
+                Some(path) => {
+                    std::fs::write(&path, diagram)?;
+                    info!("wrote {path:?}");
+                }
+                None => print!("{diagram}"),
+            }
+            timings.report();
+            Ok(())
+        }
+        Command::History => {
+            let history = thing::history::load(&root)?;
+            for (number, step) in history.iter().rev().enumerate() {
+                let files = step.files.iter().map(|x| x.path.as_str());
+                println!(
+                    "{:>3} {} {}",
+                    number + 1,
+                    step.when,
+                    files.collect::<Vec<_>>().join(", ")
+                );
+            }
+            Ok(())
+        }
+        Command::Undo { steps } => {
+            let config = Config::load(&root)?;
+            let removed = thing::history::undo(&root, &config, steps)?;
+            for path in &removed {
+                info!("removed {path:?}");
+            }
+            if config.audit && !removed.is_empty() {
+                audit(&root, "undo", &removed, &[])?;
+            }
+            Ok(())
+        }
+        Command::Duplicate { path, table, from } => {
+            let path = current_dir.join(path);
+            let Some(name) = path
+                .file_name()
+                .and_then(|x| x.to_str())
+                .and_then(parse_file_name)
+            else {
+                anyhow::bail!("{path:?} is not a migration");
+            };
+            let (operation, named_table, column) = Operation::from_file_name(name.description);
+            let source_table = from.or(named_table);
+            let name_of_copy = match (table, &source_table) {
+                (Some(_), None) => return Err(GenError::MissingArgument("from").into()),
+                (Some(table), Some(_)) => table,
+                (None, Some(source_table)) => source_table.clone(),
+                (None, None) => name.description.to_owned(),
+            };
+            let mut generator = Generator::new(&root)
+                .operation(operation)
+                .name(name_of_copy)
+                .language(name.language)
+                .duplicate(&path, source_table)
+                .today(watch::today(clock))
+                .timezone(clock.timezone)
+                .cache(cache);
+            if let Some(column) = column {
+                generator = generator.column(column);
+            }
+            if let Some(service) = name.service {
+                generator = generator.service(service);
+            }
+            let copy = generator.generate_timed(&mut timings)?;
+            info!("copied {path:?} to {copy:?}");
+            timings.report();
+            Ok(())
+        }
+        Command::Index => {
+            let index = timings.time("scan", || scan(&root));
+            let path = timings.time("write", || thing::document::write(&root, &index))?;
+            info!("wrote {path:?}");
+            timings.report();
+            Ok(())
+        }
+        Command::Verify { format, checksums } => {
+            let config = Config::load(&root)?;
+            let index = timings.time("scan", || scan(&root));
+            let today = watch::today(clock);
+            let mut problems = timings.time("verify", || verify(&root, &config, &index, today));
+            let mut rules = Rule::VERIFY.to_vec();
+            if checksums {
+                rules.push(Rule::Checksums);
+                problems.extend(
+                    timings.time("checksums", || verify_checksums(&root, &config, &index))?,
+                );
+            }
+            timings.report();
+            report_checks(format, "verify", &rules, &problems)
+        }
+        Command::Lock => {
+            let config = Config::load(&root)?;
+            let index = timings.time("scan", || scan(&root));
+            let mut manifest = Manifest::load(&root)?;
+            let mut changed = false;
+            for path in index.files() {
+                if config.is_rollback(&root, path) {
+                    continue;
+                }
+                if manifest.record(&root, path, &std::fs::read(path)?) {
+                    println!("{}", relative_path(&root, path));
+                    changed = true;
+                }
+            }
+            let length = manifest.migrations.len();
+            manifest.migrations.retain(|x| root.join(&x.path).exists());
+            if changed || manifest.migrations.len() != length {
+                manifest.store(&root)?;
+            }
+            timings.report();
+            Ok(())
+        }
+        Command::CiCheck { base, format } => {
+            let config = Config::load(&root)?;
+            let branch_files = thing::git::branch_files(&root, &base)?;
+            let index = timings.time("scan", || scan(&root));
+            let today = watch::today(clock);
+            let problems = timings.time("verify", || {
+                verify_branch(&root, &config, &index, today, &branch_files)
+            });
+            timings.report();
+            report_checks(format, "ci-check", &Rule::BRANCH, &problems)
+        }
+        Command::Snapshot { check } => {
+            #[cfg(feature = "plugins")]
+            let snapshots = thing::snapshot::render_with(load_plugins(&root)?.as_ref())?;
+            #[cfg(not(feature = "plugins"))]
+            let snapshots = thing::snapshot::render()?;
+            if check {
+                report(&thing::snapshot::check(&root, &snapshots)?)
+            } else {
+                let directory = thing::snapshot::write(&root, &snapshots)?;
+                info!("wrote {directory:?}");
+                Ok(())
+            }
+        }
+        Command::Hooks { command } => match command {
+            HooksCommand::Install { hook, force } => {
+                let name = match hook {
+                    Hook::PreCommit => "pre-commit",
+                    Hook::PrePush => "pre-push",
+                };
+                let exe = env::current_exe()?;
+                let command = format!("\"{}\" verify", exe.display());
+                let path = thing::git::install_hook(&root, name, &command, force)?;
+                info!("installed {path:?}");
+                Ok(())
+            }
+        },
+        Command::Template { command } => match command {
+            TemplateCommand::List(kind) => {
+                let (kind, pack) = kind.kind(&root)?;
+                for operation in Operation::value_variants() {
+                    let source =
+                        match thing::templates::source(&root, pack.as_deref(), kind, operation) {
+                            Some(Source::Root(path)) => relative_path(&root, &path),
+                            Some(Source::Pack(path)) => {
+                                format!("pack {}", relative_path(&root, &path))
+                            }
+                            Some(Source::BuiltIn) => "built-in".to_owned(),
+                            None => continue,
+                        };
+                    println!("{:<28} {source}", operation.name());
+                }
+                Ok(())
+            }
+            TemplateCommand::Show { operation, kind } => {
+                let (kind, pack) = kind.kind(&root)?;
+                match thing::templates::template(&root, pack.as_deref(), kind, &operation)? {
+                    Some((_, template)) => print!("{template}"),
+                    None => info!("{} has no template", operation.name()),
+                }
+                Ok(())
+            }
+            TemplateCommand::Eject {
+                operation,
+                kind,
+                force,
+            } => {
+                let (kind, pack) = kind.kind(&root)?;
+                let path =
+                    thing::templates::eject(&root, pack.as_deref(), kind, &operation, force)?;
+                println!("{}", relative_path(&root, &path));
+                Ok(())
+            }
+            TemplateCommand::Update => {
+                match thing::templates::update_pack(&root, &Config::load(&root)?)? {
+                    Some(pack) => println!("{}", relative_path(&root, &pack)),
+                    None => info!("the root has no template_pack"),
+                }
+                Ok(())
+            }
+        },
+        Command::Changelog { from, to } => {
+            let files = thing::git::added_files(&root, &from, &to)?;
+            print!("{}", thing::document::render_changelog(&from, &to, &files));
+            Ok(())
+        }
+        Command::Rename {
+            path,
+            name,
+            dry_run,
+        } => {
+            let path = current_dir.join(path);
+            let name = name.trim();
+            if name.is_empty() || name.contains(['/', '\\']) {
+                anyhow::bail!("{name:?} cannot be a description");
+            }
+            let config = Config::load(&root)?;
+            if config.is_rollback(&root, &path) {
+                anyhow::bail!("{path:?} is a rollback, rename its migration instead");
+            }
+            let Some(rename) = thing::renumber::describe(&path, name) else {
+                anyhow::bail!("{path:?} is not a migration");
+            };
+            if rename.to != rename.from && rename.to.exists() {
+                anyhow::bail!("{:?} already exists", rename.to);
+            }
+            println!(
+                "{} -> {}",
+                relative_path(&root, &rename.from),
+                relative_path(&root, &rename.to)
+            );
+            if !dry_run && rename.to != rename.from {
+                thing::renumber::apply(&root, &config, std::slice::from_ref(&rename))?;
+                if config.audit {
+                    audit(&root, "rename", &[rename.to], &[rename.from])?;
+                }
+            }
+            Ok(())
+        }
+        Command::Renumber {
+            base,
+            collisions,
+            dry_run,
+        } => {
+            let config = Config::load(&root)?;
+            let index = timings.time("scan", || scan(&root));
+            let renames = if collisions {
+                // Outside a repository nothing is committed, so of the
+                // migrations sharing an index the first by name keeps it.
+                let order = thing::git::commit_order(&root).unwrap_or_default();
+                thing::renumber::plan_collisions(&root, &config, &index, &order)
+            } else {
+                let branch_files = thing::git::branch_files(&root, &base)?;
+                thing::renumber::plan(&root, &config, &index, &branch_files)
+            };
+            for rename in &renames {
+                println!(
+                    "{} -> {}",
+                    relative_path(&root, &rename.from),
+                    relative_path(&root, &rename.to)
+                );
+            }
+            if !dry_run {
+                timings.time("rename", || {
+                    thing::renumber::apply(&root, &config, &renames)
+                })?;
+                if config.audit && !renames.is_empty() {
+                    let files = renames.iter().map(|x| x.to.clone()).collect::<Vec<_>>();
+                    let previous = renames.iter().map(|x| x.from.clone()).collect::<Vec<_>>();
+                    audit(&root, "renumber", &files, &previous)?;
+                }
+            }
+            timings.report();
+            Ok(())
+        }
+        #[cfg(feature = "db")]
+        Command::Apply {
+            database_url,
+            dry_run,
+        } => {
+            use thing::db::{block_on, Database, DatabaseOptions};
+            let config = Config::load(&root)?;
+            let index = timings.time("scan", || scan(&root));
+            let dialect = thing::apply::dialect(&database_url);
+            block_on(async {
+                let database =
+                    Database::connect(&database_url, &DatabaseOptions::default()).await?;
+                let applied = thing::apply::applied(&database).await?;
+                for path in thing::apply::pending(&root, &config, &index, &applied) {
+                    if dry_run {
+                        println!("{}", relative_path(&root, &path));
+                        continue;
+                    }
+                    thing::apply::apply(&database, dialect, &root, &path).await?;
+                    info!("applied {path:?}");
+                }
+                database.close().await;
+                Ok::<_, GenError>(())
+            })??;
+            timings.report();
+            Ok(())
+        }
+        #[cfg(feature = "archive")]
+        Command::Archive { year, keep } => {
+            let index = timings.time("scan", || scan(&root));
+            match thing::archive::archive(&root, &index, year, keep)? {
+                Some(bundle) => {
+                    info!("wrote {bundle:?}");
+                    if Config::load(&root)?.audit {
+                        audit(&root, "archive", &[bundle], &[])?;
+                    }
+                }
+                None => info!("no migrations from {year}"),
+            }
+            Ok(())
+        }
+        #[cfg(feature = "archive")]
+        Command::Unarchive { bundle } => {
+            let restored = thing::archive::extract(&root, &current_dir.join(bundle))?;
+            for path in &restored {
+                info!("restored {path:?}");
+            }
+            if Config::load(&root)?.audit {
+                audit(&root, "unarchive", &restored, &[])?;
+            }
+            Ok(())
+        }
+        #[cfg(feature = "plugins")]
+        Command::Plugin(args) => {
+            let generator = args.generator(&root, &current_dir);
+            generate_migration(
+                &root,
+                generator
+                    .today(watch::today(clock))
+                    .timezone(clock.timezone)
+                    .cache(cache),
+                args.common.print(),
+                args.common.edit(),
+                &mut timings,
+            )
+        }
+        #[cfg(feature = "plugins")]
+        Command::Plugins => {
+            for plugin in load_plugins(&root)?.iter() {
+                println!("{}", plugin.name());
+                for operation in plugin.operations() {
+                    println!("  {}", operation.name);
+                }
+            }
+            Ok(())
+        }
+        Command::Generate(generate) => {
+            let generator = generate.generator(&root, &current_dir);
+            generate_migration(
+                &root,
+                generator
+                    .today(watch::today(clock))
+                    .timezone(clock.timezone)
+                    .cache(cache),
+                generate.common().print(),
+                generate.common().edit(),
+                &mut timings,
+            )
+        }
+        Command::Preset(args) => {
+            let generator = args.generator(&root, &current_dir);
+            generate_migration(
+                &root,
+                generator
+                    .today(watch::today(clock))
+                    .timezone(clock.timezone)
+                    .cache(cache),
+                args.common.print(),
+                args.common.edit(),
+                &mut timings,
+            )
+        }
+        Command::Init { .. } => unreachable!("init runs before root discovery"),
+    }
+}
 
-    if let Some((date, last)) = last {
-        let current_date = Local::now().date_naive();
-        if date.cmp(&current_date).is_gt() {
-            return Err(anyhow::anyhow!("found date {:?} in future", date));
+/// Generates with `generator`, asking for the variables its templates
+/// declare that were not given when run in a terminal, printing what
+/// `print` says of what it generates and opening the migrations in the
+/// editor if `edit` says to, or the root does when it says nothing and this
+/// runs in a terminal.
+fn generate_migration(
+    root: &Path,
+    mut generator: Generator,
+    print: Print,
+    edit: Option<bool>,
+    timings: &mut Timings,
+) -> anyhow::Result<()> {
+    if std::io::stdin().is_terminal() {
+        for variable in generator.missing_variables()? {
+            let value = prompt(&variable)?;
+            generator = generator.var(variable.name, value);
+        }
+    }
+    #[cfg(feature = "plugins")]
+    let generator = generator.plugins(timings.time("plugin loading", || load_plugins(root))?);
+    let files = generator.generate_files_timed(timings)?;
+    timings.report();
+    let config = Config::load(root)?;
+    let mut stdout = std::io::stdout().lock();
+    match print {
+        Print::Nothing => {}
+        Print::Files => {
+            for (path, content) in &files {
+                writeln!(stdout, "==> {} <==", relative_path(root, path))?;
+                writeln!(stdout, "{}", content.trim_end())?;
+            }
+        }
+        Print::Migrations => {
+            for (_, content) in migrations(root, &config, &files) {
+                stdout.write_all(content.as_bytes())?;
+            }
         }
+    }
+    let interactive = std::io::stdin().is_terminal() && std::io::stdout().is_terminal();
+    if edit.unwrap_or(config.open_editor && interactive) {
+        let paths = migrations(root, &config, &files).map(|(path, _)| path);
+        open_editor(&config, paths)?;
+    }
+    Ok(())
+}
 
-        if (date.cmp(&current_date)).is_eq() {
-            return Ok(Some(last));
+/// The forward migrations among generated `files`. Rollbacks are left out,
+/// and so are tests, which are in no migration language.
+fn migrations<'a>(
+    root: &'a Path,
+    config: &'a Config,
+    files: &'a [(PathBuf, String)],
+) -> impl Iterator<Item = &'a (PathBuf, String)> {
+    files.iter().filter(|(path, _)| {
+        let language = path
+            .extension()
+            .and_then(|x| x.to_str())
+            .and_then(Language::from_extension);
+        language.is_some() && !config.is_rollback(root, path)
+    })
+}
+
+/// Opens `paths` with the root's `editor`, `$VISUAL` or `$EDITOR` and waits
+/// for it to exit.
+fn open_editor<'a>(
+    config: &Config,
+    paths: impl Iterator<Item = &'a PathBuf>,
+) -> anyhow::Result<()> {
+    let set = |x: &String| !x.trim().is_empty();
+    let editor = config
+        .editor
+        .clone()
+        .filter(set)
+        .or_else(|| env::var("VISUAL").ok().filter(set))
+        .or_else(|| env::var("EDITOR").ok().filter(set));
+    let Some(editor) = editor else {
+        anyhow::bail!("no editor to open the migration with, set $EDITOR or the root's editor");
+    };
+    let mut words = editor.split_whitespace();
+    let program = words.next().unwrap();
+    let status = std::process::Command::new(program)
+        .args(words)
+        .args(paths)
+        .status()
+        .map_err(|x| anyhow::anyhow!("cannot run the editor {program:?}: {x}"))?;
+    if !status.success() {
+        anyhow::bail!("the editor {program:?} failed with {status}");
+    }
+    Ok(())
+}
+
+/// Where each SQL migration stands with the database at `url`, by path.
+#[cfg(feature = "db")]
+fn database_states(
+    root: &Path,
+    index: &MigrationIndex,
+    url: &str,
+) -> anyhow::Result<Vec<(String, String)>> {
+    use thing::db::{block_on, Database, DatabaseOptions};
+    let config = Config::load(root)?;
+    let applied = block_on(async {
+        let database = Database::connect(url, &DatabaseOptions::default()).await?;
+        let applied = thing::apply::applied(&database).await;
+        database.close().await;
+        applied
+    })??;
+    let states = thing::apply::states(root, &config, index, &applied)?;
+    Ok(states
+        .into_iter()
+        .map(|(path, state)| (path, state.to_string()))
+        .collect())
+}
+
+/// Asks for the value of `variable` on the terminal.
+fn prompt(variable: &Variable) -> anyhow::Result<String> {
+    match variable.description.as_str() {
+        "" => eprint!("{}: ", variable.name),
+        description => eprint!("{} ({description}): ", variable.name),
+    }
+    std::io::stderr().flush()?;
+    let mut value = String::new();
+    std::io::stdin().read_line(&mut value)?;
+    Ok(value.trim_end_matches(['\r', '\n']).to_owned())
+}
+
+/// Records `action` in the audit log with this run's command line.
+fn audit(root: &Path, action: &str, files: &[PathBuf], previous: &[PathBuf]) -> anyhow::Result<()> {
+    let flags = env::args().skip(1).collect::<Vec<_>>();
+    Ok(thing::audit::record(root, action, files, previous, &flags)?)
+}
+
+/// Prints `problems`, failing when there are any.
+fn report(problems: &[impl std::fmt::Display]) -> anyhow::Result<()> {
+    for problem in problems {
+        println!("{problem}");
+    }
+    match problems.len() {
+        0 => Ok(()),
+        count => Err(GenError::Verification(count).into()),
+    }
+}
+
+/// Prints the problems breaking `rules` in `format`, failing if there are
+/// any, with `CollisionDetected` if migrations collide.
+fn report_checks(
+    format: ReportFormat,
+    suite: &str,
+    rules: &[Rule],
+    problems: &[Problem],
+) -> anyhow::Result<()> {
+    match format {
+        ReportFormat::Text => {
+            for problem in problems {
+                println!("{problem}");
+            }
         }
+        ReportFormat::Junit => print!("{}", thing::verify::junit(suite, rules, problems)),
     }
+    let collision = problems.iter().find_map(|x| match x {
+        Problem::Collision(paths) => Some(paths.clone()),
+        _ => None,
+    });
+    match (collision, problems.len()) {
+        (Some(paths), _) => Err(GenError::CollisionDetected(paths).into()),
+        (None, 0) => Ok(()),
+        (None, count) => Err(GenError::Verification(count).into()),
+    }
+}
 
-    Ok(None)
+#[cfg(feature = "plugins")]
+fn load_plugins(root: &Path) -> Result<std::sync::Arc<thing::plugin::Plugins>, GenError> {
+    // Plugins are code the root's owner chose to install, like a build script.
+    unsafe { thing::plugin::Plugins::load(root) }.map(std::sync::Arc::new)
 }
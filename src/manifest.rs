@@ -0,0 +1,168 @@
+use crate::error::GenError;
+use crate::naming::parse_file_name;
+use crate::operation::Operation;
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// File at the root listing every generated migration for deploy tooling.
+pub const MANIFEST_FILE: &str = "migrations.json";
+
+/// One generated migration.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Entry {
+    /// Relative to the root, with `/` separators.
+    pub path: String,
+    pub date: NaiveDate,
+    pub index: i32,
+    pub operation: String,
+    pub table: String,
+    pub column: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub service: Option<String>,
+    /// SHA-256 of the file as generated, in lowercase hex.
+    pub checksum: String,
+}
+
+/// The parsed `migrations.json`, ordered by date and index.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Manifest {
+    pub migrations: Vec<Entry>,
+}
+
+impl Manifest {
+    /// Reads the root's manifest, or an empty one if it has none yet.
+    pub fn load(root: &Path) -> Result<Self, GenError> {
+        let path = root.join(MANIFEST_FILE);
+        if !path.exists() {
+            return Ok(Manifest::default());
+        }
+        let content = fs::read_to_string(&path)?;
+        serde_json::from_str(&content).map_err(|_| GenError::MalformedManifest(path))
+    }
+
+    /// Adds `entry`, replacing any previous entry for the same path.
+    pub fn insert(&mut self, entry: Entry) {
+        self.migrations.retain(|x| x.path != entry.path);
+        self.migrations.push(entry);
+        self.migrations
+            .sort_by(|a, b| (a.date, a.index, &a.path).cmp(&(b.date, b.index, &b.path)));
+    }
+
+    /// Records `content` as what the migration at `path`, under `root`, holds
+    /// now, adding an entry for it after what its name says when it has
+    /// none. Returns whether anything changed.
+    pub fn record(&mut self, root: &Path, path: &Path, content: &[u8]) -> bool {
+        let relative = relative_path(root, path);
+        let sum = checksum(content);
+        if let Some(entry) = self.migrations.iter_mut().find(|x| x.path == relative) {
+            let changed = entry.checksum != sum;
+            entry.checksum = sum;
+            return changed;
+        }
+        let Some(name) = path
+            .file_name()
+            .and_then(|x| x.to_str())
+            .and_then(parse_file_name)
+        else {
+            return false;
+        };
+        let (operation, table, column) = Operation::from_file_name(name.description);
+        self.insert(Entry {
+            path: relative,
+            date: name.date,
+            index: name.index,
+            operation: operation.name(),
+            table: table.unwrap_or_else(|| name.description.to_owned()),
+            column,
+            service: name.service.map(ToOwned::to_owned),
+            checksum: sum,
+        });
+        true
+    }
+
+    /// Writes the manifest through a temporary file and a rename, like the
+    /// counter file.
+    pub fn store(&self, root: &Path) -> Result<(), GenError> {
+        let path = root.join(MANIFEST_FILE);
+        let temporary = PathBuf::from(format!("{}.tmp", path.display()));
+        let mut content = serde_json::to_string_pretty(self).map_err(std::io::Error::other)?;
+        content.push('\n');
+        fs::write(&temporary, content)?;
+        fs::rename(&temporary, &path)?;
+        Ok(())
+    }
+}
+
+/// The manifest path of `path`, a file under `root`.
+pub fn relative_path(root: &Path, path: &Path) -> String {
+    let path = path.strip_prefix(root).unwrap_or(path);
+    path.components()
+        .map(|x| x.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+pub fn checksum(content: &[u8]) -> String {
+    Sha256::digest(content)
+        .iter()
+        .map(|x| format!("{x:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, index: i32) -> Entry {
+        Entry {
+            path: path.to_owned(),
+            date: NaiveDate::from_ymd_opt(2024, 6, 12).unwrap(),
+            index,
+            operation: "create-table".to_owned(),
+            table: "users".to_owned(),
+            column: None,
+            service: None,
+            checksum: checksum(b""),
+        }
+    }
+
+    #[test]
+    fn test_insert() {
+        let mut manifest = Manifest::default();
+        manifest.insert(entry("b.sql", 2));
+        manifest.insert(entry("a.sql", 1));
+        manifest.insert(entry("b.sql", 3));
+        let paths = manifest.migrations.iter().map(|x| x.path.as_str());
+        assert_eq!(paths.collect::<Vec<_>>(), ["a.sql", "b.sql"]);
+        assert_eq!(manifest.migrations[1].index, 3);
+    }
+
+    #[test]
+    fn test_record() {
+        let root = Path::new("/root");
+        let mut manifest = Manifest::default();
+        manifest.insert(entry("a.sql", 1));
+        assert!(!manifest.record(root, &root.join("a.sql"), b""));
+        assert!(manifest.record(root, &root.join("a.sql"), b"select 1;"));
+        assert_eq!(manifest.migrations[0].checksum, checksum(b"select 1;"));
+        let path = root.join("db/2024061202 - add column email to users.sql");
+        assert!(manifest.record(root, &path, b""));
+        let entry = &manifest.migrations[1];
+        assert_eq!(entry.path, "db/2024061202 - add column email to users.sql");
+        assert_eq!(entry.operation, "add-column");
+        assert_eq!(entry.column.as_deref(), Some("email"));
+        assert!(!manifest.record(root, &root.join("notes.sql"), b""));
+        assert_eq!(manifest.migrations.len(), 2);
+    }
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(
+            checksum(b""),
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
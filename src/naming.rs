@@ -0,0 +1,510 @@
+//! Pure numbering rules: no filesystem and no clock, so every input is explicit.
+
+use crate::error::GenError;
+use crate::language::Language;
+use chrono::{NaiveDate, NaiveDateTime};
+use regex::Regex;
+use std::cmp::Ordering;
+use std::sync::LazyLock;
+
+static FILE_NAME_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new("^\\d{8}(\\d{2,4})(?:\\D.*)?$").unwrap());
+
+static FULL_FILE_NAME_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    let extensions = Language::ALL.map(Language::extension).join("|");
+    Regex::new(&format!(
+        "^(?:(\\w+)-)?(\\d{{8}})(\\d{{2,4}})(?: - |[ _.-])(.*)\\.({extensions})$"
+    ))
+    .unwrap()
+});
+
+static MIGRATE_FILE_NAME_REGEX: LazyLock<Regex> = LazyLock::new(|| {
+    let extensions = Language::ALL.map(Language::extension).join("|");
+    Regex::new(&format!(
+        "^(?:(\\w+)-)?(\\d{{8}})(\\d{{6}})_(.*)\\.up\\.({extensions})$"
+    ))
+    .unwrap()
+});
+
+/// A generated migration file name split into its parts.
+#[derive(Debug, PartialEq, Eq)]
+pub struct FileName<'a> {
+    /// The service prefix of a `service-YYYYMMDDNN - description.sql` name.
+    pub service: Option<&'a str>,
+    pub date: NaiveDate,
+    /// The index within the day, or the `HHMMSS` time of a migrate name.
+    pub index: i32,
+    /// What follows the prefix, e.g. `add column email to users`.
+    pub description: &'a str,
+    pub language: Language,
+}
+
+/// Splits a `[service-]YYYYMMDDNN - description.<extension>` file name, with
+/// the extension of any `Language` and ` `, `_`, `-` or `.` instead of ` - `
+/// for a root's own `file_name_format`, or a migrate name like
+/// `YYYYMMDDHHMMSS_description.up.sql`, or returns `None` for anything else.
+pub fn parse_file_name(name: &str) -> Option<FileName<'_>> {
+    let captures = FULL_FILE_NAME_REGEX
+        .captures(name)
+        .or_else(|| MIGRATE_FILE_NAME_REGEX.captures(name))?;
+    Some(FileName {
+        service: captures.get(1).map(|x| x.as_str()),
+        date: NaiveDate::parse_from_str(&captures[2], "%Y%m%d").ok()?,
+        index: captures[3].parse().ok()?,
+        description: captures.get(4)?.as_str(),
+        language: Language::from_extension(&captures[5])?,
+    })
+}
+
+/// The captures of a generated or migrate file name, and whether it is a
+/// migrate one.
+fn captures(name: &str) -> Option<(regex::Captures<'_>, bool)> {
+    match FULL_FILE_NAME_REGEX.captures(name) {
+        Some(captures) => Some((captures, false)),
+        None => Some((MIGRATE_FILE_NAME_REGEX.captures(name)?, true)),
+    }
+}
+
+/// `file_name`, a generated or migrate name, dated `date` with the index
+/// `index` padded to `width` digits instead and otherwise spelled as it was,
+/// e.g. `20240613000102_x.up.sql` for `20240612083000_x.up.sql`, a migrate
+/// name keeping its six digits.
+#[cfg_attr(not(feature = "fs"), allow(dead_code))]
+pub(crate) fn renumbered(
+    file_name: &str,
+    date: NaiveDate,
+    index: i32,
+    width: usize,
+) -> Option<String> {
+    let (captures, migrate) = captures(file_name)?;
+    let (start, digits) = (captures.get(2)?.start(), captures.get(3)?);
+    let width = if migrate { digits.len() } else { width };
+    Some(format!(
+        "{}{}{index:0width$}{}",
+        &file_name[..start],
+        date.format("%Y%m%d"),
+        &file_name[digits.end()..]
+    ))
+}
+
+/// `file_name`, a generated or migrate name, described `description`
+/// instead and otherwise spelled as it was: the words of a migrate name are
+/// joined by `_`, and so are those of a name whose `file_name_format` joined
+/// them by `_` or `-`, by that.
+#[cfg_attr(not(feature = "fs"), allow(dead_code))]
+pub(crate) fn redescribed(file_name: &str, description: &str) -> Option<String> {
+    let (captures, migrate) = captures(file_name)?;
+    let old = captures.get(4)?;
+    // A single word is joined by whatever comes before it, e.g. `_` after
+    // `{date}{index}_{description | snake}`.
+    let before = file_name[..old.start()].chars().last();
+    let joined = match old.as_str().chars().find(|x| matches!(x, ' ' | '_' | '-')) {
+        _ if migrate => Some('_'),
+        _ if file_name[..old.start()].ends_with(" - ") => None,
+        Some(' ') => None,
+        Some(separator) => Some(separator),
+        None => before.filter(|x| matches!(x, '_' | '-')),
+    };
+    let description = match joined {
+        Some(separator) => join_words(description, separator),
+        None => description.to_owned(),
+    };
+    Some(format!(
+        "{}{description}{}",
+        &file_name[..old.start()],
+        &file_name[old.end()..]
+    ))
+}
+
+/// `file_name`, a generated name with the index `index`, with the index
+/// padded to `width` digits instead, e.g. `202406120003 - x.sql` for 4.
+#[cfg_attr(not(feature = "fs"), allow(dead_code))]
+pub(crate) fn with_index_width(file_name: &str, index: i32, width: usize) -> String {
+    let (date, rest) = file_name.split_at(8);
+    let rest = rest.trim_start_matches(|x: char| x.is_ascii_digit());
+    format!("{date}{index:0width$}{rest}")
+}
+
+/// The `YYYYMMDDHHMMSS_description.up.<extension>` name golang-migrate and
+/// dbmate read, for the `description` of a generated name.
+#[cfg_attr(not(feature = "fs"), allow(dead_code))]
+pub fn migrate_file_name(time: NaiveDateTime, description: &str, language: Language) -> String {
+    format!(
+        "{}_{}.up.{}",
+        time.format("%Y%m%d%H%M%S"),
+        join_words(description, '_'),
+        language.extension()
+    )
+}
+
+/// `text` with every run of anything but ASCII letters and digits as a
+/// single `separator`, e.g. `add columns email, name to users` becomes
+/// `add_columns_email_name_to_users` with `_`.
+pub(crate) fn join_words(text: &str, separator: char) -> String {
+    let mut joined = String::new();
+    for c in text.chars() {
+        match c {
+            'a'..='z' | 'A'..='Z' | '0'..='9' => joined.push(c),
+            _ if !joined.ends_with(separator) && !joined.is_empty() => joined.push(separator),
+            _ => {}
+        }
+    }
+    joined.trim_end_matches(separator).to_owned()
+}
+
+/// Classifies a migration file name against `day` (formatted as `YYYYMMDD`).
+///
+/// Only the `YYYYMMDD` prefix is needed to decide whether a file matters:
+/// earlier days are dropped before any regex or date parsing, files from `day`
+/// yield their index, and future-dated files yield their date.
+pub(crate) fn classify_file_name(name: &str, day: &str) -> Option<Result<i32, NaiveDate>> {
+    let prefix = name.get(..8)?;
+    match prefix.cmp(day) {
+        Ordering::Less => None,
+        Ordering::Equal => FILE_NAME_REGEX
+            .captures(name)
+            .and_then(|x| x.get(1))
+            .and_then(|x| x.as_str().parse::<i32>().ok())
+            .map(Ok),
+        Ordering::Greater => FILE_NAME_REGEX
+            .captures(name)
+            .and_then(|_| NaiveDate::parse_from_str(prefix, "%Y%m%d").ok())
+            .map(Err),
+    }
+}
+
+/// Makes `value` safe to put in a file name: anything but ASCII letters,
+/// digits, `-`, `_` and `.` becomes `-`, e.g. `feature/add users` becomes
+/// `feature-add-users`.
+#[cfg_attr(not(feature = "fs"), allow(dead_code))]
+pub fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|x| match x {
+            'a'..='z' | 'A'..='Z' | '0'..='9' | '-' | '_' | '.' => x,
+            _ => '-',
+        })
+        .collect()
+}
+
+/// Returns the highest index used on `today` among `file_names`, failing if
+/// any of them is dated after it.
+pub fn last_index<'a>(
+    file_names: impl IntoIterator<Item = &'a str>,
+    today: NaiveDate,
+) -> Result<Option<i32>, GenError> {
+    let day = today.format("%Y%m%d").to_string();
+    let mut last = None;
+    for entry in file_names
+        .into_iter()
+        .filter_map(|x| classify_file_name(x, &day))
+    {
+        match entry {
+            Ok(index) => last = last.max(Some(index)),
+            Err(date) => return Err(GenError::FutureDatedFile(date)),
+        }
+    }
+    Ok(last)
+}
+
+/// The index that follows `last`, the highest one already used on a day.
+pub fn index_after(last: Option<i32>) -> i32 {
+    last.map(|index| index + 1).unwrap_or(1)
+}
+
+/// Fails when `index` does not fit in `width` digits, as a day has no more
+/// migrations than the index can number.
+#[cfg_attr(not(feature = "fs"), allow(dead_code))]
+pub(crate) fn check_index_width(index: i32, width: usize) -> Result<(), GenError> {
+    match index < 10i32.pow(width as u32) {
+        true => Ok(()),
+        false => Err(GenError::IndexOverflow(index, width)),
+    }
+}
+
+/// Returns the index the next migration created on `today` gets, given the
+/// names of the existing ones.
+pub fn next_index<'a>(
+    file_names: impl IntoIterator<Item = &'a str>,
+    today: NaiveDate,
+) -> Result<i32, GenError> {
+    last_index(file_names, today).map(index_after)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(day: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(day, "%Y%m%d").unwrap()
+    }
+
+    #[test]
+    fn classify_drops_earlier_days() {
+        assert_eq!(classify_file_name("2024061105 - x.sql", "20240612"), None);
+    }
+
+    #[test]
+    fn classify_reads_index_of_the_same_day() {
+        assert_eq!(
+            classify_file_name("2024061205 - x.sql", "20240612"),
+            Some(Ok(5))
+        );
+    }
+
+    #[test]
+    fn classify_reports_future_dates() {
+        assert_eq!(
+            classify_file_name("2024061301 - x.sql", "20240612"),
+            Some(Err(day("20240613")))
+        );
+    }
+
+    #[test]
+    fn classify_ignores_future_prefixes_that_are_not_dates() {
+        assert_eq!(classify_file_name("2024991301 - x.sql", "20240612"), None);
+    }
+
+    #[test]
+    fn renumbered_keeps_the_spelling() {
+        assert_eq!(
+            renumbered("api-2024061203 - add users.sql", day("20240613"), 4, 2).as_deref(),
+            Some("api-2024061304 - add users.sql")
+        );
+        assert_eq!(
+            renumbered("2024061203 - add users.sql", day("20240612"), 4, 3).as_deref(),
+            Some("20240612004 - add users.sql")
+        );
+        assert_eq!(
+            renumbered("20240612083000_add_users.up.sql", day("20240613"), 102, 2).as_deref(),
+            Some("20240613000102_add_users.up.sql")
+        );
+        assert_eq!(renumbered("notes.sql", day("20240613"), 1, 2), None);
+    }
+
+    #[test]
+    fn redescribed_keeps_the_spelling() {
+        assert_eq!(
+            redescribed("api-2024061203 - add users.sql", "create table orders").as_deref(),
+            Some("api-2024061203 - create table orders.sql")
+        );
+        assert_eq!(
+            redescribed("20240612083000_add_users.up.sql", "create table orders").as_deref(),
+            Some("20240612083000_create_table_orders.up.sql")
+        );
+        assert_eq!(
+            redescribed("2024061203_add_users.sql", "create table orders").as_deref(),
+            Some("2024061203_create_table_orders.sql")
+        );
+        assert_eq!(
+            redescribed("2024061203-users.sql", "create table orders").as_deref(),
+            Some("2024061203-create-table-orders.sql")
+        );
+        assert_eq!(
+            redescribed("2024061203 add users.sql", "create table orders").as_deref(),
+            Some("2024061203 create table orders.sql")
+        );
+    }
+
+    #[test]
+    fn classify_ignores_migrate_names() {
+        assert_eq!(
+            classify_file_name("20240612093000_create_table_users.up.sql", "20240612"),
+            None
+        );
+    }
+
+    #[test]
+    fn classify_ignores_names_without_an_index() {
+        assert_eq!(classify_file_name("20240612 - x.sql", "20240612"), None);
+        assert_eq!(classify_file_name("202406", "20240612"), None);
+        assert_eq!(classify_file_name("notes.sql", "20240612"), None);
+        assert_eq!(classify_file_name("2024061é01.sql", "20240612"), None);
+    }
+
+    #[test]
+    fn last_index_of_an_empty_day_is_none() {
+        assert_eq!(last_index([], day("20240612")).unwrap(), None);
+        assert_eq!(
+            last_index(["2024061107 - x.sql"], day("20240612")).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn last_index_is_the_highest_of_the_day_in_any_order() {
+        let names = [
+            "2024061203 - c.sql",
+            "2024061207 - g.sql",
+            "2024061199 - old.sql",
+            "2024061201 - a.sql",
+        ];
+        assert_eq!(last_index(names, day("20240612")).unwrap(), Some(7));
+    }
+
+    /// A xorshift generator, so the properties below check the same cases on
+    /// every run.
+    struct Random(u64);
+
+    impl Random {
+        fn below(&mut self, n: u64) -> u64 {
+            self.0 ^= self.0 << 13;
+            self.0 ^= self.0 >> 7;
+            self.0 ^= self.0 << 17;
+            self.0 % n
+        }
+    }
+
+    #[test]
+    fn last_index_is_the_highest_of_the_day_for_any_names_and_order() {
+        let today = day("20240612");
+        let mut random = Random(0x2545_f491_4f6c_dd1d);
+        for _ in 0..500 {
+            // Mostly earlier days and today, now and then a later one, with
+            // indexes of either width.
+            let mut names = (0..random.below(12))
+                .map(|_| {
+                    let date = today + chrono::TimeDelta::days(random.below(8) as i64 - 6);
+                    let width = 2 + random.below(2) as usize;
+                    let index = 1 + random.below(10u64.pow(width as u32) - 1) as i32;
+                    let name = format!("{}{index:0width$} - x.sql", date.format("%Y%m%d"));
+                    (date, index, name)
+                })
+                .collect::<Vec<_>>();
+            let future = names
+                .iter()
+                .map(|x| x.0)
+                .filter(|x| *x > today)
+                .collect::<Vec<_>>();
+            let expected = names.iter().filter(|x| x.0 == today).map(|x| x.1).max();
+            for _ in 0..3 {
+                for i in (1..names.len()).rev() {
+                    names.swap(i, random.below(i as u64 + 1) as usize);
+                }
+                match last_index(names.iter().map(|x| x.2.as_str()), today) {
+                    Ok(last) => assert!(future.is_empty() && last == expected, "{names:?}"),
+                    Err(GenError::FutureDatedFile(date)) => assert!(future.contains(&date)),
+                    Err(error) => panic!("{error} for {names:?}"),
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn last_index_fails_on_future_dated_files() {
+        let names = ["2024061201 - a.sql", "2024070101 - b.sql"];
+        assert!(matches!(
+            last_index(names, day("20240612")),
+            Err(GenError::FutureDatedFile(date)) if date == day("20240701")
+        ));
+    }
+
+    #[test]
+    fn parse_file_name_splits_generated_names() {
+        assert_eq!(
+            parse_file_name("2024061203 - add column email to users.sql"),
+            Some(FileName {
+                service: None,
+                date: day("20240612"),
+                index: 3,
+                description: "add column email to users",
+                language: Language::Sql,
+            })
+        );
+        assert_eq!(
+            parse_file_name("2024061203 - x.cypher").map(|x| x.language),
+            Some(Language::Cypher)
+        );
+        assert_eq!(
+            parse_file_name("auth-2024061203 - x.sql").and_then(|x| x.service),
+            Some("auth")
+        );
+        assert_eq!(
+            parse_file_name("2024061203_create_table_users.sql").map(|x| x.description),
+            Some("create_table_users")
+        );
+        assert_eq!(parse_file_name("20240612031.sql"), None);
+        assert_eq!(parse_file_name("2024061203 - x.txt"), None);
+        assert_eq!(parse_file_name("2024991303 - x.sql"), None);
+        assert_eq!(parse_file_name("notes.sql"), None);
+    }
+
+    #[test]
+    fn indexes_can_be_wider() {
+        assert_eq!(
+            with_index_width("2024061203 - x.sql", 3, 4),
+            "202406120003 - x.sql"
+        );
+        assert_eq!(
+            with_index_width("20240612123 - x.sql", 123, 3),
+            "20240612123 - x.sql"
+        );
+        assert_eq!(
+            parse_file_name("202406120123 - x.sql").map(|x| x.index),
+            Some(123)
+        );
+        assert_eq!(
+            last_index(["202406120123 - x.sql"], day("20240612")).unwrap(),
+            Some(123)
+        );
+    }
+
+    #[test]
+    fn migrate_names_are_stamped_to_the_second() {
+        let time = day("20240612").and_hms_opt(9, 30, 5).unwrap();
+        let name = migrate_file_name(time, "add columns email, name to users", Language::Sql);
+        assert_eq!(
+            name,
+            "20240612093005_add_columns_email_name_to_users.up.sql"
+        );
+        assert_eq!(
+            parse_file_name(&name),
+            Some(FileName {
+                service: None,
+                date: day("20240612"),
+                index: 93005,
+                description: "add_columns_email_name_to_users",
+                language: Language::Sql,
+            })
+        );
+        assert_eq!(
+            join_words("create table user_roles", '_'),
+            "create_table_user_roles"
+        );
+        assert_eq!(join_words(" drop view v ", '-'), "drop-view-v");
+        assert_eq!(parse_file_name("20240612093005_x.down.sql"), None);
+    }
+
+    #[test]
+    fn sanitize_replaces_path_and_space_characters() {
+        assert_eq!(sanitize("feature/add users"), "feature-add-users");
+        assert_eq!(sanitize("fix-1.2_x"), "fix-1.2_x");
+        assert_eq!(sanitize("é"), "-");
+    }
+
+    #[test]
+    fn index_after_starts_at_one() {
+        assert_eq!(index_after(None), 1);
+        assert_eq!(index_after(Some(1)), 2);
+        assert_eq!(index_after(Some(41)), 42);
+    }
+
+    #[test]
+    fn index_has_to_fit_the_width() {
+        assert!(check_index_width(99, 2).is_ok());
+        assert!(matches!(
+            check_index_width(100, 2),
+            Err(GenError::IndexOverflow(100, 2))
+        ));
+        assert!(check_index_width(100, 3).is_ok());
+    }
+
+    #[test]
+    fn next_index_follows_the_last_one_of_the_day() {
+        assert_eq!(next_index([], day("20240612")).unwrap(), 1);
+        assert_eq!(
+            next_index(["2024061204 - a.sql"], day("20240612")).unwrap(),
+            5
+        );
+    }
+}
@@ -0,0 +1,1827 @@
+use crate::column::{column_name, split_columns, Column, Key};
+use crate::dialect::Dialect;
+use crate::error::GenError;
+use crate::identifier::Quoting;
+use crate::language::Language;
+use crate::naming::join_words;
+use crate::seed::Seed;
+use crate::trigger::{self, Timing};
+use chrono::NaiveDate;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::{Arc, LazyLock};
+
+/// `{{> name}}`, including the partial `name`.
+static PARTIAL_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{\{>\s*(\w+)\s*\}\}").unwrap());
+
+/// `{name}` in quotes, `"{name}"`, `` `{name}` `` or `[{name}]`, as
+/// templates quote an identifier by hand.
+static QUOTED_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"([`"\[])\{([\w.@]+)\}([`"\]])"#).unwrap());
+
+/// A `{% ... %}` block tag, with what is between the braces.
+static BLOCK_TAG_REGEX: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"\{%\s*([^%{}]*?)\s*%\}").unwrap());
+
+/// What starts a migration that cannot run in a transaction, such as one
+/// adding a label to a PostgreSQL enum.
+#[cfg_attr(not(feature = "fs"), allow(dead_code))]
+pub(crate) const NO_TRANSACTION: &str = "-- no-transaction";
+
+/// How deep partials can include other partials, which stops cycles.
+const PARTIAL_DEPTH: usize = 8;
+
+/// The rows a data migration updates at a time unless `--batch-size` says.
+const DATA_MIGRATION_BATCH: usize = 1000;
+
+#[derive(Serialize)]
+pub(crate) struct TemplateData {
+    table_name: String,
+    column_name: Option<String>,
+    /// The type and constraints of a `name:type:constraints` column.
+    column_type: Option<String>,
+    column_constraints: Option<String>,
+    schema_name: Option<String>,
+    dot: Option<String>,
+    /// The names of the comma separated `column_name` as a list, for the
+    /// index operations, which take several.
+    columns: Vec<String>,
+    /// The same columns with their types and constraints, for `create-table`.
+    definitions: Vec<Column>,
+    /// The labels of an enum, with their quotes doubled.
+    values: Vec<String>,
+    /// The primary, unique and foreign keys of `create-table`.
+    keys: Vec<Key>,
+    /// The rows of `seed` as literals, a statement's worth to a batch.
+    batches: Vec<Vec<Vec<String>>>,
+    /// The name of the dialect rendered for, and whether it is each one,
+    /// for `{{ if is.mysql }}`.
+    dialect: &'static str,
+    is: BTreeMap<&'static str, bool>,
+    template: Cow<'static, str>,
+    #[serde(flatten)]
+    tokens: BTreeMap<String, String>,
+}
+
+impl TemplateData {
+    pub(crate) fn new(
+        name: &str,
+        schema: Option<&str>,
+        column: Option<&str>,
+        template: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        let spec = column.map(Column::parse);
+        let definitions = column
+            .map(split_columns)
+            .unwrap_or_default()
+            .iter()
+            .map(|x| Column::parse(x))
+            .collect::<Vec<_>>();
+        let (column_type, column_constraints) = match &spec {
+            Some(spec) => (spec.data_type.clone(), spec.constraints.clone()),
+            None => (None, None),
+        };
+        TemplateData {
+            table_name: name.to_owned(),
+            column_name: spec.map(|x| x.name),
+            column_type,
+            column_constraints,
+            schema_name: schema.map(ToString::to_string),
+            dot: schema.map(|_| ".".to_string()),
+            columns: definitions.iter().map(|x| x.name.clone()).collect(),
+            definitions,
+            values: Vec::new(),
+            keys: Vec::new(),
+            batches: Vec::new(),
+            dialect: Dialect::default().name(),
+            is: BTreeMap::new(),
+            template: template.into(),
+            tokens: BTreeMap::new(),
+        }
+        .with_dialect(Dialect::default())
+    }
+
+    fn with_dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = dialect.name();
+        self.is = Dialect::ALL.map(|x| (x.name(), x == dialect)).into();
+        self
+    }
+
+    /// Quotes the tables and columns named one of `quoted` the way of
+    /// `dialect`.
+    fn quote(&mut self, dialect: Dialect, quoted: &BTreeSet<String>) {
+        let quote = |x: &mut String| {
+            if quoted.contains(x.as_str()) {
+                *x = dialect.quote(x);
+            }
+        };
+        quote(&mut self.table_name);
+        self.schema_name.iter_mut().for_each(quote);
+        self.column_name.iter_mut().for_each(quote);
+        self.columns.iter_mut().for_each(quote);
+        self.definitions.iter_mut().for_each(|x| quote(&mut x.name));
+        for key in &mut self.keys {
+            key.columns.iter_mut().for_each(quote);
+            key.referenced_table.iter_mut().for_each(quote);
+            key.referenced_column.iter_mut().for_each(quote);
+        }
+        for token in ["referenced_table", "referenced_column", "new_name"] {
+            self.tokens.get_mut(token).into_iter().for_each(quote);
+        }
+    }
+}
+
+/// A formatter templates can apply to a value as `{value | name}`.
+pub(crate) type Helper =
+    Arc<dyn Fn(&serde_json::Value, &mut String) -> tinytemplate::error::Result<()> + Send + Sync>;
+
+/// Values and formatters added on top of the built-in context, by the
+/// generator or by plugins.
+#[derive(Clone, Default)]
+pub(crate) struct Extensions {
+    pub(crate) tokens: BTreeMap<String, String>,
+    pub(crate) helpers: Vec<(String, Helper)>,
+    /// The labels of the enum of `create-enum` and `alter-enum`.
+    pub(crate) values: Vec<String>,
+    /// The keys of the table of `create-table`.
+    pub(crate) keys: Vec<Key>,
+    /// The columns and rows `seed` inserts.
+    pub(crate) seed: Option<Seed>,
+    /// The dialect rendered for, when the template data does not know.
+    pub(crate) dialect: Option<Dialect>,
+    /// Templates others include as `{{> name}}`, by name.
+    pub(crate) partials: BTreeMap<String, String>,
+    /// How `{name | quote}` quotes, the dialect's way unless set. Once set,
+    /// what templates quote by hand is quoted this way instead.
+    pub(crate) quoting: Option<Quoting>,
+    /// Identifiers written quoted in the dialect's way wherever they are a
+    /// name, `{name | quote}` or not, as `--allow-keyword` has the reserved
+    /// keywords `quoting` leaves bare.
+    pub(crate) quoted: BTreeSet<String>,
+}
+
+impl Extensions {
+    /// Adds `other`'s tokens and helpers, which win over existing ones.
+    #[cfg(feature = "plugins")]
+    pub(crate) fn extend(&mut self, other: Extensions) {
+        self.tokens.extend(other.tokens);
+        self.helpers.extend(other.helpers);
+        self.values.extend(other.values);
+        self.keys.extend(other.keys);
+        self.seed = other.seed.or(self.seed.take());
+        self.dialect = other.dialect.or(self.dialect);
+        self.partials.extend(other.partials);
+        self.quoting = other.quoting.or(self.quoting);
+        self.quoted.extend(other.quoted);
+    }
+}
+
+#[derive(Debug, clap::ValueEnum, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Operation {
+    Script,
+    /// A table with the `--column`s and keys, if given.
+    CreateTable,
+    AlterTable,
+    DropTable,
+    /// Adds the `--column`s, a statement each, with their types and
+    /// constraints if given.
+    AddColumn,
+    AlterColumn,
+    DropColumn,
+    /// An index on the comma separated `--column`s.
+    CreateIndex,
+    DropIndex,
+    /// A foreign key from the comma separated `--column`s.
+    AddForeignKey,
+    DropForeignKey,
+    /// A check or unique constraint, whose definition is left to fill in.
+    AddConstraint,
+    DropConstraint,
+    /// Renames the table to `--to`.
+    RenameTable,
+    /// Renames the column to `--to`.
+    RenameColumn,
+    /// A view named `--name`.
+    CreateView,
+    AlterView,
+    DropView,
+    /// A PostgreSQL materialized view named `--name`.
+    CreateMaterializedView,
+    RefreshMaterializedView,
+    /// A function named `--name`, in `--routine-language` for PostgreSQL.
+    CreateFunction,
+    AlterFunction,
+    DropFunction,
+    /// A procedure named `--name`, in `--routine-language` for PostgreSQL.
+    CreateProcedure,
+    AlterProcedure,
+    DropProcedure,
+    /// A trigger on the table, firing `--timing` the `--event`s, with its
+    /// function for PostgreSQL.
+    CreateTrigger,
+    DropTrigger,
+    /// A PostgreSQL sequence named `--name`.
+    CreateSequence,
+    DropSequence,
+    /// A PostgreSQL enum type named `--name` with the `--value`s.
+    CreateEnum,
+    /// Adds the `--value`s to an enum, outside a transaction.
+    AlterEnum,
+    /// Inserts the rows of the CSV file `--from`, whose header names the
+    /// columns, into the table.
+    Seed,
+    /// Updates rows of the table in a transaction that checks how many it
+    /// updated, with a batched variant `--batch-size` rows at a time.
+    DataMigration,
+}
+
+impl Operation {
+    pub fn requires_column(&self) -> bool {
+        matches!(
+            self,
+            Operation::AddColumn
+                | Operation::AlterColumn
+                | Operation::DropColumn
+                | Operation::CreateIndex
+                | Operation::DropIndex
+                | Operation::AddForeignKey
+                | Operation::DropForeignKey
+                | Operation::AddConstraint
+                | Operation::DropConstraint
+                | Operation::RenameColumn
+        )
+    }
+
+    /// The token naming the index, constraint or trigger the operation is
+    /// about, for operations about one.
+    pub(crate) fn object_token(&self) -> Option<&'static str> {
+        match self {
+            Operation::CreateIndex | Operation::DropIndex => Some("index_name"),
+            Operation::CreateTrigger | Operation::DropTrigger => Some("trigger_name"),
+            Operation::AddForeignKey
+            | Operation::DropForeignKey
+            | Operation::AddConstraint
+            | Operation::DropConstraint => Some("constraint_name"),
+            _ => None,
+        }
+    }
+
+    /// The name the index or constraint on `columns` of `table`, or the
+    /// trigger on it, gets unless it is given one, e.g. `idx_users_email`,
+    /// `fk_users_org_id` or `trg_users`.
+    pub(crate) fn object_name(&self, table: &str, columns: Option<&str>) -> Option<String> {
+        if let Operation::CreateTrigger | Operation::DropTrigger = self {
+            return Some(format!("trg_{table}"));
+        }
+        let prefix = match self {
+            Operation::CreateIndex | Operation::DropIndex => "idx",
+            Operation::AddForeignKey | Operation::DropForeignKey => "fk",
+            Operation::AddConstraint | Operation::DropConstraint => "ck",
+            _ => return None,
+        };
+        Some(format!(
+            "{prefix}_{table}_{}",
+            split_columns(columns?).join("_")
+        ))
+    }
+
+    /// The name a rename operation renames to unless it is given one,
+    /// `new_users` for the table `users`.
+    pub(crate) fn new_name(&self, table: &str, column: Option<&str>) -> Option<String> {
+        match self {
+            Operation::RenameTable => Some(format!("new_{table}")),
+            Operation::RenameColumn => Some(format!("new_{}", column_name(column?))),
+            _ => None,
+        }
+    }
+
+    /// Whether the operation is rendered once per column of a comma separated
+    /// `--column`, a statement each.
+    pub(crate) fn per_column(&self) -> bool {
+        matches!(self, Operation::AddColumn | Operation::DropColumn)
+    }
+
+    /// Whether the operation is about a function or procedure.
+    pub(crate) fn is_routine(&self) -> bool {
+        matches!(
+            self,
+            Operation::CreateFunction
+                | Operation::AlterFunction
+                | Operation::DropFunction
+                | Operation::CreateProcedure
+                | Operation::AlterProcedure
+                | Operation::DropProcedure
+        )
+    }
+
+    /// The context of `template` rendered for the operation. The name of
+    /// the index or constraint, the table and column a foreign key
+    /// references, the new name of a rename and the language of a function
+    /// are tokens so that ones given explicitly replace them: a foreign key
+    /// from `org_id` references `org(id)`, and functions are in `plpgsql`,
+    /// unless told otherwise.
+    pub(crate) fn template_data(
+        &self,
+        dialect: Dialect,
+        name: &str,
+        schema: Option<&str>,
+        column: Option<&str>,
+        template: impl Into<Cow<'static, str>>,
+    ) -> TemplateData {
+        let takes_column = self.requires_column() || *self == Operation::CreateTable;
+        let column = column.filter(|_| takes_column);
+        let mut data = TemplateData::new(name, schema, column, template).with_dialect(dialect);
+        if let (Some(token), Some(object_name)) =
+            (self.object_token(), self.object_name(name, column))
+        {
+            data.tokens.insert(token.to_owned(), object_name);
+        }
+        if let Operation::CreateTrigger | Operation::DropTrigger = self {
+            data.tokens.extend([
+                ("timing".to_owned(), Timing::default().keyword().to_owned()),
+                ("events".to_owned(), trigger::events(dialect, &[])),
+            ]);
+        }
+        if let (Operation::AddForeignKey | Operation::DropForeignKey, Some(column)) = (self, column)
+        {
+            let first = split_columns(column).into_iter().next().unwrap_or_default();
+            let table = first.strip_suffix("_id").unwrap_or(&first).to_owned();
+            data.tokens.extend([
+                ("referenced_table".to_owned(), table),
+                ("referenced_column".to_owned(), "id".to_owned()),
+            ]);
+        }
+        if let Some(new_name) = self.new_name(name, column) {
+            data.tokens.insert("new_name".to_owned(), new_name);
+        }
+        if self.is_routine() {
+            data.tokens
+                .insert("routine_language".to_owned(), "plpgsql".to_owned());
+        }
+        if *self == Operation::DataMigration {
+            data.tokens
+                .insert("batch_size".to_owned(), DATA_MIGRATION_BATCH.to_string());
+        }
+        data
+    }
+
+    /// The name the operation is given on the command line, e.g. `add-column`.
+    pub fn name(&self) -> String {
+        use clap::ValueEnum;
+        self.to_possible_value()
+            .expect("no operation is skipped")
+            .get_name()
+            .to_owned()
+    }
+
+    /// The name of the operation's template files, e.g. `add_column.tmpl`.
+    #[cfg_attr(not(feature = "fs"), allow(dead_code))]
+    pub(crate) fn template_file_name(&self) -> String {
+        format!("{}.tmpl", self.name().replace('-', "_"))
+    }
+
+    pub(crate) fn to_file_name(&self, name: &str, column: Option<&str>) -> String {
+        match self {
+            Operation::Script => name.replace(' ', "_").to_string(),
+            Operation::CreateTable => format!("{} {}", "create table", name),
+            Operation::AlterTable => format!("{} {}", "alter table", name),
+            Operation::DropTable => format!("{} {}", "drop table", name),
+            Operation::AddColumn => format!("add {} to {name}", column_names(column.unwrap())),
+            Operation::AlterColumn => {
+                format!("alter column {} in {name}", column_name(column.unwrap()))
+            }
+            Operation::DropColumn => {
+                format!("drop {} from {name}", column_names(column.unwrap()))
+            }
+            Operation::CreateView => format!("create view {name}"),
+            Operation::AlterView => format!("alter view {name}"),
+            Operation::DropView => format!("drop view {name}"),
+            Operation::CreateMaterializedView => format!("create materialized view {name}"),
+            Operation::RefreshMaterializedView => format!("refresh materialized view {name}"),
+            Operation::CreateFunction => format!("create function {name}"),
+            Operation::AlterFunction => format!("alter function {name}"),
+            Operation::DropFunction => format!("drop function {name}"),
+            Operation::CreateProcedure => format!("create procedure {name}"),
+            Operation::AlterProcedure => format!("alter procedure {name}"),
+            Operation::DropProcedure => format!("drop procedure {name}"),
+            Operation::CreateSequence => format!("create sequence {name}"),
+            Operation::DropSequence => format!("drop sequence {name}"),
+            Operation::CreateEnum => format!("create enum {name}"),
+            Operation::AlterEnum => format!("alter enum {name}"),
+            Operation::Seed => format!("seed {name}"),
+            Operation::DataMigration => format!("data migration on {name}"),
+            Operation::RenameTable | Operation::RenameColumn => {
+                let new_name = self.new_name(name, column).unwrap();
+                self.rename_file_name(name, column, &new_name)
+            }
+            _ => {
+                let object_name = self.object_name(name, column).unwrap();
+                self.object_file_name(&object_name, name)
+            }
+        }
+    }
+
+    /// The file name part of a rename to `new_name`, e.g. `rename column
+    /// email to primary_email in users`.
+    pub(crate) fn rename_file_name(
+        &self,
+        name: &str,
+        column: Option<&str>,
+        new_name: &str,
+    ) -> String {
+        match self {
+            Operation::RenameColumn => {
+                let column = column_name(column.unwrap());
+                format!("rename column {column} to {new_name} in {name}")
+            }
+            _ => format!("rename table {name} to {new_name}"),
+        }
+    }
+
+    /// The file name part of an operation on the index, constraint or
+    /// trigger `object_name` of `name`, e.g. `create index idx_users_email on users`.
+    pub(crate) fn object_file_name(&self, object_name: &str, name: &str) -> String {
+        match self {
+            Operation::CreateIndex => format!("create index {object_name} on {name}"),
+            Operation::DropIndex => format!("drop index {object_name} on {name}"),
+            Operation::AddForeignKey => format!("add foreign key {object_name} to {name}"),
+            Operation::DropForeignKey => format!("drop foreign key {object_name} from {name}"),
+            Operation::AddConstraint => format!("add constraint {object_name} to {name}"),
+            Operation::DropConstraint => format!("drop constraint {object_name} from {name}"),
+            Operation::CreateTrigger => format!("create trigger {object_name} on {name}"),
+            Operation::DropTrigger => format!("drop trigger {object_name} on {name}"),
+            _ => unreachable!("{self:?} is not about an index, constraint or trigger"),
+        }
+    }
+
+    /// Reverses `to_file_name`, returning the operation with the table and
+    /// column it names. Index and constraint operations name the index or
+    /// constraint rather than its columns, so they come back without a
+    /// column. Anything unrecognised is a script.
+    ///
+    /// The words may also be joined by `_` or `-`, as a `file_name_format`
+    /// with `{description | snake}` or `{description | kebab}` writes them,
+    /// e.g. `add_column_email_to_users`, and may follow the `--ticket` the
+    /// migration is for, e.g. `DB-2 create table orders`.
+    pub fn from_file_name(file_name: &str) -> (Operation, Option<String>, Option<String>) {
+        let found = Operation::from_words(file_name);
+        if !matches!(found.0, Operation::Script) {
+            return found;
+        }
+        // A ticket is a few words with a digit among them.
+        let mut rest = file_name;
+        for _ in 0..3 {
+            let Some((_, after)) = rest.split_once([' ', '_', '-']) else {
+                break;
+            };
+            rest = after;
+            let ticket = &file_name[..file_name.len() - rest.len()];
+            if ticket.contains(|x: char| x.is_ascii_digit()) {
+                let found = Operation::from_words(rest);
+                if !matches!(found.0, Operation::Script) {
+                    return found;
+                }
+            }
+        }
+        found
+    }
+
+    fn from_words(file_name: &str) -> (Operation, Option<String>, Option<String>) {
+        let separator = file_name
+            .chars()
+            .find(|x| matches!(x, ' ' | '_' | '-'))
+            .unwrap_or(' ');
+        let spelled = |phrase: &str| phrase.replace(' ', &separator.to_string());
+        let table = |operation, prefix: &str| {
+            file_name
+                .strip_prefix(&spelled(prefix))
+                .map(|x| (operation, Some(x.to_owned()), None))
+        };
+        let object = |operation, prefix: &str, separator: &str| {
+            let (_, table) = file_name
+                .strip_prefix(&spelled(prefix))?
+                .split_once(&spelled(separator))?;
+            Some((operation, Some(table.to_owned()), None))
+        };
+        let column = |operation, prefix: &str, separator: &str| {
+            let (column, table) = file_name
+                .strip_prefix(&spelled(prefix))?
+                .split_once(&spelled(separator))?;
+            Some((operation, Some(table.to_owned()), Some(column.to_owned())))
+        };
+        let rename_table = || {
+            let (table, _) = file_name
+                .strip_prefix(&spelled("rename table "))?
+                .split_once(&spelled(" to "))?;
+            Some((Operation::RenameTable, Some(table.to_owned()), None))
+        };
+        let rename_column = || {
+            let rest = file_name.strip_prefix(&spelled("rename column "))?;
+            let (column, rest) = rest.split_once(&spelled(" to "))?;
+            let (_, table) = rest.split_once(&spelled(" in "))?;
+            Some((
+                Operation::RenameColumn,
+                Some(table.to_owned()),
+                Some(column.to_owned()),
+            ))
+        };
+        // Operations named after the object alone, e.g. `drop view sales`.
+        let named = [
+            (Operation::CreateTable, "create table "),
+            (Operation::AlterTable, "alter table "),
+            (Operation::DropTable, "drop table "),
+            (Operation::CreateView, "create view "),
+            (Operation::AlterView, "alter view "),
+            (Operation::DropView, "drop view "),
+            (
+                Operation::CreateMaterializedView,
+                "create materialized view ",
+            ),
+            (
+                Operation::RefreshMaterializedView,
+                "refresh materialized view ",
+            ),
+            (Operation::CreateFunction, "create function "),
+            (Operation::AlterFunction, "alter function "),
+            (Operation::DropFunction, "drop function "),
+            (Operation::CreateProcedure, "create procedure "),
+            (Operation::AlterProcedure, "alter procedure "),
+            (Operation::DropProcedure, "drop procedure "),
+            (Operation::CreateSequence, "create sequence "),
+            (Operation::DropSequence, "drop sequence "),
+            (Operation::CreateEnum, "create enum "),
+            (Operation::AlterEnum, "alter enum "),
+            (Operation::Seed, "seed "),
+            (Operation::DataMigration, "data migration on "),
+        ];
+        named
+            .into_iter()
+            .find_map(|(operation, prefix)| table(operation, prefix))
+            .or_else(|| column(Operation::AddColumn, "add column ", " to "))
+            .or_else(|| column(Operation::AddColumn, "add columns ", " to "))
+            .or_else(|| column(Operation::AlterColumn, "alter column ", " in "))
+            .or_else(|| column(Operation::DropColumn, "drop column ", " from "))
+            .or_else(|| column(Operation::DropColumn, "drop columns ", " from "))
+            .or_else(|| object(Operation::CreateIndex, "create index ", " on "))
+            .or_else(|| object(Operation::DropIndex, "drop index ", " on "))
+            .or_else(|| object(Operation::AddForeignKey, "add foreign key ", " to "))
+            .or_else(|| object(Operation::DropForeignKey, "drop foreign key ", " from "))
+            .or_else(|| object(Operation::AddConstraint, "add constraint ", " to "))
+            .or_else(|| object(Operation::DropConstraint, "drop constraint ", " from "))
+            .or_else(|| object(Operation::CreateTrigger, "create trigger ", " on "))
+            .or_else(|| object(Operation::DropTrigger, "drop trigger ", " on "))
+            .or_else(rename_table)
+            .or_else(rename_column)
+            .unwrap_or((Operation::Script, None, None))
+    }
+
+    pub(crate) fn get_template_data(
+        &self,
+        language: Language,
+        dialect: Dialect,
+        name: &str,
+        schema: Option<&str>,
+        column: Option<&str>,
+    ) -> Option<TemplateData> {
+        let template = language.template(dialect, self)?;
+        Some(self.template_data(dialect, name, schema, column, template))
+    }
+
+    /// The rollback undoing the operation, for operations that have one.
+    #[cfg_attr(not(feature = "fs"), allow(dead_code))]
+    pub(crate) fn get_down_template_data(
+        &self,
+        language: Language,
+        dialect: Dialect,
+        name: &str,
+        schema: Option<&str>,
+        column: Option<&str>,
+    ) -> Option<TemplateData> {
+        let template = language.down_template(dialect, self)?;
+        Some(self.template_data(dialect, name, schema, column, template))
+    }
+
+    /// The pgTAP test asserting that the migration did its job, for
+    /// operations whose effect can be checked without knowing more.
+    #[cfg_attr(not(feature = "fs"), allow(dead_code))]
+    pub(crate) fn get_test_template_data(
+        &self,
+        name: &str,
+        schema: Option<&str>,
+        column: Option<&str>,
+    ) -> Option<TemplateData> {
+        let template = self.test_template()?;
+        Some(self.template_data(Dialect::Postgres, name, schema, column, template))
+    }
+
+    /// The built-in pgTAP template of the operation, if it has one.
+    #[cfg_attr(not(feature = "fs"), allow(dead_code))]
+    pub(crate) fn test_template(&self) -> Option<&'static str> {
+        match self {
+            Operation::CreateTable => Some(include_str!("../templates/pgtap/create_table.tmpl")),
+            Operation::DropTable => Some(include_str!("../templates/pgtap/drop_table.tmpl")),
+            Operation::AddColumn => Some(include_str!("../templates/pgtap/add_column.tmpl")),
+            Operation::DropColumn => Some(include_str!("../templates/pgtap/drop_column.tmpl")),
+            Operation::CreateIndex => Some(include_str!("../templates/pgtap/create_index.tmpl")),
+            Operation::DropIndex => Some(include_str!("../templates/pgtap/drop_index.tmpl")),
+            Operation::AddForeignKey => {
+                Some(include_str!("../templates/pgtap/add_foreign_key.tmpl"))
+            }
+            Operation::RenameTable => Some(include_str!("../templates/pgtap/rename_table.tmpl")),
+            Operation::RenameColumn => Some(include_str!("../templates/pgtap/rename_column.tmpl")),
+            Operation::CreateView => Some(include_str!("../templates/pgtap/create_view.tmpl")),
+            Operation::DropView => Some(include_str!("../templates/pgtap/drop_view.tmpl")),
+            Operation::CreateMaterializedView => Some(include_str!(
+                "../templates/pgtap/create_materialized_view.tmpl"
+            )),
+            Operation::CreateFunction => {
+                Some(include_str!("../templates/pgtap/create_function.tmpl"))
+            }
+            Operation::DropFunction => Some(include_str!("../templates/pgtap/drop_function.tmpl")),
+            Operation::CreateTrigger => {
+                Some(include_str!("../templates/pgtap/create_trigger.tmpl"))
+            }
+            Operation::DropTrigger => Some(include_str!("../templates/pgtap/drop_trigger.tmpl")),
+            Operation::CreateSequence => {
+                Some(include_str!("../templates/pgtap/create_sequence.tmpl"))
+            }
+            Operation::DropSequence => Some(include_str!("../templates/pgtap/drop_sequence.tmpl")),
+            Operation::CreateEnum => Some(include_str!("../templates/pgtap/create_enum.tmpl")),
+            _ => None,
+        }
+    }
+}
+
+/// `column email` or `columns email, name` for the comma separated `column`.
+fn column_names(column: &str) -> String {
+    let names = split_columns(column)
+        .iter()
+        .map(|x| column_name(x).to_owned())
+        .collect::<Vec<_>>();
+    match names.len() {
+        1 => format!("column {}", names[0]),
+        _ => format!("columns {}", names.join(", ")),
+    }
+}
+
+/// Builds the `YYYYMMDDNN - <description>.sql` name of a migration.
+pub fn compute_filename(
+    operation: &Operation,
+    name: &str,
+    column: Option<&str>,
+    day: NaiveDate,
+    index: i32,
+) -> Result<String, GenError> {
+    compute_filename_in(Language::Sql, operation, name, column, day, index)
+}
+
+/// Like `compute_filename`, with the extension of `language`.
+pub(crate) fn compute_filename_in(
+    language: Language,
+    operation: &Operation,
+    name: &str,
+    column: Option<&str>,
+    day: NaiveDate,
+    index: i32,
+) -> Result<String, GenError> {
+    if operation.requires_column() && column.is_none() {
+        return Err(GenError::MissingArgument("column"));
+    }
+    Ok(format_file_name(
+        language,
+        day,
+        index,
+        &operation.to_file_name(name, column),
+    ))
+}
+
+pub(crate) fn format_file_name(
+    language: Language,
+    day: NaiveDate,
+    index: i32,
+    file_name_part: &str,
+) -> String {
+    format!(
+        "{}{index:02} - {file_name_part}.{}",
+        day.format("%Y%m%d"),
+        language.extension()
+    )
+}
+
+/// Renders the body of a migration, or `None` for operations without a
+/// template.
+pub fn render_operation(
+    operation: &Operation,
+    name: &str,
+    schema: Option<&str>,
+    column: Option<&str>,
+) -> Result<Option<String>, GenError> {
+    render_operation_with(
+        Language::Sql,
+        Dialect::Postgres,
+        operation,
+        name,
+        schema,
+        column,
+        &Extensions::default(),
+    )
+}
+
+pub(crate) fn render_operation_with(
+    language: Language,
+    dialect: Dialect,
+    operation: &Operation,
+    name: &str,
+    schema: Option<&str>,
+    column: Option<&str>,
+    extensions: &Extensions,
+) -> Result<Option<String>, GenError> {
+    each_column(operation, column, |column| {
+        operation
+            .get_template_data(language, dialect, name, schema, column)
+            .map(|data| render_template(data, extensions))
+            .transpose()
+    })
+}
+
+/// `render`s `operation` once, or once per column for operations rendered
+/// per column, joining what comes out.
+pub(crate) fn each_column(
+    operation: &Operation,
+    column: Option<&str>,
+    mut render: impl FnMut(Option<&str>) -> Result<Option<String>, GenError>,
+) -> Result<Option<String>, GenError> {
+    let Some(column) = column.filter(|_| operation.per_column()) else {
+        return render(column);
+    };
+    let mut bodies = Vec::new();
+    for column in split_columns(column) {
+        bodies.extend(render(Some(&column))?);
+    }
+    Ok((!bodies.is_empty()).then(|| bodies.join("\n\n")))
+}
+
+/// A custom value a template needs, declared in its frontmatter.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Variable {
+    pub name: String,
+    /// What to ask for, empty when the frontmatter does not say.
+    pub description: String,
+}
+
+/// Splits the `-- no-transaction` line off the top of `body`, if it starts
+/// with one, so that what is put around the rest leaves it first.
+#[cfg_attr(not(feature = "fs"), allow(dead_code))]
+pub(crate) fn split_no_transaction(body: &str) -> (&str, &str) {
+    let Some(rest) = body.strip_prefix(NO_TRANSACTION) else {
+        return ("", body);
+    };
+    match rest.strip_prefix("\r\n").or(rest.strip_prefix('\n')) {
+        Some(after) => body.split_at(body.len() - after.len()),
+        None if rest.is_empty() => (body, ""),
+        None => ("", body),
+    }
+}
+
+/// Splits a template into the variables its frontmatter declares and its
+/// body. The frontmatter is an optional block of `name: description` lines
+/// between two `---` lines at the very start:
+///
+/// ```text
+/// ---
+/// owner: team that owns the table
+/// retention_days
+/// ---
+/// COMMENT ON TABLE {table_name} IS 'owned by {owner}';
+/// ```
+pub fn frontmatter(template: &str) -> (Vec<Variable>, &str) {
+    let Some(rest) = template.strip_prefix("---\n") else {
+        return (Vec::new(), template);
+    };
+    let (head, body) = match rest.split_once("\n---\n") {
+        Some(split) => split,
+        None => match rest.strip_suffix("\n---") {
+            Some(head) => (head, ""),
+            None => return (Vec::new(), template),
+        },
+    };
+    let variables = head
+        .lines()
+        .map(str::trim)
+        .filter(|x| !x.is_empty() && !x.starts_with('#'))
+        .map(|line| {
+            let (name, description) = line.split_once(':').unwrap_or((line, ""));
+            Variable {
+                name: name.trim().to_owned(),
+                description: description.trim().to_owned(),
+            }
+        })
+        .collect();
+    (variables, body)
+}
+
+/// Renders a template with tinytemplate. Its blocks can also be written
+/// `{% ... %}`, e.g. `{% for column in columns %}`, and partials included as
+/// `{{> name}}`.
+pub(crate) fn render_template(
+    mut template_data: TemplateData,
+    extensions: &Extensions,
+) -> Result<String, GenError> {
+    template_data.tokens.extend(extensions.tokens.clone());
+    if let Some(dialect) = extensions.dialect {
+        template_data = template_data.with_dialect(dialect);
+    }
+    if let Some(seed) = &extensions.seed {
+        template_data.columns = seed.columns.clone();
+        template_data.batches = seed.batches.clone();
+    }
+    if !extensions.keys.is_empty() {
+        template_data.keys = extensions.keys.clone();
+    }
+    if !extensions.values.is_empty() {
+        template_data.values = extensions
+            .values
+            .iter()
+            .map(|x| x.replace('\'', "''"))
+            .collect();
+    }
+    if !extensions.quoted.is_empty() {
+        template_data.quote(extensions.dialect.unwrap_or_default(), &extensions.quoted);
+    }
+    let (variables, body) = frontmatter(&template_data.template);
+    if let Some(missing) = variables
+        .iter()
+        .find(|x| !template_data.tokens.contains_key(&x.name))
+    {
+        return Err(GenError::MissingVariable(missing.name.clone()));
+    }
+    if !variables.is_empty() {
+        template_data.template = Cow::Owned(body.to_owned());
+    }
+    let dialect = extensions.dialect.unwrap_or_default();
+    let template = include_partials(&template_data.template, &extensions.partials)?;
+    let template = match extensions.quoting {
+        Some(_) => Cow::Owned(quote_by_policy(&template, dialect)),
+        None => template,
+    };
+    let template = tinytemplate_syntax(&template).into_owned();
+    template_data.template = Cow::Owned(template);
+    let mut engine = tinytemplate::TinyTemplate::new();
+    // Values go into SQL and commit messages, never into HTML.
+    engine.set_default_formatter(&tinytemplate::format_unescaped);
+    // Words of a value joined for file names, e.g. `{description | snake}`.
+    engine.add_formatter("snake", |value, output| join_formatter(value, output, '_'));
+    engine.add_formatter("kebab", |value, output| join_formatter(value, output, '-'));
+    // An identifier quoted as the dialect and `quoting` want it, e.g.
+    // `{table_name | quote}`.
+    let quoting = extensions.quoting.unwrap_or(Quoting::of(dialect));
+    engine.add_formatter("quote", move |value, output| {
+        match value {
+            serde_json::Value::Null => {}
+            serde_json::Value::String(name) if quoting.quotes(dialect, name) => {
+                output.push_str(&dialect.quote(name))
+            }
+            value => tinytemplate::format_unescaped(value, output)?,
+        }
+        Ok(())
+    });
+    for (name, helper) in &extensions.helpers {
+        let helper = Arc::clone(helper);
+        engine.add_formatter(name, move |value, output| helper(value, output));
+    }
+    engine.add_template("template", &template_data.template)?;
+    Ok(engine.render("template", &template_data)?)
+}
+
+/// `template` with the identifiers it quotes by hand in `dialect`'s way
+/// written `{name | quote}`, so that they are quoted as configured instead.
+fn quote_by_policy(template: &str, dialect: Dialect) -> String {
+    let quotes = dialect.quote("");
+    let replaced = QUOTED_REGEX.replace_all(template, |captures: &regex::Captures| {
+        match format!("{}{}", &captures[1], &captures[3]) == quotes {
+            true => format!("{{{} | quote}}", &captures[2]),
+            false => captures[0].to_owned(),
+        }
+    });
+    replaced.into_owned()
+}
+
+/// `template` with each `{{> name}}` replaced by the partial `name`, whose
+/// own includes are replaced in turn, up to `PARTIAL_DEPTH` deep.
+fn include_partials<'a>(
+    template: &'a str,
+    partials: &BTreeMap<String, String>,
+) -> Result<Cow<'a, str>, GenError> {
+    let mut template = Cow::Borrowed(template);
+    for _ in 0..PARTIAL_DEPTH {
+        if !PARTIAL_REGEX.is_match(&template) {
+            break;
+        }
+        let mut included = String::new();
+        let mut last = 0;
+        for captures in PARTIAL_REGEX.captures_iter(&template) {
+            let (all, name) = (captures.get(0).unwrap(), &captures[1]);
+            let partial = partials
+                .get(name)
+                .ok_or_else(|| GenError::UnknownPartial(name.to_owned()))?;
+            included.push_str(&template[last..all.start()]);
+            included.push_str(partial.strip_suffix('\n').unwrap_or(partial));
+            last = all.end();
+        }
+        included.push_str(&template[last..]);
+        template = Cow::Owned(included);
+    }
+    Ok(template)
+}
+
+/// `template` in tinytemplate's own syntax, with whole `{% ... %}` block
+/// tags as `{{ ... }}` and any other `%}`, as in `'{"a": "50%"}'`, kept.
+fn tinytemplate_syntax(template: &str) -> Cow<'_, str> {
+    BLOCK_TAG_REGEX.replace_all(template, "{{ $1 }}")
+}
+
+fn join_formatter(
+    value: &serde_json::Value,
+    output: &mut String,
+    separator: char,
+) -> tinytemplate::error::Result<()> {
+    let mut text = String::new();
+    tinytemplate::format_unescaped(value, &mut text)?;
+    output.push_str(&join_words(&text, separator));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2024, 6, 12).unwrap()
+    }
+
+    fn file_name(operation: Operation, column: Option<&str>) -> String {
+        compute_filename(&operation, "users", column, day(), 3).unwrap()
+    }
+
+    #[test]
+    fn file_names_of_every_operation() {
+        assert_eq!(file_name(Operation::Script, None), "2024061203 - users.sql");
+        assert_eq!(
+            file_name(Operation::CreateTable, None),
+            "2024061203 - create table users.sql"
+        );
+        assert_eq!(
+            file_name(Operation::AlterTable, None),
+            "2024061203 - alter table users.sql"
+        );
+        assert_eq!(
+            file_name(Operation::DropTable, None),
+            "2024061203 - drop table users.sql"
+        );
+        assert_eq!(
+            file_name(Operation::AddColumn, Some("email")),
+            "2024061203 - add column email to users.sql"
+        );
+        assert_eq!(
+            file_name(Operation::AlterColumn, Some("email")),
+            "2024061203 - alter column email in users.sql"
+        );
+        assert_eq!(
+            file_name(Operation::DropColumn, Some("email")),
+            "2024061203 - drop column email from users.sql"
+        );
+        assert_eq!(
+            file_name(Operation::CreateIndex, Some("email, created_at")),
+            "2024061203 - create index idx_users_email_created_at on users.sql"
+        );
+        assert_eq!(
+            file_name(Operation::DropIndex, Some("email")),
+            "2024061203 - drop index idx_users_email on users.sql"
+        );
+        assert_eq!(
+            file_name(Operation::AddForeignKey, Some("org_id")),
+            "2024061203 - add foreign key fk_users_org_id to users.sql"
+        );
+        assert_eq!(
+            file_name(Operation::DropConstraint, Some("email")),
+            "2024061203 - drop constraint ck_users_email from users.sql"
+        );
+        assert_eq!(
+            file_name(Operation::RenameTable, None),
+            "2024061203 - rename table users to new_users.sql"
+        );
+        assert_eq!(
+            file_name(Operation::RenameColumn, Some("email")),
+            "2024061203 - rename column email to new_email in users.sql"
+        );
+        assert_eq!(
+            file_name(Operation::CreateMaterializedView, None),
+            "2024061203 - create materialized view users.sql"
+        );
+    }
+
+    #[test]
+    fn script_names_replace_spaces() {
+        assert_eq!(
+            compute_filename(&Operation::Script, "fix up users", None, day(), 1).unwrap(),
+            "2024061201 - fix_up_users.sql"
+        );
+    }
+
+    #[test]
+    fn file_names_parse_back() {
+        let users = Some("users".to_owned());
+        let email = Some("email".to_owned());
+        assert_eq!(
+            Operation::from_file_name("create table users"),
+            (Operation::CreateTable, users.clone(), None)
+        );
+        assert_eq!(
+            Operation::from_file_name("alter column email in users"),
+            (Operation::AlterColumn, users.clone(), email.clone())
+        );
+        assert_eq!(
+            Operation::from_file_name("drop column email from users"),
+            (Operation::DropColumn, users.clone(), email)
+        );
+        assert_eq!(
+            Operation::from_file_name("create index idx_users_email on users"),
+            (Operation::CreateIndex, users.clone(), None)
+        );
+        assert_eq!(
+            Operation::from_file_name("drop foreign key users_org_fkey from users"),
+            (Operation::DropForeignKey, users.clone(), None)
+        );
+        assert_eq!(
+            Operation::from_file_name("rename table users to accounts"),
+            (Operation::RenameTable, users.clone(), None)
+        );
+        assert_eq!(
+            Operation::from_file_name("rename column email to primary_email in users"),
+            (Operation::RenameColumn, users, Some("email".to_owned()))
+        );
+        assert_eq!(
+            Operation::from_file_name("refresh materialized view sales"),
+            (
+                Operation::RefreshMaterializedView,
+                Some("sales".to_owned()),
+                None
+            )
+        );
+        assert_eq!(
+            Operation::from_file_name("fix_up_users"),
+            (Operation::Script, None, None)
+        );
+    }
+
+    #[test]
+    fn file_names_with_a_ticket_parse_back() {
+        let orders = Some("orders".to_owned());
+        assert_eq!(
+            Operation::from_file_name("DB-2 create table orders"),
+            (Operation::CreateTable, orders.clone(), None)
+        );
+        assert_eq!(
+            Operation::from_file_name("db_2_create_table_orders"),
+            (Operation::CreateTable, orders, None)
+        );
+        assert_eq!(
+            Operation::from_file_name("backfill 2 users"),
+            (Operation::Script, None, None)
+        );
+    }
+
+    #[test]
+    fn snake_and_kebab_file_names_parse_back() {
+        let users = Some("users".to_owned());
+        assert_eq!(
+            Operation::from_file_name("create_table_order_items"),
+            (Operation::CreateTable, Some("order_items".to_owned()), None)
+        );
+        assert_eq!(
+            Operation::from_file_name("add_column_created_at_to_users"),
+            (
+                Operation::AddColumn,
+                users.clone(),
+                Some("created_at".to_owned())
+            )
+        );
+        assert_eq!(
+            Operation::from_file_name("drop-index-idx-email-on-users"),
+            (Operation::DropIndex, users, None)
+        );
+    }
+
+    #[test]
+    fn index_is_padded_to_two_digits() {
+        let name = |index| compute_filename(&Operation::Script, "x", None, day(), index).unwrap();
+        assert_eq!(name(1), "2024061201 - x.sql");
+        assert_eq!(name(10), "2024061210 - x.sql");
+        assert_eq!(name(100), "20240612100 - x.sql");
+    }
+
+    #[test]
+    fn column_operations_require_a_column() {
+        for operation in [
+            Operation::AddColumn,
+            Operation::AlterColumn,
+            Operation::DropColumn,
+        ] {
+            assert!(matches!(
+                compute_filename(&operation, "users", None, day(), 1),
+                Err(GenError::MissingArgument("column"))
+            ));
+        }
+    }
+
+    #[test]
+    fn operations_without_templates_render_nothing() {
+        for operation in [
+            Operation::Script,
+            Operation::AlterTable,
+            Operation::DropTable,
+            Operation::AlterColumn,
+        ] {
+            assert_eq!(
+                render_operation(&operation, "users", None, Some("email")).unwrap(),
+                None
+            );
+        }
+    }
+
+    #[test]
+    fn data_migrations_assert_the_rows_they_update() {
+        let sql = render_operation(&Operation::DataMigration, "users", None, None)
+            .unwrap()
+            .unwrap();
+        assert!(sql.starts_with("-- A data fix of users"));
+        assert!(sql.contains("IF updated <> expected THEN"));
+        assert!(sql.contains("--     LIMIT 1000\n"));
+        assert!(sql.ends_with("COMMIT;"));
+        assert_eq!(
+            Operation::from_file_name("data migration on users"),
+            (Operation::DataMigration, Some("users".to_owned()), None)
+        );
+    }
+
+    #[test]
+    fn create_table_renders_with_and_without_schema() {
+        assert_eq!(
+            render_operation(&Operation::CreateTable, "users", None, None).unwrap(),
+            Some("CREATE TABLE IF NOT EXISTS users(\n    -- todo\n);".to_string())
+        );
+        assert_eq!(
+            render_operation(&Operation::CreateTable, "users", Some("app"), None).unwrap(),
+            Some("CREATE TABLE IF NOT EXISTS app.users(\n    -- todo\n);".to_string())
+        );
+    }
+
+    #[test]
+    fn languages_have_their_own_extension_and_templates() {
+        let file_name = |language| {
+            compute_filename_in(language, &Operation::CreateTable, "users", None, day(), 3).unwrap()
+        };
+        assert_eq!(
+            file_name(Language::Sql),
+            "2024061203 - create table users.sql"
+        );
+        assert_eq!(
+            file_name(Language::Cypher),
+            "2024061203 - create table users.cypher"
+        );
+        let render = |language| {
+            let extensions = Extensions::default();
+            render_operation_with(
+                language,
+                Dialect::Postgres,
+                &Operation::DropColumn,
+                "users",
+                Some("app"),
+                Some("email"),
+                &extensions,
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            render(Language::Cql),
+            Some("ALTER TABLE app.users\n    DROP email;".to_string())
+        );
+        assert_eq!(
+            render(Language::Surql),
+            Some("REMOVE FIELD IF EXISTS email ON TABLE users;".to_string())
+        );
+    }
+
+    #[test]
+    fn dialects_quote_their_own_way() {
+        let render = |dialect, schema| {
+            let extensions = Extensions::default();
+            render_operation_with(
+                Language::Sql,
+                dialect,
+                &Operation::CreateTable,
+                "users",
+                schema,
+                None,
+                &extensions,
+            )
+            .unwrap()
+            .unwrap()
+        };
+        assert!(render(Dialect::Mysql, Some("app"))
+            .starts_with("CREATE TABLE IF NOT EXISTS `app`.`users` ("));
+        assert!(render(Dialect::Mssql, None)
+            .starts_with("IF OBJECT_ID(N'users', N'U') IS NULL\nCREATE TABLE [users] ("));
+        assert!(render(Dialect::Sqlite, None).starts_with("CREATE TABLE IF NOT EXISTS \"users\" ("));
+        assert_eq!(
+            render(Dialect::Postgres, Some("app")),
+            render_operation(&Operation::CreateTable, "users", Some("app"), None)
+                .unwrap()
+                .unwrap()
+        );
+    }
+
+    #[test]
+    fn quoting_overrides_what_templates_quote() {
+        let render = |dialect, quoting, name| {
+            let extensions = Extensions {
+                dialect: Some(dialect),
+                quoting,
+                ..Extensions::default()
+            };
+            render_operation_with(
+                Language::Sql,
+                dialect,
+                &Operation::AddColumn,
+                name,
+                None,
+                Some("email:text"),
+                &extensions,
+            )
+            .unwrap()
+            .unwrap()
+        };
+        assert!(render(Dialect::Mysql, Some(Quoting::Never), "users")
+            .starts_with("ALTER TABLE users\n    ADD COLUMN email text"));
+        assert!(render(Dialect::Mysql, Some(Quoting::WhenNeeded), "key")
+            .starts_with("ALTER TABLE `key`\n    ADD COLUMN email text"));
+        assert!(
+            render(Dialect::Postgres, Some(Quoting::WhenNeeded), "Users")
+                .starts_with("ALTER TABLE \"Users\"\n    ADD COLUMN IF NOT EXISTS email text")
+        );
+        assert!(render(Dialect::Postgres, Some(Quoting::Always), "users")
+            .starts_with("ALTER TABLE \"users\"\n    ADD COLUMN IF NOT EXISTS \"email\" text"));
+        assert!(render(Dialect::Postgres, None, "Users").starts_with("ALTER TABLE Users\n"));
+    }
+
+    #[test]
+    fn allowed_keywords_are_quoted_in_any_template() {
+        let extensions = Extensions {
+            quoted: BTreeSet::from(["order".to_owned()]),
+            ..Extensions::default()
+        };
+        let root = TemplateData::new(
+            "order",
+            Some("app"),
+            None,
+            "CREATE TABLE {schema_name}{dot}{table_name} ();",
+        );
+        assert_eq!(
+            render_template(root, &extensions).unwrap(),
+            "CREATE TABLE app.\"order\" ();"
+        );
+        let built_in = render_operation_with(
+            Language::Sql,
+            Dialect::Postgres,
+            &Operation::CreateTable,
+            "order",
+            None,
+            None,
+            &extensions,
+        )
+        .unwrap()
+        .unwrap();
+        assert!(built_in.starts_with("CREATE TABLE IF NOT EXISTS \"order\"("));
+    }
+
+    #[test]
+    fn tests_name_the_schema_only_when_there_is_one() {
+        let test = |schema| {
+            let data = Operation::DropTable.get_test_template_data("users", schema, None);
+            render_template(data.unwrap(), &Extensions::default()).unwrap()
+        };
+        assert!(test(None).contains("SELECT hasnt_table('users', 'users is gone');"));
+        assert!(
+            test(Some("app")).contains("SELECT hasnt_table('app', 'users', 'app.users is gone');")
+        );
+        assert!(Operation::AlterTable
+            .get_test_template_data("users", None, None)
+            .is_none());
+    }
+
+    #[test]
+    fn values_are_not_escaped() {
+        let mut extensions = Extensions::default();
+        extensions
+            .tokens
+            .insert("author".to_owned(), "A <a@example.com>".to_owned());
+        let data = TemplateData::new("o'k", None, None, "{table_name} {author}");
+        assert_eq!(
+            render_template(data, &extensions).unwrap(),
+            "o'k A <a@example.com>"
+        );
+    }
+
+    #[test]
+    fn words_can_be_joined_for_file_names() {
+        let mut extensions = Extensions::default();
+        extensions.tokens.insert(
+            "description".to_owned(),
+            "add column email to users".to_owned(),
+        );
+        let template = "{description | snake} {description | kebab} {table_name | kebab}";
+        let data = TemplateData::new("user_roles", None, None, template);
+        assert_eq!(
+            render_template(data, &extensions).unwrap(),
+            "add_column_email_to_users add-column-email-to-users user-roles"
+        );
+    }
+
+    #[test]
+    fn blocks_can_be_written_with_percent_signs() {
+        let template =
+            "{% for column in columns %}{column}{% if not @last %},{% endif %}{% endfor %}\
+            {% if is.mysql %} ENGINE=InnoDB{% else %} ({dialect}){% endif %}";
+        let data = Operation::CreateTable.template_data(
+            Dialect::Mysql,
+            "users",
+            None,
+            Some("a,b"),
+            template,
+        );
+        assert_eq!(
+            render_template(data, &Extensions::default()).unwrap(),
+            "a,b ENGINE=InnoDB"
+        );
+        let data = TemplateData::new("users", None, Some("a"), template);
+        let extensions = Extensions {
+            dialect: Some(Dialect::Sqlite),
+            ..Extensions::default()
+        };
+        assert_eq!(render_template(data, &extensions).unwrap(), "a (sqlite)");
+    }
+
+    #[test]
+    fn percent_signs_outside_block_tags_are_kept() {
+        let template = "{% if is.sqlite %}WHERE{% endif %} name LIKE '%}%'";
+        let data = TemplateData::new("users", None, None, template);
+        let extensions = Extensions {
+            dialect: Some(Dialect::Sqlite),
+            ..Extensions::default()
+        };
+        assert_eq!(
+            render_template(data, &extensions).unwrap(),
+            "WHERE name LIKE '%}%'"
+        );
+    }
+
+    #[test]
+    fn partials_are_included_in_turn() {
+        let extensions = Extensions {
+            partials: BTreeMap::from([
+                (
+                    "header".to_owned(),
+                    "-- {{> owner}} owns {table_name}\n".to_owned(),
+                ),
+                ("owner".to_owned(), "{author}".to_owned()),
+                ("loop".to_owned(), "{{> loop}}".to_owned()),
+            ]),
+            tokens: BTreeMap::from([("author".to_owned(), "payments".to_owned())]),
+            ..Extensions::default()
+        };
+        let data = TemplateData::new("users", None, None, "{{> header}}\nSELECT 1;");
+        assert_eq!(
+            render_template(data, &extensions).unwrap(),
+            "-- payments owns users\nSELECT 1;"
+        );
+        let data = TemplateData::new("users", None, None, "{{>missing}}");
+        assert!(matches!(
+            render_template(data, &extensions),
+            Err(GenError::UnknownPartial(x)) if x == "missing"
+        ));
+        let data = TemplateData::new("users", None, None, "{{> loop}}");
+        assert!(render_template(data, &extensions).is_err());
+    }
+
+    #[test]
+    fn frontmatter_declares_variables() {
+        let template =
+            "---\nowner: team that owns it\n# a comment\nretention\n---\n{owner} {retention}";
+        let (variables, body) = frontmatter(template);
+        assert_eq!(
+            variables,
+            [
+                Variable {
+                    name: "owner".to_owned(),
+                    description: "team that owns it".to_owned()
+                },
+                Variable {
+                    name: "retention".to_owned(),
+                    description: String::new()
+                }
+            ]
+        );
+        assert_eq!(body, "{owner} {retention}");
+        assert_eq!(frontmatter("--- no frontmatter").1, "--- no frontmatter");
+
+        let data = || TemplateData::new("users", None, None, template);
+        assert!(matches!(
+            render_template(data(), &Extensions::default()),
+            Err(GenError::MissingVariable(x)) if x == "owner"
+        ));
+        let mut extensions = Extensions::default();
+        extensions.tokens.extend([
+            ("owner".to_owned(), "billing".to_owned()),
+            ("retention".to_owned(), "30".to_owned()),
+        ]);
+        assert_eq!(render_template(data(), &extensions).unwrap(), "billing 30");
+    }
+
+    #[test]
+    fn add_column_renders_the_column() {
+        assert_eq!(
+            render_operation(&Operation::AddColumn, "users", Some("app"), Some("email")).unwrap(),
+            Some(
+                "ALTER TABLE app.users\n    ADD COLUMN IF NOT EXISTS email\n    -- todo\n    ;"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn drop_column_renders_the_column() {
+        assert_eq!(
+            render_operation(&Operation::DropColumn, "users", None, Some("email")).unwrap(),
+            Some("alter table users\n  drop column if exists email;\n".to_string())
+        );
+    }
+
+    #[test]
+    fn rollbacks_undo_the_operation() {
+        let rollback = |operation: Operation, dialect| {
+            let data = operation.get_down_template_data(
+                Language::Sql,
+                dialect,
+                "users",
+                Some("app"),
+                Some("email"),
+            );
+            render_template(data.unwrap(), &Extensions::default()).unwrap()
+        };
+        assert_eq!(
+            rollback(Operation::CreateTable, Dialect::Postgres),
+            "DROP TABLE IF EXISTS app.users;"
+        );
+        assert_eq!(
+            rollback(Operation::AddColumn, Dialect::Mysql),
+            "ALTER TABLE `app`.`users`\n    DROP COLUMN `email`;"
+        );
+        assert!(Operation::AlterTable
+            .get_down_template_data(Language::Sql, Dialect::Postgres, "users", None, None)
+            .is_none());
+        assert!(Operation::CreateTable
+            .get_down_template_data(Language::Cql, Dialect::Postgres, "users", None, None)
+            .is_none());
+    }
+
+    #[test]
+    fn indexes_take_several_columns() {
+        assert_eq!(
+            render_operation(
+                &Operation::CreateIndex,
+                "users",
+                Some("app"),
+                Some("email,name")
+            )
+            .unwrap(),
+            Some(
+                "CREATE INDEX IF NOT EXISTS idx_users_email_name\n    ON app.users (email, name);"
+                    .to_string()
+            )
+        );
+        let mut extensions = Extensions::default();
+        extensions
+            .tokens
+            .insert("index_name".to_owned(), "users_email_key".to_owned());
+        let data = Operation::DropIndex.get_template_data(
+            Language::Sql,
+            Dialect::Mysql,
+            "users",
+            None,
+            Some("email"),
+        );
+        assert_eq!(
+            render_template(data.unwrap(), &extensions).unwrap(),
+            "DROP INDEX `users_email_key`\n    ON `users`;"
+        );
+    }
+
+    #[test]
+    fn foreign_keys_reference_the_table_their_column_is_named_after() {
+        assert_eq!(
+            render_operation(
+                &Operation::AddForeignKey,
+                "users",
+                Some("app"),
+                Some("org_id")
+            )
+            .unwrap()
+            .unwrap(),
+            "ALTER TABLE app.users\n    ADD CONSTRAINT fk_users_org_id\n    \
+             FOREIGN KEY (org_id)\n    REFERENCES app.org (id);"
+        );
+        let mut extensions = Extensions::default();
+        extensions.tokens.extend([
+            ("referenced_table".to_owned(), "people".to_owned()),
+            ("constraint_name".to_owned(), "users_owner_fkey".to_owned()),
+        ]);
+        let data = Operation::AddForeignKey.get_template_data(
+            Language::Sql,
+            Dialect::Mssql,
+            "users",
+            None,
+            Some("owner"),
+        );
+        assert!(render_template(data.unwrap(), &extensions)
+            .unwrap()
+            .contains("ADD CONSTRAINT [users_owner_fkey]\n    FOREIGN KEY ([owner])\n    REFERENCES [people] ([id]);"));
+    }
+
+    #[test]
+    fn renames_rename_to_the_new_name() {
+        let mut extensions = Extensions::default();
+        extensions
+            .tokens
+            .insert("new_name".to_owned(), "primary_email".to_owned());
+        let render = |dialect| {
+            let data = Operation::RenameColumn.get_template_data(
+                Language::Sql,
+                dialect,
+                "users",
+                Some("app"),
+                Some("email"),
+            );
+            render_template(data.unwrap(), &extensions).unwrap()
+        };
+        assert_eq!(
+            render(Dialect::Postgres),
+            "ALTER TABLE app.users\n    RENAME COLUMN email TO primary_email;"
+        );
+        assert_eq!(
+            render(Dialect::Mssql),
+            "EXEC sp_rename N'app.users.email', N'primary_email', N'COLUMN';"
+        );
+    }
+
+    #[test]
+    fn materialized_views_are_postgres_only() {
+        let render = |dialect, operation| {
+            let extensions = Extensions::default();
+            render_operation_with(
+                Language::Sql,
+                dialect,
+                &operation,
+                "sales",
+                Some("analytics"),
+                None,
+                &extensions,
+            )
+            .unwrap()
+        };
+        assert_eq!(
+            render(Dialect::Postgres, Operation::CreateView),
+            Some("CREATE OR REPLACE VIEW analytics.sales AS\nSELECT\n    -- todo\n;".to_owned())
+        );
+        assert!(render(Dialect::Mssql, Operation::CreateView)
+            .unwrap()
+            .starts_with("CREATE OR ALTER VIEW [analytics].[sales] AS"));
+        assert!(
+            render(Dialect::Postgres, Operation::RefreshMaterializedView)
+                .unwrap()
+                .ends_with("REFRESH MATERIALIZED VIEW analytics.sales;")
+        );
+        assert_eq!(
+            render(Dialect::Mysql, Operation::CreateMaterializedView),
+            None
+        );
+    }
+
+    #[test]
+    fn functions_are_dollar_quoted() {
+        let mut extensions = Extensions::default();
+        let render = |extensions: &Extensions| {
+            let data = Operation::CreateFunction.get_template_data(
+                Language::Sql,
+                Dialect::Postgres,
+                "touch",
+                Some("app"),
+                None,
+            );
+            render_template(data.unwrap(), extensions).unwrap()
+        };
+        let function = render(&extensions);
+        assert!(function.starts_with("CREATE OR REPLACE FUNCTION app.touch()\n"));
+        assert!(function.contains("LANGUAGE plpgsql\nAS $function$\n"));
+        assert!(function.ends_with("$function$;"));
+        extensions
+            .tokens
+            .insert("routine_language".to_owned(), "sql".to_owned());
+        assert!(render(&extensions).contains("LANGUAGE sql\n"));
+    }
+
+    #[test]
+    fn triggers_are_bound_to_their_table() {
+        let mut extensions = Extensions::default();
+        let render = |dialect, extensions: &Extensions| {
+            let data = Operation::CreateTrigger.get_template_data(
+                Language::Sql,
+                dialect,
+                "users",
+                None,
+                None,
+            );
+            render_template(data.unwrap(), extensions).unwrap()
+        };
+        let trigger = render(Dialect::Postgres, &extensions);
+        assert!(trigger.starts_with("CREATE OR REPLACE FUNCTION trg_users()\n"));
+        assert!(trigger.contains("    AFTER INSERT OR UPDATE OR DELETE ON users\n"));
+        assert!(trigger.ends_with("EXECUTE FUNCTION trg_users();"));
+        assert!(render(Dialect::Sqlite, &extensions).contains("    AFTER INSERT ON \"users\"\n"));
+        extensions
+            .tokens
+            .insert("trigger_name".to_owned(), "audit_users".to_owned());
+        assert!(render(Dialect::Mssql, &extensions)
+            .starts_with("CREATE OR ALTER TRIGGER [audit_users]\n"));
+        assert_eq!(
+            compute_filename(&Operation::DropTrigger, "users", None, day(), 1).unwrap(),
+            "2024061201 - drop trigger trg_users on users.sql"
+        );
+        assert_eq!(
+            Operation::from_file_name("create trigger audit_users on users"),
+            (Operation::CreateTrigger, Some("users".to_owned()), None)
+        );
+    }
+
+    #[test]
+    fn enums_list_their_quoted_values() {
+        let extensions = Extensions {
+            values: vec!["happy".to_owned(), "it's ok".to_owned()],
+            ..Extensions::default()
+        };
+        let render = |operation: Operation| {
+            let data =
+                operation.get_template_data(Language::Sql, Dialect::Postgres, "mood", None, None);
+            render_template(data.unwrap(), &extensions).unwrap()
+        };
+        assert_eq!(
+            render(Operation::CreateEnum),
+            "CREATE TYPE mood AS ENUM ('happy', 'it''s ok');"
+        );
+        let alter = render(Operation::AlterEnum);
+        assert!(alter.starts_with("-- no-transaction\n"));
+        let (marker, statements) = split_no_transaction(&alter);
+        assert_eq!(marker, "-- no-transaction\n");
+        assert!(statements.starts_with("-- ALTER TYPE ... ADD VALUE"));
+        assert_eq!(
+            split_no_transaction("-- no-transactions\nx"),
+            ("", "-- no-transactions\nx")
+        );
+        assert_eq!(split_no_transaction("SELECT 1;"), ("", "SELECT 1;"));
+        assert!(alter.ends_with(
+            "ALTER TYPE mood ADD VALUE IF NOT EXISTS 'happy';\n\
+             ALTER TYPE mood ADD VALUE IF NOT EXISTS 'it''s ok';"
+        ));
+        assert!(Operation::CreateEnum
+            .get_template_data(Language::Sql, Dialect::Mysql, "mood", None, None)
+            .is_none());
+    }
+
+    #[test]
+    fn seeds_insert_their_batches() {
+        let extensions = Extensions {
+            seed: Some(
+                Seed::parse(Dialect::Sqlite, "code,name\nie,Ireland\nfr,\n", Some(1)).unwrap(),
+            ),
+            ..Extensions::default()
+        };
+        let data = Operation::Seed.get_template_data(
+            Language::Sql,
+            Dialect::Sqlite,
+            "countries",
+            None,
+            None,
+        );
+        assert_eq!(
+            render_template(data.unwrap(), &extensions).unwrap(),
+            "INSERT INTO \"countries\" (\"code\", \"name\")\n\
+             VALUES\n    ('ie', 'Ireland');\n\n\
+             INSERT INTO \"countries\" (\"code\", \"name\")\n\
+             VALUES\n    ('fr', NULL);"
+        );
+        assert_eq!(
+            Operation::from_file_name("seed countries"),
+            (Operation::Seed, Some("countries".to_owned()), None)
+        );
+    }
+
+    #[test]
+    fn added_columns_take_their_type_from_the_spec() {
+        let render = |dialect, column| {
+            let data = Operation::AddColumn.get_template_data(
+                Language::Sql,
+                dialect,
+                "users",
+                None,
+                Some(column),
+            );
+            render_template(data.unwrap(), &Extensions::default()).unwrap()
+        };
+        assert_eq!(
+            render(Dialect::Postgres, "age:int:not null default 0"),
+            "ALTER TABLE users\n    ADD COLUMN IF NOT EXISTS age int not null default 0;"
+        );
+        assert_eq!(
+            render(Dialect::Mysql, "age:int"),
+            "ALTER TABLE `users`\n    ADD COLUMN `age` int;"
+        );
+        assert!(render(Dialect::Postgres, "age").ends_with("age\n    -- todo\n    ;"));
+        assert_eq!(
+            compute_filename(&Operation::AddColumn, "users", Some("age:int"), day(), 1).unwrap(),
+            "2024061201 - add column age to users.sql"
+        );
+    }
+
+    #[test]
+    fn several_columns_are_a_statement_each() {
+        let render = |operation: &Operation, column| {
+            render_operation(operation, "users", None, Some(column))
+                .unwrap()
+                .unwrap()
+        };
+        assert_eq!(
+            render(&Operation::AddColumn, "price:numeric(10,2), age:int"),
+            "ALTER TABLE users\n    ADD COLUMN IF NOT EXISTS price numeric(10,2);\n\n\
+             ALTER TABLE users\n    ADD COLUMN IF NOT EXISTS age int;"
+        );
+        assert_eq!(
+            render(&Operation::CreateTable, "id:bigint:primary key,email:text"),
+            "CREATE TABLE IF NOT EXISTS users(\n    id bigint primary key,\n    email text\n);"
+        );
+        let name = |operation| compute_filename(&operation, "users", Some("a:int,b"), day(), 1);
+        assert_eq!(
+            name(Operation::DropColumn).unwrap(),
+            "2024061201 - drop columns a, b from users.sql"
+        );
+        assert_eq!(
+            Operation::from_file_name("add columns a, b to users"),
+            (
+                Operation::AddColumn,
+                Some("users".to_owned()),
+                Some("a, b".to_owned())
+            )
+        );
+    }
+
+    #[test]
+    fn new_tables_list_their_keys_after_their_columns() {
+        let extensions = Extensions {
+            keys: vec![
+                Key::primary_key("id"),
+                Key::foreign_key("org_id:orgs").unwrap(),
+            ],
+            ..Extensions::default()
+        };
+        let render = |dialect, column| {
+            let data = Operation::CreateTable.get_template_data(
+                Language::Sql,
+                dialect,
+                "users",
+                None,
+                column,
+            );
+            render_template(data.unwrap(), &extensions).unwrap()
+        };
+        assert_eq!(
+            render(Dialect::Postgres, Some("id:bigint,org_id:bigint")),
+            "CREATE TABLE IF NOT EXISTS users(\n    id bigint,\n    org_id bigint,\n    \
+             PRIMARY KEY (id),\n    FOREIGN KEY (org_id)\n        REFERENCES orgs (id)\n);"
+        );
+        assert!(render(Dialect::Mssql, None).ends_with(
+            "(\n    PRIMARY KEY ([id]),\n    FOREIGN KEY ([org_id])\n        REFERENCES [orgs] ([id])\n);"
+        ));
+    }
+}
@@ -0,0 +1,262 @@
+//! Dynamic library plugins loaded from the root's `plugins/` directory.
+//!
+//! A plugin exports the C functions declared in `include/gen_plugin.h`. Its
+//! manifest can add operations, tokens that are filled into every template
+//! context, and helpers that templates apply as `{value | helper}`.
+
+use crate::error::GenError;
+use crate::language::Language;
+use crate::operation::{format_file_name, render_template, Extensions, TemplateData};
+use chrono::NaiveDate;
+use libloading::Library;
+use serde::{Deserialize, Serialize};
+use std::ffi::{c_char, CStr, CString};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Directory under the root that plugins are loaded from.
+pub const PLUGINS_DIR: &str = "plugins";
+
+/// The `abi` a manifest has to declare to be loaded.
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+type ManifestFn = unsafe extern "C" fn() -> *const c_char;
+type CallFn = unsafe extern "C" fn(*const c_char, *const c_char) -> *mut c_char;
+type FreeFn = unsafe extern "C" fn(*mut c_char);
+
+#[derive(Deserialize)]
+struct Manifest {
+    abi: u32,
+    name: String,
+    #[serde(default)]
+    operations: Vec<PluginOperation>,
+    #[serde(default)]
+    tokens: Vec<String>,
+    #[serde(default)]
+    helpers: Vec<String>,
+}
+
+/// An operation contributed by a plugin.
+#[derive(Deserialize)]
+pub struct PluginOperation {
+    pub name: String,
+    /// Template for the descriptive part of the file name, e.g.
+    /// `create view {table_name}`.
+    pub file_name: String,
+    pub template: Option<String>,
+    #[serde(default)]
+    pub requires_column: bool,
+}
+
+/// What a plugin's token function is told about the migration being generated.
+#[derive(Serialize)]
+struct TokenContext<'a> {
+    operation: &'a str,
+    name: &'a str,
+    column: Option<&'a str>,
+    schema: Option<&'a str>,
+}
+
+pub struct Plugin {
+    manifest: Manifest,
+    token: Option<CallFn>,
+    helper: Option<CallFn>,
+    free: Option<FreeFn>,
+    // Keeps the function pointers above valid.
+    _library: Library,
+}
+
+impl Plugin {
+    /// Loads the plugin at `path`.
+    ///
+    /// # Safety
+    ///
+    /// Loading a library runs its initialisers, and its exports are trusted to
+    /// match `include/gen_plugin.h`.
+    unsafe fn load(path: &Path) -> Result<Self, GenError> {
+        let error = |x: libloading::Error| GenError::Plugin(format!("{}: {x}", path.display()));
+        let library = Library::new(path).map_err(error)?;
+        let manifest = *library
+            .get::<ManifestFn>(b"gen_plugin_manifest")
+            .map_err(error)?;
+        let manifest = CStr::from_ptr(manifest()).to_string_lossy();
+        let manifest: Manifest = serde_json::from_str(&manifest)
+            .map_err(|x| GenError::Plugin(format!("{}: bad manifest: {x}", path.display())))?;
+        if manifest.abi != PLUGIN_ABI_VERSION {
+            return Err(GenError::Plugin(format!(
+                "{}: plugin abi {} is not supported, expected {PLUGIN_ABI_VERSION}",
+                path.display(),
+                manifest.abi
+            )));
+        }
+
+        let optional = |symbol: &[u8]| library.get::<CallFn>(symbol).ok().map(|x| *x);
+        let token = optional(b"gen_plugin_token");
+        let helper = optional(b"gen_plugin_helper");
+        let free = library.get::<FreeFn>(b"gen_plugin_free").ok().map(|x| *x);
+        if (!manifest.tokens.is_empty() && token.is_none())
+            || (!manifest.helpers.is_empty() && helper.is_none())
+            || ((token.is_some() || helper.is_some()) && free.is_none())
+        {
+            return Err(GenError::Plugin(format!(
+                "{}: manifest declares functions the library does not export",
+                path.display()
+            )));
+        }
+
+        Ok(Plugin {
+            manifest,
+            token,
+            helper,
+            free,
+            _library: library,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.manifest.name
+    }
+
+    pub fn operations(&self) -> &[PluginOperation] {
+        &self.manifest.operations
+    }
+
+    /// Calls one of the plugin's string functions, returning `None` when it
+    /// answers with a null pointer.
+    fn call(&self, function: Option<CallFn>, first: &str, second: &str) -> Option<String> {
+        let function = function?;
+        let first = CString::new(first).ok()?;
+        let second = CString::new(second).ok()?;
+        // The manifest check in `load` guarantees `free` with any function.
+        unsafe {
+            let result = function(first.as_ptr(), second.as_ptr());
+            if result.is_null() {
+                return None;
+            }
+            let value = CStr::from_ptr(result).to_string_lossy().into_owned();
+            (self.free.unwrap())(result);
+            Some(value)
+        }
+    }
+}
+
+/// Every plugin found under a root.
+#[derive(Default)]
+pub struct Plugins {
+    plugins: Vec<Arc<Plugin>>,
+}
+
+impl Plugins {
+    /// Loads every dynamic library in `<root>/plugins`, in file name order.
+    /// A root without that directory has no plugins.
+    ///
+    /// # Safety
+    ///
+    /// See `Plugin::load`: the libraries are trusted code.
+    pub unsafe fn load(root: &Path) -> Result<Self, GenError> {
+        let directory = root.join(PLUGINS_DIR);
+        if !directory.is_dir() {
+            return Ok(Plugins::default());
+        }
+        let mut paths = std::fs::read_dir(directory)?
+            .map(|x| x.map(|x| x.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        paths.retain(|x| {
+            x.extension()
+                .is_some_and(|x| x == std::env::consts::DLL_EXTENSION)
+        });
+        paths.sort();
+
+        let mut plugins = Vec::new();
+        for path in paths {
+            plugins.push(Arc::new(Plugin::load(&path)?));
+        }
+        Ok(Plugins { plugins })
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Plugin> {
+        self.plugins.iter().map(Arc::as_ref)
+    }
+
+    pub fn operation(&self, name: &str) -> Option<&PluginOperation> {
+        self.iter()
+            .flat_map(Plugin::operations)
+            .find(|x| x.name == name)
+    }
+
+    /// Collects every plugin's tokens and helpers for one migration.
+    pub(crate) fn extensions(
+        &self,
+        operation: &str,
+        name: &str,
+        schema: Option<&str>,
+        column: Option<&str>,
+    ) -> Result<Extensions, GenError> {
+        let context = serde_json::to_string(&TokenContext {
+            operation,
+            name,
+            column,
+            schema,
+        })
+        .map_err(|x| GenError::Plugin(x.to_string()))?;
+
+        let mut extensions = Extensions::default();
+        for plugin in &self.plugins {
+            for token in &plugin.manifest.tokens {
+                if let Some(value) = plugin.call(plugin.token, token, &context) {
+                    extensions.tokens.insert(token.clone(), value);
+                }
+            }
+            for helper in &plugin.manifest.helpers {
+                let plugin = Arc::clone(plugin);
+                let name = helper.clone();
+                extensions.helpers.push((
+                    helper.clone(),
+                    Arc::new(move |value, output| {
+                        let value = serde_json::to_string(value)?;
+                        let formatted =
+                            plugin.call(plugin.helper, &name, &value).ok_or_else(|| {
+                                tinytemplate::error::Error::GenericError {
+                                    msg: format!("helper {name} failed on {value}"),
+                                }
+                            })?;
+                        output.push_str(&formatted);
+                        Ok(())
+                    }),
+                ));
+            }
+        }
+        Ok(extensions)
+    }
+
+    /// Computes the file name and body of a plugin operation.
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn render(
+        &self,
+        operation: &str,
+        name: &str,
+        schema: Option<&str>,
+        column: Option<&str>,
+        day: NaiveDate,
+        index: i32,
+        extensions: &Extensions,
+    ) -> Result<(String, Option<String>), GenError> {
+        let operation = self
+            .operation(operation)
+            .ok_or_else(|| GenError::Plugin(format!("no plugin provides {operation:?}")))?;
+        if operation.requires_column && column.is_none() {
+            return Err(GenError::MissingArgument("column"));
+        }
+
+        let render = |template: &str| {
+            let data = TemplateData::new(name, schema, column, template.to_owned());
+            render_template(data, extensions)
+        };
+        let file_name_part = render(&operation.file_name)?;
+        let content = operation.template.as_deref().map(render).transpose()?;
+        Ok((
+            format_file_name(Language::Sql, day, index, &file_name_part),
+            content,
+        ))
+    }
+}
@@ -0,0 +1,49 @@
+//! Python bindings for the naming and rendering core, built as the `thing`
+//! extension module.
+
+use crate::{compute_filename as compute, render_operation as render, Operation};
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+fn parse_operation(operation: &str) -> PyResult<Operation> {
+    Operation::from_str(operation, false)
+        .map_err(|_| PyValueError::new_err(format!("unknown operation {operation:?}")))
+}
+
+/// Computes the file name of a migration dated `date` with the given daily
+/// `index`.
+#[pyfunction]
+#[pyo3(signature = (operation, name, date, index, column = None))]
+fn compute_filename(
+    operation: &str,
+    name: &str,
+    date: NaiveDate,
+    index: i32,
+    column: Option<&str>,
+) -> PyResult<String> {
+    compute(&parse_operation(operation)?, name, column, date, index)
+        .map_err(|x| PyValueError::new_err(x.to_string()))
+}
+
+/// Renders the body of a migration, or `None` for operations without a
+/// template.
+#[pyfunction]
+#[pyo3(signature = (operation, name, schema = None, column = None))]
+fn render_operation(
+    operation: &str,
+    name: &str,
+    schema: Option<&str>,
+    column: Option<&str>,
+) -> PyResult<Option<String>> {
+    render(&parse_operation(operation)?, name, schema, column)
+        .map_err(|x| PyValueError::new_err(x.to_string()))
+}
+
+#[pymodule]
+fn thing(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(compute_filename, module)?)?;
+    module.add_function(wrap_pyfunction!(render_operation, module)?)?;
+    Ok(())
+}
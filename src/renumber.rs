@@ -0,0 +1,297 @@
+//! Moving the migrations a branch added after the ones its base already has,
+//! which is what every rebase onto a base that gained migrations calls for,
+//! and apart when a merge left several with the same date and index.
+
+use crate::config::Config;
+use crate::counter::Counter;
+use crate::document;
+use crate::error::GenError;
+use crate::git;
+use crate::index::MigrationIndex;
+use crate::manifest::{relative_path, Manifest, MANIFEST_FILE};
+use crate::naming::{parse_file_name, redescribed, renumbered};
+use crate::operation::Operation;
+use crate::verify::scope;
+use chrono::NaiveDate;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A migration that gets a new date and index.
+#[derive(Debug, PartialEq, Eq)]
+pub struct Rename {
+    pub from: PathBuf,
+    pub to: PathBuf,
+    pub date: NaiveDate,
+    pub index: i32,
+}
+
+/// Plans the renames that put every migration in `branch_files` after the
+/// latest other migration of its numbering space, keeping the branch's own
+/// order. A migration that already sorts after everything before it keeps its
+/// name; one that does not is numbered right after its predecessor, on that
+/// predecessor's date.
+pub fn plan(
+    root: &Path,
+    config: &Config,
+    index: &MigrationIndex,
+    branch_files: &BTreeSet<PathBuf>,
+) -> Vec<Rename> {
+    let mut base = BTreeMap::new();
+    let mut branch = BTreeMap::<_, Vec<_>>::new();
+    for path in index.files() {
+        if config.is_rollback(root, path) {
+            continue;
+        }
+        let name = path
+            .file_name()
+            .and_then(|x| x.to_str())
+            .and_then(parse_file_name);
+        let Some(name) = name else {
+            continue;
+        };
+        let space = (
+            scope(root, config, path),
+            name.service.map(ToOwned::to_owned),
+        );
+        let prefix = (name.date, name.index);
+        if branch_files.contains(path) {
+            branch.entry(space).or_default().push((prefix, path));
+        } else {
+            let last = base.entry(space).or_insert(prefix);
+            *last = (*last).max(prefix);
+        }
+    }
+
+    let mut renames = Vec::new();
+    for (space, mut files) in branch {
+        files.sort();
+        let mut last = base.get(&space).copied();
+        for ((date, index), path) in files {
+            let (date, index) = match last {
+                Some(last) if (date, index) <= last => (last.0, last.1 + 1),
+                _ => (date, index),
+            };
+            last = Some((date, index));
+            let file_name = path.file_name().unwrap().to_str().unwrap();
+            let name = parse_file_name(file_name).unwrap();
+            if (date, index) == (name.date, name.index) {
+                continue;
+            }
+            renames.push(Rename {
+                from: path.to_path_buf(),
+                to: path.with_file_name(
+                    renumbered(file_name, date, index, config.index_width()).unwrap(),
+                ),
+                date,
+                index,
+            });
+        }
+    }
+    renames
+}
+
+/// Plans the renames that give every migration sharing its date and index
+/// with others in its numbering space an index of its own, after the last
+/// one of that day. Of those sharing one, the migration committed first
+/// keeps it, `order` giving the position commits added each file in; files
+/// not in it come last, by name.
+pub fn plan_collisions(
+    root: &Path,
+    config: &Config,
+    index: &MigrationIndex,
+    order: &BTreeMap<PathBuf, usize>,
+) -> Vec<Rename> {
+    let mut numbered = BTreeMap::<_, Vec<_>>::new();
+    for path in index.files() {
+        if config.is_rollback(root, path) {
+            continue;
+        }
+        let name = path
+            .file_name()
+            .and_then(|x| x.to_str())
+            .and_then(parse_file_name);
+        let Some(name) = name else {
+            continue;
+        };
+        let day = (
+            scope(root, config, path),
+            name.service.map(ToOwned::to_owned),
+            name.date,
+        );
+        numbered.entry(day).or_default().push((
+            name.index,
+            order.get(path).copied().unwrap_or(usize::MAX),
+            path,
+        ));
+    }
+
+    let mut renames = Vec::new();
+    for ((_, _, date), mut files) in numbered {
+        files.sort();
+        let mut last = files.iter().map(|x| x.0).max().unwrap_or_default();
+        for pair in files.windows(2) {
+            let ((index, ..), (next_index, _, path)) = (pair[0], pair[1]);
+            if index != next_index {
+                continue;
+            }
+            last += 1;
+            let file_name = path.file_name().unwrap().to_str().unwrap();
+            renames.push(Rename {
+                from: path.to_path_buf(),
+                to: path.with_file_name(
+                    renumbered(file_name, date, last, config.index_width()).unwrap(),
+                ),
+                date,
+                index: last,
+            });
+        }
+    }
+    renames
+}
+
+/// The rename giving the migration at `path` the description `description`,
+/// keeping its service, date, index, language and spelling, or `None` when
+/// `path` is not a migration.
+pub fn describe(path: &Path, description: &str) -> Option<Rename> {
+    let file_name = path.file_name()?.to_str()?;
+    let name = parse_file_name(file_name)?;
+    Some(Rename {
+        from: path.to_path_buf(),
+        to: path.with_file_name(redescribed(file_name, description)?),
+        date: name.date,
+        index: name.index,
+    })
+}
+
+/// Carries out `renames`, moving rollbacks along with their migrations and
+/// keeping the manifest, `LATEST`, `MIGRATIONS.md` and the counter in line.
+/// A manifest entry whose description changed also gets the operation and
+/// table of the new one.
+/// Files git tracks are moved with `git mv`, and the others, every one outside
+/// a repository, renamed.
+pub fn apply(root: &Path, config: &Config, renames: &[Rename]) -> Result<(), GenError> {
+    let mut moves = Vec::new();
+    for rename in renames {
+        moves.push((rename.from.clone(), rename.to.clone()));
+        if let (Some(from), Some(to)) = (
+            config.rollback(root, &rename.from),
+            config.rollback(root, &rename.to),
+        ) {
+            if from.exists() {
+                moves.push((from, to));
+            }
+        }
+    }
+
+    // Through temporary names, since a migration may take the name another
+    // one of the branch's migrations still has.
+    let tracked = git::tracked_files(root)?;
+    let temporary = |x: &Path| PathBuf::from(format!("{}.renumber", x.display()));
+    let move_file = |from: &Path, to: &Path, tracked: bool| match tracked {
+        true => git::mv(root, from, to),
+        false => Ok(fs::rename(from, to)?),
+    };
+    for (from, _) in &moves {
+        move_file(from, &temporary(from), tracked.contains(from))?;
+    }
+    for (from, to) in &moves {
+        move_file(&temporary(from), to, tracked.contains(from))?;
+    }
+
+    if root.join(MANIFEST_FILE).exists() {
+        let mut manifest = Manifest::load(root)?;
+        for rename in renames {
+            let from = relative_path(root, &rename.from);
+            let Some(position) = manifest.migrations.iter().position(|x| x.path == from) else {
+                continue;
+            };
+            let mut entry = manifest.migrations.remove(position);
+            let description = |x: &Path| {
+                let name = parse_file_name(x.file_name()?.to_str()?)?;
+                Some(name.description.to_owned())
+            };
+            if let Some(to) =
+                description(&rename.to).filter(|x| Some(x) != description(&rename.from).as_ref())
+            {
+                let (operation, table, column) = Operation::from_file_name(&to);
+                entry.operation = operation.name();
+                entry.table = table.unwrap_or(to);
+                entry.column = column;
+            }
+            entry.path = relative_path(root, &rename.to);
+            entry.date = rename.date;
+            entry.index = rename.index;
+            manifest.insert(entry);
+        }
+        manifest.store(root)?;
+    }
+
+    if let Some(kind) = config.latest {
+        let latest = root.join(crate::latest::LATEST_FILE);
+        let target = fs::read_link(&latest)
+            .ok()
+            .map(|x| x.display().to_string())
+            .or_else(|| fs::read_to_string(&latest).ok())
+            .map(|x| x.trim().to_owned());
+        let renamed = renames
+            .iter()
+            .find(|x| target.as_deref() == Some(&relative_path(root, &x.from)));
+        if let Some(rename) = renamed {
+            crate::latest::update(root, &rename.to, kind)?;
+        }
+    }
+
+    if config.index_document {
+        document::write(root, &MigrationIndex::scan(root))?;
+    }
+
+    if let Some(counter) = Counter::load(root)? {
+        let last = renames.iter().map(|x| (x.date, x.index)).max();
+        if let Some((date, index)) = last {
+            // Only ever moves forward, like generation does.
+            if counter
+                .last_index_for(date)
+                .is_ok_and(|x| x.is_none_or(|x| x < index))
+            {
+                Counter::new(date, index).store(root)?;
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(day: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(day, "%Y%m%d").unwrap()
+    }
+
+    #[test]
+    fn collisions_keep_the_first_committed() {
+        let root = Path::new("/nonexistent/root");
+        let mut index = MigrationIndex::scan(root);
+        for name in [
+            "2024061201 - create table users.sql",
+            "2024061202 - add column email to users.sql",
+            "2024061202 - create table orders.sql",
+            "2024061202 - create table orders.down.sql",
+            "2024061301 - create table items.sql",
+        ] {
+            index.add(root.join(name));
+        }
+        let order = BTreeMap::from([(root.join("2024061202 - create table orders.sql"), 0)]);
+        let renames = plan_collisions(root, &Config::default(), &index, &order);
+        assert_eq!(
+            renames,
+            [Rename {
+                from: root.join("2024061202 - add column email to users.sql"),
+                to: root.join("2024061203 - add column email to users.sql"),
+                date: day("20240612"),
+                index: 3,
+            }]
+        );
+    }
+}
@@ -0,0 +1,655 @@
+//! The tables and columns the migrations add up to, reconstructed by folding
+//! their SQL in order. Only the DDL that shapes tables is understood; anything
+//! else is skipped, so the model is a best effort rather than what a database
+//! would report.
+
+#[cfg(feature = "fs")]
+use crate::config::Config;
+#[cfg(feature = "fs")]
+use crate::error::GenError;
+#[cfg(feature = "fs")]
+use crate::index::MigrationIndex;
+#[cfg(feature = "fs")]
+use crate::language::Language;
+#[cfg(feature = "fs")]
+use crate::naming::parse_file_name;
+use std::collections::BTreeMap;
+#[cfg(feature = "fs")]
+use std::path::Path;
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct SchemaModel {
+    /// By name, qualified with the schema when the migrations give one, e.g.
+    /// `app.users`.
+    pub tables: BTreeMap<String, Table>,
+}
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct Table {
+    /// In the order they were added.
+    pub columns: Vec<Column>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Column {
+    pub name: String,
+    /// As written, e.g. `varchar(255)`; empty when the migrations never said.
+    pub data_type: String,
+    pub references: Option<Reference>,
+}
+
+/// The target of a foreign key.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    pub table: String,
+    /// `None` for the referenced table's primary key.
+    pub column: Option<String>,
+}
+
+impl Table {
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|x| x.name == name)
+    }
+
+    fn column_mut(&mut self, name: &str) -> Option<&mut Column> {
+        self.columns.iter_mut().find(|x| x.name == name)
+    }
+
+    fn add(&mut self, column: Column) {
+        self.columns.retain(|x| x.name != column.name);
+        self.columns.push(column);
+    }
+}
+
+impl SchemaModel {
+    /// Folds every forward SQL migration of the root, oldest first.
+    #[cfg(feature = "fs")]
+    pub fn load(root: &Path, config: &Config, index: &MigrationIndex) -> Result<Self, GenError> {
+        let mut migrations = index
+            .files()
+            .filter(|x| !config.is_rollback(root, x))
+            .filter_map(|path| {
+                let name = parse_file_name(path.file_name()?.to_str()?)?;
+                (name.language == Language::Sql).then_some((name.date, name.index, path))
+            })
+            .collect::<Vec<_>>();
+        migrations.sort();
+
+        let mut model = SchemaModel::default();
+        for (_, _, path) in migrations {
+            model.apply(&std::fs::read_to_string(path)?);
+        }
+        Ok(model)
+    }
+
+    /// Applies the statements of one migration.
+    pub fn apply(&mut self, sql: &str) {
+        let tokens = tokenize(sql);
+        for statement in tokens.split(|x| *x == Token::Symbol(';')) {
+            self.statement(&mut Parser {
+                tokens: statement,
+                position: 0,
+            });
+        }
+    }
+
+    fn statement(&mut self, parser: &mut Parser) {
+        // A statement SQL Server only runs when a condition holds, e.g.
+        // `IF OBJECT_ID(...) IS NULL CREATE TABLE ...`.
+        if parser.keyword("IF") {
+            while parser.position < parser.tokens.len()
+                && !parser.peek_any_keyword(&["CREATE", "ALTER", "DROP"])
+            {
+                parser.position += 1;
+            }
+        }
+        if parser.keyword("CREATE") {
+            parser.keyword("OR");
+            parser.keyword("REPLACE");
+            parser.any_keyword(&["TEMP", "TEMPORARY", "UNLOGGED"]);
+            if parser.keyword("TABLE") {
+                parser.keywords(&["IF", "NOT", "EXISTS"]);
+                if let Some(name) = parser.name() {
+                    let table = parser.group().map(table).unwrap_or_default();
+                    self.tables.insert(name, table);
+                }
+            }
+        } else if parser.keywords(&["ALTER", "TABLE"]) {
+            parser.keywords(&["IF", "EXISTS"]);
+            parser.keyword("ONLY");
+            if let Some(name) = parser.name() {
+                for action in split_commas(&parser.tokens[parser.position..]) {
+                    self.alter(&name, action);
+                }
+            }
+        } else if parser.keywords(&["DROP", "TABLE"]) {
+            parser.keywords(&["IF", "EXISTS"]);
+            while let Some(name) = parser.name() {
+                self.tables.remove(&name);
+                if !parser.symbol(',') {
+                    break;
+                }
+            }
+        }
+    }
+
+    fn alter(&mut self, name: &str, action: &[Token]) {
+        let parser = &mut Parser {
+            tokens: action,
+            position: 0,
+        };
+        if parser.keywords(&["RENAME", "TO"]) {
+            if let (Some(new_name), Some(table)) = (parser.name(), self.tables.remove(name)) {
+                // A new name stays in the table's schema.
+                let new_name = match name.rsplit_once('.') {
+                    Some((schema, _)) if !new_name.contains('.') => format!("{schema}.{new_name}"),
+                    _ => new_name,
+                };
+                self.tables.insert(new_name, table);
+            }
+            return;
+        }
+        let Some(table) = self.tables.get_mut(name) else {
+            return;
+        };
+        if parser.keyword("ADD") {
+            if let Some(foreign_key) = parser.rest().and_then(table_constraint) {
+                foreign_key.apply(table);
+                return;
+            }
+            parser.keyword("COLUMN");
+            parser.keywords(&["IF", "NOT", "EXISTS"]);
+            if let Some(column) = column(&parser.tokens[parser.position..]) {
+                table.add(column);
+            }
+        } else if parser.keyword("DROP") {
+            if parser.keyword("CONSTRAINT") {
+                return;
+            }
+            parser.keyword("COLUMN");
+            parser.keywords(&["IF", "EXISTS"]);
+            if let Some(column) = parser.identifier() {
+                table.columns.retain(|x| x.name != column);
+            }
+        } else if parser.keyword("RENAME") {
+            if parser.keyword("CONSTRAINT") {
+                return;
+            }
+            parser.keyword("COLUMN");
+            let from = parser.identifier();
+            parser.keyword("TO");
+            let to = parser.identifier();
+            if let (Some(column), Some(to)) = (from.and_then(|x| table.column_mut(&x)), to) {
+                column.name = to;
+            }
+        } else if parser.keyword("ALTER") {
+            parser.keyword("COLUMN");
+            let column = parser.identifier();
+            if parser.keyword("TYPE") || parser.keywords(&["SET", "DATA", "TYPE"]) {
+                let data_type = data_type(&parser.tokens[parser.position..]);
+                if let Some(column) = column.and_then(|x| table.column_mut(&x)) {
+                    column.data_type = data_type;
+                }
+            }
+        }
+    }
+}
+
+/// The table a `CREATE TABLE` body defines.
+fn table(body: &[Token]) -> Table {
+    let mut table = Table::default();
+    let mut foreign_keys = Vec::new();
+    for element in split_commas(body) {
+        match table_constraint(element) {
+            Some(foreign_key) => foreign_keys.push(foreign_key),
+            None if is_constraint(element) => {}
+            None => table.columns.extend(column(element)),
+        }
+    }
+    for foreign_key in foreign_keys {
+        foreign_key.apply(&mut table);
+    }
+    table
+}
+
+fn is_constraint(tokens: &[Token]) -> bool {
+    let parser = Parser {
+        tokens,
+        position: 0,
+    };
+    parser.peek_any_keyword(&[
+        "CONSTRAINT",
+        "PRIMARY",
+        "UNIQUE",
+        "CHECK",
+        "EXCLUDE",
+        "FOREIGN",
+        "LIKE",
+    ])
+}
+
+struct ForeignKey {
+    columns: Vec<String>,
+    reference: Reference,
+}
+
+impl ForeignKey {
+    fn apply(self, table: &mut Table) {
+        for name in self.columns {
+            if let Some(column) = table.column_mut(&name) {
+                column.references = Some(self.reference.clone());
+            }
+        }
+    }
+}
+
+/// A `[CONSTRAINT name] FOREIGN KEY (columns) REFERENCES table [(column)]`
+/// element; other constraints are `None`.
+fn table_constraint(tokens: &[Token]) -> Option<ForeignKey> {
+    let parser = &mut Parser {
+        tokens,
+        position: 0,
+    };
+    if parser.keyword("CONSTRAINT") {
+        parser.identifier()?;
+    }
+    if !parser.keywords(&["FOREIGN", "KEY"]) {
+        return None;
+    }
+    let columns = split_commas(parser.group()?)
+        .filter_map(|x| Parser::new(x).identifier())
+        .collect();
+    parser.keyword("REFERENCES").then_some(())?;
+    Some(ForeignKey {
+        columns,
+        reference: reference(parser)?,
+    })
+}
+
+/// A column definition: name, type and constraints.
+fn column(tokens: &[Token]) -> Option<Column> {
+    let parser = &mut Parser::new(tokens);
+    let name = parser.identifier()?;
+    let rest = &tokens[parser.position..];
+    let mut references = None;
+    let mut parser = Parser::new(rest);
+    while parser.position < rest.len() {
+        if parser.keyword("REFERENCES") {
+            references = reference(&mut parser);
+        } else {
+            parser.position += 1;
+        }
+    }
+    Some(Column {
+        name,
+        data_type: data_type(rest),
+        references,
+    })
+}
+
+fn reference(parser: &mut Parser) -> Option<Reference> {
+    let table = parser.name()?;
+    let column = parser
+        .group()
+        .and_then(|x| split_commas(x).next())
+        .and_then(|x| Parser::new(x).identifier());
+    Some(Reference { table, column })
+}
+
+/// The type at the start of a column definition, up to the first constraint.
+fn data_type(tokens: &[Token]) -> String {
+    const CONSTRAINTS: &[&str] = &[
+        "NOT",
+        "NULL",
+        "DEFAULT",
+        "PRIMARY",
+        "REFERENCES",
+        "UNIQUE",
+        "CHECK",
+        "CONSTRAINT",
+        "GENERATED",
+        "COLLATE",
+        "USING",
+    ];
+    let mut data_type = String::new();
+    let mut depth = 0;
+    for token in tokens {
+        match token {
+            Token::Word(word, false)
+                if depth == 0 && CONSTRAINTS.iter().any(|x| word.eq_ignore_ascii_case(x)) =>
+            {
+                break
+            }
+            Token::Word(word, _) => {
+                if !data_type.is_empty() && !data_type.ends_with('(') {
+                    data_type.push(' ');
+                }
+                data_type.push_str(&word.to_lowercase());
+            }
+            Token::Symbol(symbol) => {
+                match symbol {
+                    '(' => depth += 1,
+                    ')' => depth -= 1,
+                    _ => {}
+                }
+                data_type.push(*symbol);
+            }
+            Token::Literal => {}
+        }
+    }
+    data_type
+}
+
+/// Splits `tokens` at the commas outside parentheses.
+fn split_commas(tokens: &[Token]) -> impl Iterator<Item = &[Token]> {
+    let mut depth = 0;
+    tokens
+        .split(move |x| match x {
+            Token::Symbol('(') => {
+                depth += 1;
+                false
+            }
+            Token::Symbol(')') => {
+                depth -= 1;
+                false
+            }
+            Token::Symbol(',') => depth == 0,
+            _ => false,
+        })
+        .filter(|x| !x.is_empty())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    /// A keyword or identifier, and whether it was quoted.
+    Word(String, bool),
+    Symbol(char),
+    /// A string or dollar-quoted body, whose content never matters here.
+    Literal,
+}
+
+fn tokenize(sql: &str) -> Vec<Token> {
+    let mut tokens = Vec::new();
+    let mut chars = sql.char_indices().peekable();
+    while let Some((start, c)) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '-' if sql[start..].starts_with("--") => {
+                while chars.next_if(|x| x.1 != '\n').is_some() {}
+            }
+            '/' if sql[start..].starts_with("/*") => {
+                chars.next();
+                while let Some((position, _)) = chars.next() {
+                    if sql[position..].starts_with("*/") {
+                        chars.next();
+                        break;
+                    }
+                }
+            }
+            '\'' => {
+                // A doubled quote is an escaped one, which the loop reads as
+                // two strings in a row.
+                while chars.next_if(|x| x.1 != '\'').is_some() {}
+                chars.next();
+                tokens.push(Token::Literal);
+            }
+            // MySQL quotes with backticks and SQL Server with brackets, which
+            // are only told apart from an array type by what they hold.
+            '"' | '`' | '[' if c != '[' || sql[start + 1..].starts_with(is_identifier_start) => {
+                let end = match c {
+                    '[' => ']',
+                    c => c,
+                };
+                let mut word = String::new();
+                while let Some((_, c)) = chars.next_if(|x| x.1 != end) {
+                    word.push(c);
+                }
+                chars.next();
+                tokens.push(Token::Word(word, true));
+            }
+            '$' => {
+                let tag_end = sql[start + 1..]
+                    .find(|x: char| !(x.is_alphanumeric() || x == '_'))
+                    .map(|x| start + 1 + x);
+                match tag_end.filter(|x| sql[*x..].starts_with('$')) {
+                    Some(tag_end) => {
+                        let tag = &sql[start..=tag_end];
+                        let body = tag_end + 1;
+                        let end = sql[body..]
+                            .find(tag)
+                            .map_or(sql.len(), |x| body + x + tag.len());
+                        while chars.next_if(|x| x.0 < end).is_some() {}
+                        tokens.push(Token::Literal);
+                    }
+                    None => tokens.push(Token::Symbol('$')),
+                }
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut word = c.to_string();
+                while let Some((_, c)) = chars.next_if(|x| x.1.is_alphanumeric() || x.1 == '_') {
+                    word.push(c);
+                }
+                tokens.push(Token::Word(word, false));
+            }
+            c => tokens.push(Token::Symbol(c)),
+        }
+    }
+    tokens
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_alphabetic() || c == '_'
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(tokens: &'a [Token]) -> Self {
+        Parser {
+            tokens,
+            position: 0,
+        }
+    }
+
+    fn peek_any_keyword(&self, keywords: &[&str]) -> bool {
+        matches!(
+            self.tokens.get(self.position),
+            Some(Token::Word(word, false)) if keywords.iter().any(|x| word.eq_ignore_ascii_case(x))
+        )
+    }
+
+    fn any_keyword(&mut self, keywords: &[&str]) -> bool {
+        let found = self.peek_any_keyword(keywords);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        self.any_keyword(&[keyword])
+    }
+
+    /// Consumes `keywords` if they all follow, and nothing otherwise.
+    fn keywords(&mut self, keywords: &[&str]) -> bool {
+        let start = self.position;
+        if keywords.iter().all(|x| self.keyword(x)) {
+            return true;
+        }
+        self.position = start;
+        false
+    }
+
+    fn symbol(&mut self, symbol: char) -> bool {
+        let found = self.tokens.get(self.position) == Some(&Token::Symbol(symbol));
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    /// An identifier, folded to lower case unless quoted, like PostgreSQL
+    /// does.
+    fn identifier(&mut self) -> Option<String> {
+        let Some(Token::Word(word, quoted)) = self.tokens.get(self.position) else {
+            return None;
+        };
+        self.position += 1;
+        Some(match quoted {
+            true => word.clone(),
+            false => word.to_lowercase(),
+        })
+    }
+
+    /// A possibly schema qualified name, e.g. `app.users`.
+    fn name(&mut self) -> Option<String> {
+        let mut name = self.identifier()?;
+        while self.symbol('.') {
+            name.push('.');
+            name.push_str(&self.identifier()?);
+        }
+        Some(name)
+    }
+
+    /// The tokens inside the parentheses that follow, if they do.
+    fn group(&mut self) -> Option<&'a [Token]> {
+        if self.tokens.get(self.position) != Some(&Token::Symbol('(')) {
+            return None;
+        }
+        let start = self.position + 1;
+        let mut depth = 0;
+        for (position, token) in self.tokens.iter().enumerate().skip(self.position) {
+            match token {
+                Token::Symbol('(') => depth += 1,
+                Token::Symbol(')') => {
+                    depth -= 1;
+                    if depth == 0 {
+                        self.position = position + 1;
+                        return Some(&self.tokens[start..position]);
+                    }
+                }
+                _ => {}
+            }
+        }
+        None
+    }
+
+    fn rest(&self) -> Option<&'a [Token]> {
+        Some(&self.tokens[self.position..]).filter(|x| !x.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn model(sql: &str) -> SchemaModel {
+        let mut model = SchemaModel::default();
+        model.apply(sql);
+        model
+    }
+
+    fn columns(model: &SchemaModel, table: &str) -> Vec<(String, String)> {
+        model.tables[table]
+            .columns
+            .iter()
+            .map(|x| (x.name.clone(), x.data_type.clone()))
+            .collect()
+    }
+
+    fn pairs(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(x, y)| (x.to_string(), y.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn create_table_reads_columns_and_skips_constraints() {
+        let model = model(
+            "-- users\nCREATE TABLE IF NOT EXISTS app.Users(\n    id bigint PRIMARY KEY,\n    \
+             \"Name\" varchar(255) NOT NULL DEFAULT 'a, b',\n    total numeric(10, 2),\n    \
+             CONSTRAINT users_name UNIQUE (\"Name\")\n);",
+        );
+        assert_eq!(
+            columns(&model, "app.users"),
+            pairs(&[
+                ("id", "bigint"),
+                ("Name", "varchar(255)"),
+                ("total", "numeric(10, 2)")
+            ])
+        );
+    }
+
+    #[test]
+    fn alter_table_adds_drops_renames_and_retypes() {
+        let mut model = model("create table users (id int, email text);");
+        model.apply(
+            "alter table users add column if not exists age int, drop column email;\n\
+             ALTER TABLE users RENAME COLUMN age TO years;\n\
+             ALTER TABLE users ALTER COLUMN years TYPE bigint;\n\
+             ALTER TABLE users RENAME TO people;",
+        );
+        assert!(!model.tables.contains_key("users"));
+        assert_eq!(
+            columns(&model, "people"),
+            pairs(&[("id", "int"), ("years", "bigint")])
+        );
+        model.apply("DROP TABLE IF EXISTS people, other CASCADE;");
+        assert!(model.tables.is_empty());
+    }
+
+    #[test]
+    fn foreign_keys_are_recorded_inline_and_as_constraints() {
+        let mut model = model(
+            "create table users (id int primary key);\n\
+             create table orders (id int, user_id int references users, \
+             buyer int, foreign key (buyer) references users (id));",
+        );
+        model.apply("alter table orders add constraint fk foreign key (id) references app.x(y);");
+        let orders = &model.tables["orders"];
+        let reference = |column| orders.column(column).unwrap().references.clone();
+        assert_eq!(
+            reference("user_id"),
+            Some(Reference {
+                table: "users".to_owned(),
+                column: None
+            })
+        );
+        assert_eq!(
+            reference("buyer"),
+            Some(Reference {
+                table: "users".to_owned(),
+                column: Some("id".to_owned())
+            })
+        );
+        assert_eq!(reference("id").unwrap().table, "app.x");
+    }
+
+    #[test]
+    fn dialects_quote_identifiers_their_own_way() {
+        let model = model(
+            "CREATE TABLE IF NOT EXISTS `app`.`Users` (id BIGINT, tags text[]);\n\
+             IF OBJECT_ID(N'orders', N'U') IS NULL\nCREATE TABLE [Orders] ([Id] BIGINT);",
+        );
+        assert_eq!(
+            columns(&model, "app.Users"),
+            pairs(&[("id", "bigint"), ("tags", "text[]")])
+        );
+        assert_eq!(columns(&model, "Orders"), pairs(&[("Id", "bigint")]));
+    }
+
+    #[test]
+    fn strings_and_dollar_quoted_bodies_are_not_parsed() {
+        let model = model(
+            "DO $body$ BEGIN CREATE TABLE hidden (id int); END $body$;\n\
+             INSERT INTO t VALUES ('create table nope (x int);');\n\
+             /* create table also_hidden (x int); */\n\
+             create table kept (x int);",
+        );
+        assert_eq!(model.tables.keys().collect::<Vec<_>>(), ["kept"]);
+    }
+}
@@ -0,0 +1,193 @@
+//! The migrations under a root whose names or contents match a pattern, for
+//! `grep`.
+
+use crate::index::MigrationIndex;
+use crate::manifest::relative_path;
+use crate::naming::parse_file_name;
+use crate::operation::Operation;
+use chrono::NaiveDate;
+use regex::{Regex, RegexBuilder};
+use serde::Serialize;
+use std::fs;
+use std::path::Path;
+
+/// A match as `grep --json` prints it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Match {
+    /// The path of the migration relative to the root, with forward
+    /// slashes.
+    pub path: String,
+    pub date: NaiveDate,
+    pub index: i32,
+    /// The line matching, counted from 1, or `None` when the name matches.
+    pub line: Option<usize>,
+    /// The line matching, or the description when the name does.
+    pub text: String,
+}
+
+/// What `grep` looks for.
+#[derive(Debug, Clone)]
+pub struct Query {
+    pattern: Regex,
+    /// The table and column a `table.column` pattern names. The migrations
+    /// whose names say they are about the table also match where they name
+    /// the column, as `create table users` does `email text` for
+    /// `users.email`.
+    column: Option<(String, Regex)>,
+}
+
+impl Query {
+    /// Looks for `pattern` as it is written, or as a regular expression
+    /// with `regex`.
+    pub fn new(pattern: &str, regex: bool, ignore_case: bool) -> Result<Query, regex::Error> {
+        let build = |pattern: &str| {
+            RegexBuilder::new(pattern)
+                .case_insensitive(ignore_case)
+                .build()
+        };
+        let column = match pattern.split_once('.') {
+            Some((table, column)) if !regex && is_word(table) && is_word(column) => Some((
+                table.to_owned(),
+                build(&format!(r"\b{}\b", regex::escape(column)))?,
+            )),
+            _ => None,
+        };
+        let pattern = match regex {
+            true => build(pattern)?,
+            false => build(&regex::escape(pattern))?,
+        };
+        Ok(Query { pattern, column })
+    }
+
+    /// The column pattern to also look for in the migration described
+    /// `description`, if it is about the query's table.
+    fn column_in(&self, description: &str) -> Option<&Regex> {
+        let (table, column) = self.column.as_ref()?;
+        let (_, about, _) = Operation::from_file_name(description);
+        about
+            .is_some_and(|x| x.eq_ignore_ascii_case(table))
+            .then_some(column)
+    }
+}
+
+fn is_word(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|x| x.is_alphanumeric() || x == '_')
+}
+
+/// Every match of `query` in the migrations of `index`, by date, index and
+/// then line, a migration's name first. Files that do not follow the naming
+/// scheme are left out, and so are the lines of those that are not UTF-8.
+pub fn search(root: &Path, index: &MigrationIndex, query: &Query) -> Vec<Match> {
+    let mut migrations = index
+        .files()
+        .filter_map(|path| {
+            let name = parse_file_name(path.file_name()?.to_str()?)?;
+            Some((name.date, name.index, path, name.description.to_owned()))
+        })
+        .collect::<Vec<_>>();
+    migrations.sort();
+
+    let mut matches = Vec::new();
+    for (date, index, path, description) in migrations {
+        let column = query.column_in(&description);
+        let is_match =
+            |text: &str| query.pattern.is_match(text) || column.is_some_and(|x| x.is_match(text));
+        let found = |line, text: &str| Match {
+            path: relative_path(root, path),
+            date,
+            index,
+            line,
+            text: text.to_owned(),
+        };
+        if is_match(&description) {
+            matches.push(found(None, &description));
+        }
+        let Ok(content) = fs::read_to_string(path) else {
+            continue;
+        };
+        for (number, line) in content.lines().enumerate() {
+            if is_match(line) {
+                matches.push(found(Some(number + 1), line));
+            }
+        }
+    }
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patterns_are_literal_unless_regex() {
+        let query = Query::new("a.b", false, false).unwrap();
+        assert!(query.pattern.is_match("select a.b"));
+        assert!(!query.pattern.is_match("select axb"));
+        let query = Query::new("a.b", true, false).unwrap();
+        assert!(query.pattern.is_match("select axb"));
+        assert!(Query::new("(", true, false).is_err());
+    }
+
+    #[test]
+    fn case_is_ignored_on_request() {
+        assert!(!Query::new("users", false, false)
+            .unwrap()
+            .pattern
+            .is_match("USERS"));
+        assert!(Query::new("users", false, true)
+            .unwrap()
+            .pattern
+            .is_match("USERS"));
+    }
+
+    #[test]
+    fn columns_are_looked_for_in_migrations_about_their_table() {
+        let query = Query::new("users.email", false, false).unwrap();
+        let column = query.column_in("create table users").unwrap();
+        assert!(column.is_match("    email text not null,"));
+        assert!(!column.is_match("    email_verified boolean,"));
+        assert!(query.column_in("create table orders").is_none());
+        assert!(query.column_in("backfill emails").is_none());
+        let query = Query::new("users.email", true, false).unwrap();
+        assert!(query.column_in("create table users").is_none());
+    }
+
+    #[test]
+    fn search_finds_names_then_lines() {
+        let root = std::env::temp_dir().join(format!("thing-search-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let files = [
+            (
+                "2024061202 - add column email to users.sql",
+                "ALTER TABLE users ADD email text;\n",
+            ),
+            (
+                "2024061201 - create table users.sql",
+                "CREATE TABLE users (\n    email text\n);\n",
+            ),
+            (
+                "2024061203 - create table orders.sql",
+                "CREATE TABLE orders ();\n",
+            ),
+        ];
+        for (name, content) in files {
+            fs::write(root.join(name), content).unwrap();
+        }
+        let index = MigrationIndex::scan(&root);
+        let query = Query::new("users.email", false, false).unwrap();
+        let found = search(&root, &index, &query)
+            .into_iter()
+            .map(|x| (x.index, x.line, x.text))
+            .collect::<Vec<_>>();
+        fs::remove_dir_all(&root).unwrap();
+        assert_eq!(
+            found,
+            [
+                (1, Some(2), "    email text".to_owned()),
+                (2, None, "add column email to users".to_owned()),
+                (2, Some(1), "ALTER TABLE users ADD email text;".to_owned()),
+            ]
+        );
+    }
+}
@@ -0,0 +1,168 @@
+//! Rows of a CSV file as the SQL literals `seed` inserts. The header names
+//! the columns; an empty field is `NULL` unless it is quoted.
+
+use crate::dialect::Dialect;
+use crate::error::GenError;
+use serde::Serialize;
+
+/// The most rows SQL Server takes in one `INSERT ... VALUES`.
+const MSSQL_MAX_ROWS: usize = 1000;
+
+/// The columns and rows of a CSV file, the rows in batches of one
+/// `INSERT` statement each.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Seed {
+    pub columns: Vec<String>,
+    pub batches: Vec<Vec<Vec<String>>>,
+}
+
+impl Seed {
+    /// Reads `csv` into literals of `dialect`, at most `batch_size` rows to
+    /// a statement when given.
+    pub fn parse(dialect: Dialect, csv: &str, batch_size: Option<usize>) -> Result<Seed, GenError> {
+        let mut records = parse_csv(csv)?.into_iter();
+        let columns = records
+            .next()
+            .ok_or_else(|| GenError::MalformedCsv("no header".to_owned()))?
+            .into_iter()
+            .map(Option::unwrap_or_default)
+            .collect::<Vec<_>>();
+        let mut rows = Vec::new();
+        for (i, record) in records.enumerate() {
+            if record.len() != columns.len() {
+                return Err(GenError::MalformedCsv(format!(
+                    "row {} has {} fields, the header {}",
+                    i + 1,
+                    record.len(),
+                    columns.len()
+                )));
+            }
+            rows.push(
+                record
+                    .iter()
+                    .map(|x| literal(dialect, x.as_deref()))
+                    .collect(),
+            );
+        }
+        let mut batch_size = batch_size.unwrap_or(usize::MAX).max(1);
+        if dialect == Dialect::Mssql {
+            batch_size = batch_size.min(MSSQL_MAX_ROWS);
+        }
+        let batches = rows.chunks(batch_size).map(<[_]>::to_vec).collect();
+        Ok(Seed { columns, batches })
+    }
+}
+
+/// `value` as a string literal of `dialect`, or `NULL`.
+pub(crate) fn literal(dialect: Dialect, value: Option<&str>) -> String {
+    let Some(value) = value else {
+        return "NULL".to_owned();
+    };
+    let mut value = value.replace('\'', "''");
+    // MySQL reads backslashes in strings as escapes.
+    if dialect == Dialect::Mysql {
+        value = value.replace('\\', "\\\\");
+    }
+    format!("'{value}'")
+}
+
+/// The records of `csv`, with `None` for empty fields that are not quoted.
+/// Quoted fields can hold commas, line breaks and quotes doubled.
+fn parse_csv(csv: &str) -> Result<Vec<Vec<Option<String>>>, GenError> {
+    let csv = csv.strip_prefix('\u{feff}').unwrap_or(csv);
+    let mut records = Vec::new();
+    let mut record = Vec::new();
+    let mut field = String::new();
+    let (mut quoted, mut in_quotes) = (false, false);
+    let mut chars = csv.chars().peekable();
+    let mut line = 1;
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes && chars.peek() == Some(&'"') => {
+                chars.next();
+                field.push('"');
+            }
+            '"' if in_quotes => in_quotes = false,
+            '"' if field.is_empty() && !quoted => (quoted, in_quotes) = (true, true),
+            '"' => {
+                return Err(GenError::MalformedCsv(format!(
+                    "stray quote on line {line}"
+                )));
+            }
+            _ if in_quotes => {
+                line += usize::from(c == '\n');
+                field.push(c);
+            }
+            ',' => record.push(take(&mut field, &mut quoted)),
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' => {
+                line += 1;
+                record.push(take(&mut field, &mut quoted));
+                // Blank lines separate nothing.
+                if record.len() > 1 || record[0].is_some() {
+                    records.push(std::mem::take(&mut record));
+                }
+                record.clear();
+            }
+            _ => field.push(c),
+        }
+    }
+    if in_quotes {
+        return Err(GenError::MalformedCsv("unterminated quote".to_owned()));
+    }
+    if !field.is_empty() || quoted || !record.is_empty() {
+        record.push(take(&mut field, &mut quoted));
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// The field read so far, which starts the next one.
+fn take(field: &mut String, quoted: &mut bool) -> Option<String> {
+    let value = std::mem::take(field);
+    let is_null = value.is_empty() && !*quoted;
+    *quoted = false;
+    (!is_null).then_some(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn csv_fields_can_be_quoted() {
+        assert_eq!(
+            parse_csv("code,name\r\nie,\"Ireland, \"\"the\"\"\nisle\"\n\nxx,\n,\"\"").unwrap(),
+            [
+                vec![Some("code".to_owned()), Some("name".to_owned())],
+                vec![
+                    Some("ie".to_owned()),
+                    Some("Ireland, \"the\"\nisle".to_owned())
+                ],
+                vec![Some("xx".to_owned()), None],
+                vec![None, Some(String::new())],
+            ]
+        );
+        assert!(matches!(parse_csv("a\"b"), Err(GenError::MalformedCsv(_))));
+        assert!(matches!(parse_csv("\"a"), Err(GenError::MalformedCsv(_))));
+    }
+
+    #[test]
+    fn rows_become_literals_in_batches() {
+        let seed = Seed::parse(Dialect::Mysql, "code,name\nie,O'Neil\\\nfr,\n", Some(1)).unwrap();
+        assert_eq!(seed.columns, ["code", "name"]);
+        assert_eq!(
+            seed.batches,
+            [
+                vec![vec!["'ie'".to_owned(), "'O''Neil\\\\'".to_owned()]],
+                vec![vec!["'fr'".to_owned(), "NULL".to_owned()]],
+            ]
+        );
+        let seed = Seed::parse(Dialect::Postgres, "a\n1\n2\n3", None).unwrap();
+        assert_eq!(seed.batches.len(), 1);
+        assert!(matches!(
+            Seed::parse(Dialect::Postgres, "a,b\n1", None),
+            Err(GenError::MalformedCsv(x)) if x == "row 1 has 1 fields, the header 2"
+        ));
+    }
+}
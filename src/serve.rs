@@ -0,0 +1,182 @@
+//! Line-delimited JSON-RPC 2.0 over a unix socket, answered from an index that
+//! is kept warm by the same watcher as `watch`.
+
+use crate::watch::{keep_current, next_prefix, today, Clock, Scope};
+use log::{info, warn};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::{mpsc, Arc, Mutex};
+use thing::config::Config;
+use thing::index::MigrationIndex;
+use thing::verify::verify;
+use thing::{Generator, Operation};
+
+const PARSE_ERROR: i64 = -32700;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+const GENERATION_FAILED: i64 = -32000;
+
+#[derive(Deserialize)]
+struct Request {
+    #[serde(default)]
+    id: Value,
+    method: String,
+    #[serde(default)]
+    params: Value,
+}
+
+#[derive(Deserialize)]
+struct GenerateParams {
+    operation: Operation,
+    name: String,
+    column: Option<String>,
+    schema: Option<String>,
+    service: Option<String>,
+    /// Relative to the root; defaults to its configured output directory.
+    output_dir: Option<PathBuf>,
+}
+
+struct Error {
+    code: i64,
+    message: String,
+}
+
+impl Error {
+    fn new(code: i64, message: impl ToString) -> Self {
+        Error {
+            code,
+            message: message.to_string(),
+        }
+    }
+}
+
+pub fn serve(root: &Path, socket: &Path, clock: Clock) -> anyhow::Result<()> {
+    let index = Arc::new(Mutex::new(MigrationIndex::scan(root)));
+    let (tx, _rx) = mpsc::channel();
+    let _watcher = keep_current(root, Arc::clone(&index), tx)?;
+
+    if socket.exists() {
+        std::fs::remove_file(socket)?;
+    }
+    let listener = UnixListener::bind(socket)?;
+    info!("listening on {:?}", socket);
+
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let root = root.to_path_buf();
+        let index = Arc::clone(&index);
+        std::thread::spawn(move || {
+            if let Err(error) = connection(stream, &root, &index, clock) {
+                warn!("connection error: {error}");
+            }
+        });
+    }
+    Ok(())
+}
+
+fn connection(
+    stream: UnixStream,
+    root: &Path,
+    index: &Mutex<MigrationIndex>,
+    clock: Clock,
+) -> std::io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    for line in BufReader::new(stream).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let response = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => {
+                let result = handle(&request, root, index, clock);
+                response(request.id, result)
+            }
+            Err(error) => response(Value::Null, Err(Error::new(PARSE_ERROR, error))),
+        };
+        writeln!(writer, "{response}")?;
+    }
+    Ok(())
+}
+
+fn response(id: Value, result: Result<Value, Error>) -> Value {
+    match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(error) => json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "error": { "code": error.code, "message": error.message },
+        }),
+    }
+}
+
+fn handle(
+    request: &Request,
+    root: &Path,
+    index: &Mutex<MigrationIndex>,
+    clock: Clock,
+) -> Result<Value, Error> {
+    match request.method.as_str() {
+        "next" => {
+            let scope = match &request.params {
+                Value::Null => Scope::default(),
+                params => serde_json::from_value::<Scope>(params.clone())
+                    .map_err(|x| Error::new(INVALID_PARAMS, x))?,
+            };
+            let index = index.lock().unwrap();
+            let prefix = next_prefix(&index, today(clock), &scope)
+                .map_err(|x| Error::new(GENERATION_FAILED, x))?;
+            // `null` with migrate names, which have no index.
+            Ok(json!(prefix))
+        }
+        "list" => {
+            let index = index.lock().unwrap();
+            let mut files = index
+                .files()
+                .map(|x| x.strip_prefix(root).unwrap_or(x))
+                .collect::<Vec<_>>();
+            files.sort_by_key(|x| x.file_name());
+            Ok(json!(files))
+        }
+        "generate" => {
+            let params = serde_json::from_value::<GenerateParams>(request.params.clone())
+                .map_err(|x| Error::new(INVALID_PARAMS, x))?;
+            let mut generator = Generator::new(root)
+                .operation(params.operation)
+                .name(params.name)
+                .today(today(clock))
+                .timezone(clock.timezone);
+            if let Some(column) = params.column {
+                generator = generator.column(column);
+            }
+            if let Some(schema) = params.schema {
+                generator = generator.schema(schema);
+            }
+            if let Some(service) = params.service {
+                generator = generator.service(service);
+            }
+            if let Some(output_dir) = params.output_dir {
+                generator = generator.output_dir(root.join(output_dir));
+            }
+            let path = generator
+                .generate_in(&mut index.lock().unwrap())
+                .map_err(|x| Error::new(GENERATION_FAILED, x))?;
+            Ok(json!({ "path": path }))
+        }
+        "lint" => {
+            let config = Config::load(root).map_err(|x| Error::new(GENERATION_FAILED, x))?;
+            let index = index.lock().unwrap();
+            let problems = verify(root, &config, &index, today(clock))
+                .iter()
+                .map(|x| json!({ "rule": x.rule().name(), "message": x.to_string() }))
+                .collect::<Vec<_>>();
+            Ok(json!(problems))
+        }
+        method => Err(Error::new(
+            METHOD_NOT_FOUND,
+            format!("unknown method {method:?}"),
+        )),
+    }
+}
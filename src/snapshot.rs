@@ -0,0 +1,221 @@
+//! Golden files of what every operation renders, so a change to a template
+//! shows up as a difference to review instead of in the next migration.
+
+use crate::dialect::Dialect;
+use crate::error::GenError;
+use crate::language::Language;
+use crate::operation::{compute_filename_in, render_operation_with, Extensions};
+#[cfg(feature = "plugins")]
+use crate::plugin::Plugins;
+use crate::Operation;
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Directory under the root that snapshots are kept in.
+pub const SNAPSHOTS_DIR: &str = "__snapshots__";
+
+const EXTENSION: &str = "snap";
+
+/// The canned migration every operation is rendered for.
+const NAME: &str = "users";
+const COLUMN: &str = "email";
+const SCHEMA: &str = "app";
+const INDEX: i32 = 1;
+
+fn day() -> NaiveDate {
+    NaiveDate::from_ymd_opt(2024, 6, 12).unwrap()
+}
+
+/// What one operation rendered: its file name, a blank line and its body.
+#[derive(Debug)]
+pub struct Snapshot {
+    /// The operation, followed by the language's extension for built-in
+    /// ones, e.g. `create-table.sql`, and by the dialect before it for SQL
+    /// other than PostgreSQL, e.g. `create-table.mysql.sql`.
+    pub name: String,
+    pub content: String,
+}
+
+impl Snapshot {
+    fn new(name: String, file_name: &str, body: Option<String>) -> Self {
+        Snapshot {
+            name,
+            content: format!("{file_name}\n\n{}", body.unwrap_or_default()),
+        }
+    }
+
+    fn file_name(&self) -> String {
+        format!("{}.{EXTENSION}", self.name)
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Difference {
+    /// An operation without a snapshot yet.
+    Missing(String),
+    /// An operation that renders differently, and the first line that does.
+    Changed(String, usize),
+    /// A snapshot of an operation that no longer exists.
+    Stale(String),
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Difference::Missing(operation) => write!(f, "no snapshot of {operation}"),
+            Difference::Changed(operation, line) => {
+                write!(f, "{operation} renders differently from line {line}")
+            }
+            Difference::Stale(name) => write!(f, "snapshot of unknown operation {name}"),
+        }
+    }
+}
+
+/// The tokens the generator fills in from the environment, fixed so the
+/// snapshots do not depend on who renders them.
+fn tokens() -> Extensions {
+    let mut extensions = Extensions::default();
+    extensions.tokens.extend([
+        ("branch".to_owned(), "main".to_owned()),
+        ("author".to_owned(), "Gen <gen@example.com>".to_owned()),
+    ]);
+    extensions
+}
+
+/// Renders every built-in operation.
+pub fn render() -> Result<Vec<Snapshot>, GenError> {
+    render_builtin(|_| Ok(tokens()))
+}
+
+/// Renders every built-in operation with the plugins' tokens and helpers,
+/// followed by the plugins' own operations.
+#[cfg(feature = "plugins")]
+pub fn render_with(plugins: &Plugins) -> Result<Vec<Snapshot>, GenError> {
+    let extensions = |operation: &str| {
+        let mut extensions = plugins.extensions(operation, NAME, Some(SCHEMA), Some(COLUMN))?;
+        // Also over plugins' own idea of these.
+        extensions.extend(tokens());
+        Ok(extensions)
+    };
+    let mut snapshots = render_builtin(extensions)?;
+    for operation in plugins.iter().flat_map(|x| x.operations()) {
+        let (file_name, body) = plugins.render(
+            &operation.name,
+            NAME,
+            Some(SCHEMA),
+            Some(COLUMN),
+            day(),
+            INDEX,
+            &extensions(&operation.name)?,
+        )?;
+        snapshots.push(Snapshot::new(operation.name.clone(), &file_name, body));
+    }
+    Ok(snapshots)
+}
+
+fn render_builtin(
+    extensions: impl Fn(&str) -> Result<Extensions, GenError>,
+) -> Result<Vec<Snapshot>, GenError> {
+    let mut snapshots = Vec::new();
+    let sql = Dialect::ALL.map(|x| (Language::Sql, x));
+    let others = Language::ALL
+        .into_iter()
+        .filter(|x| *x != Language::Sql)
+        .map(|x| (x, Dialect::default()));
+    for (language, dialect) in sql.into_iter().chain(others) {
+        for operation in Operation::value_variants() {
+            let name = operation.name();
+            let file_name =
+                compute_filename_in(language, operation, NAME, Some(COLUMN), day(), INDEX)?;
+            let body = render_operation_with(
+                language,
+                dialect,
+                operation,
+                NAME,
+                Some(SCHEMA),
+                Some(COLUMN),
+                &extensions(&name)?,
+            )?;
+            let name = match dialect {
+                Dialect::Postgres => format!("{name}.{}", language.extension()),
+                dialect => format!("{name}.{}.{}", dialect.name(), language.extension()),
+            };
+            snapshots.push(Snapshot::new(name, &file_name, body));
+        }
+    }
+    Ok(snapshots)
+}
+
+/// Writes `snapshots` into the root's snapshot directory, removing the
+/// snapshots of operations that no longer exist.
+pub fn write(root: &Path, snapshots: &[Snapshot]) -> Result<PathBuf, GenError> {
+    let directory = root.join(SNAPSHOTS_DIR);
+    fs::create_dir_all(&directory)?;
+    let current = snapshots
+        .iter()
+        .map(Snapshot::file_name)
+        .collect::<BTreeSet<_>>();
+    for path in stored(&directory)? {
+        if !current.contains(path.file_name().unwrap().to_str().unwrap_or_default()) {
+            fs::remove_file(path)?;
+        }
+    }
+    for snapshot in snapshots {
+        fs::write(directory.join(snapshot.file_name()), &snapshot.content)?;
+    }
+    Ok(directory)
+}
+
+/// Compares `snapshots` with the ones stored under the root.
+pub fn check(root: &Path, snapshots: &[Snapshot]) -> Result<Vec<Difference>, GenError> {
+    let directory = root.join(SNAPSHOTS_DIR);
+    let mut differences = Vec::new();
+    for snapshot in snapshots {
+        let path = directory.join(snapshot.file_name());
+        if !path.exists() {
+            differences.push(Difference::Missing(snapshot.name.clone()));
+            continue;
+        }
+        let stored = fs::read_to_string(path)?;
+        if stored != snapshot.content {
+            let line = stored
+                .lines()
+                .zip(snapshot.content.lines())
+                .take_while(|(x, y)| x == y)
+                .count();
+            differences.push(Difference::Changed(snapshot.name.clone(), line + 1));
+        }
+    }
+    let current = snapshots
+        .iter()
+        .map(Snapshot::file_name)
+        .collect::<BTreeSet<_>>();
+    for path in stored(&directory)? {
+        let file_name = path.file_name().unwrap().to_string_lossy();
+        if !current.contains(file_name.as_ref()) {
+            let name = file_name.trim_end_matches(&format!(".{EXTENSION}"));
+            differences.push(Difference::Stale(name.to_owned()));
+        }
+    }
+    Ok(differences)
+}
+
+/// The snapshot files in `directory`, which may not exist yet.
+fn stored(directory: &Path) -> Result<Vec<PathBuf>, GenError> {
+    if !directory.is_dir() {
+        return Ok(Vec::new());
+    }
+    let mut paths = Vec::new();
+    for entry in fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.extension().is_some_and(|x| x == EXTENSION) {
+            paths.push(path);
+        }
+    }
+    paths.sort();
+    Ok(paths)
+}
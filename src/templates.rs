@@ -0,0 +1,261 @@
+//! The templates operations are rendered with: the root's own in
+//! `templates/`, those of its template pack, or the built-in ones.
+
+use crate::config::{Config, TEMPLATES_DIR};
+use crate::dialect::Dialect;
+use crate::error::GenError;
+use crate::git;
+use crate::language::Language;
+use crate::Operation;
+use std::ffi::OsStr;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+
+/// Directory under the root that git template packs are cloned into, one
+/// directory per URL.
+pub const PACKS_DIR: &str = ".gen_packs";
+
+/// Where a template comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// The root's own file.
+    Root(PathBuf),
+    /// A file of the root's template pack.
+    Pack(PathBuf),
+    BuiltIn,
+}
+
+impl Source {
+    /// The file the template is in, unless it is built in.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Source::Root(path) | Source::Pack(path) => Some(path),
+            Source::BuiltIn => None,
+        }
+    }
+}
+
+/// Which template of an operation: in what language and dialect, and the
+/// forward migration's or the rollback's.
+#[derive(Debug, Clone, Copy)]
+pub struct Kind {
+    pub language: Language,
+    pub dialect: Dialect,
+    pub down: bool,
+}
+
+impl Kind {
+    /// Where the root's own template of `operation` is, whether it has one
+    /// or not, e.g. `templates/mysql/down/create_table.tmpl`.
+    pub fn path(self, root: &Path, operation: &Operation) -> PathBuf {
+        self.path_in(&root.join(TEMPLATES_DIR), operation)
+    }
+
+    /// Where the template of `operation` is in the templates directory
+    /// `templates`, laid out like the root's.
+    fn path_in(self, templates: &Path, operation: &Operation) -> PathBuf {
+        let mut path = templates.join(self.language.template_directory(self.dialect));
+        if self.down {
+            path.push("down");
+        }
+        path.join(operation.template_file_name())
+    }
+
+    fn built_in(self, operation: &Operation) -> Option<&'static str> {
+        match self.down {
+            true => self.language.down_template(self.dialect, operation),
+            false => self.language.template(self.dialect, operation),
+        }
+    }
+
+    /// What templates of this kind are for, e.g. `mysql` or `cql`.
+    fn name(self) -> &'static str {
+        match self.language {
+            Language::Sql => self.dialect.name(),
+            language => language.extension(),
+        }
+    }
+}
+
+/// The directories templates are looked up in, in order: the root's own
+/// and then those of its template pack, `pack` as [`pack`] returns it.
+pub fn directories(root: &Path, pack: Option<&Path>) -> Vec<PathBuf> {
+    let mut directories = vec![root.join(TEMPLATES_DIR)];
+    directories.extend(pack.map(Path::to_path_buf));
+    directories
+}
+
+/// The templates directory of the root's `template_pack`, if it sets one:
+/// the pack's `templates/` when it has one and the pack itself otherwise. A
+/// pack given by git URL is cloned into `.gen_packs/` the first time it is
+/// needed and used as it is from then on, until [`update_pack`].
+pub fn pack(root: &Path, config: &Config) -> Result<Option<PathBuf>, GenError> {
+    let Some(pack) = &config.template_pack else {
+        return Ok(None);
+    };
+    let directory = match is_git_url(pack) {
+        true => {
+            let clone = clone_directory(root, pack);
+            if !clone.exists() {
+                clone_pack(root, pack, &clone)?;
+            }
+            clone
+        }
+        false => root.join(pack),
+    };
+    if !directory.is_dir() {
+        return Err(GenError::MalformedConfig(
+            root.join(crate::config::ROOT_FILE),
+            format!("template_pack {directory:?} is not a directory"),
+        ));
+    }
+    Ok(Some(templates_of(directory)))
+}
+
+/// Fetches the latest of the root's template pack when it is a git
+/// repository, cloning it if it is not yet, and returns its templates
+/// directory as [`pack`] does.
+pub fn update_pack(root: &Path, config: &Config) -> Result<Option<PathBuf>, GenError> {
+    let Some(pack) = &config.template_pack else {
+        return Ok(None);
+    };
+    let clone = clone_directory(root, pack);
+    if is_git_url(pack) && clone.exists() {
+        git::run(&clone, ["fetch", "--depth", "1", "origin"])?;
+        git::run(&clone, ["reset", "--hard", "FETCH_HEAD"])?;
+    }
+    self::pack(root, config)
+}
+
+/// Whether `pack` names a git repository rather than a local directory:
+/// a URL, `user@host:path` as ssh takes it or anything ending in `.git`.
+fn is_git_url(pack: &str) -> bool {
+    let scp = pack
+        .split_once(':')
+        .is_some_and(|(host, _)| host.contains('@') && !host.contains('/'));
+    pack.contains("://") || scp || pack.ends_with(".git")
+}
+
+/// Where the pack at `url` is cloned, e.g.
+/// `.gen_packs/github.com_org_sql-templates` for
+/// `git@github.com:org/sql-templates`.
+fn clone_directory(root: &Path, url: &str) -> PathBuf {
+    let url = url.split_once("://").map_or(url, |(_, x)| x);
+    let url = url.split_once('@').map_or(url, |(_, x)| x);
+    let url = url.strip_suffix(".git").unwrap_or(url);
+    let name = url
+        .chars()
+        .map(
+            |x| match x.is_ascii_alphanumeric() || x == '-' || x == '.' {
+                true => x,
+                false => '_',
+            },
+        )
+        .collect::<String>();
+    root.join(PACKS_DIR).join(name)
+}
+
+/// Clones `url` into `clone` through a temporary directory, so a
+/// generation running alongside never sees half a pack.
+fn clone_pack(root: &Path, url: &str, clone: &Path) -> Result<(), GenError> {
+    fs::create_dir_all(clone.parent().unwrap())?;
+    let temporary = clone.with_extension(format!("{}.tmp", std::process::id()));
+    let args = ["clone", "--quiet", "--depth", "1", "--", url].map(OsStr::new);
+    let cloned = git::run(root, args.iter().chain([temporary.as_os_str()].iter()));
+    if let Err(error) = cloned {
+        let _ = fs::remove_dir_all(&temporary);
+        return Err(error);
+    }
+    if fs::rename(&temporary, clone).is_err() {
+        // Cloned by someone else meanwhile.
+        fs::remove_dir_all(&temporary)?;
+    }
+    Ok(())
+}
+
+/// The templates of a pack: its `templates/` if it has one, all of it
+/// otherwise.
+fn templates_of(pack: PathBuf) -> PathBuf {
+    let templates = pack.join(TEMPLATES_DIR);
+    match templates.is_dir() {
+        true => templates,
+        false => pack,
+    }
+}
+
+/// Where the template `operation` is rendered with comes from, if it has
+/// one, given the root's template pack as [`pack`] returns it.
+pub fn source(
+    root: &Path,
+    pack: Option<&Path>,
+    kind: Kind,
+    operation: &Operation,
+) -> Option<Source> {
+    let path = kind.path(root, operation);
+    if path.is_file() {
+        return Some(Source::Root(path));
+    }
+    if let Some(pack) = pack {
+        let path = kind.path_in(pack, operation);
+        if path.is_file() {
+            return Some(Source::Pack(path));
+        }
+    }
+    kind.built_in(operation).map(|_| Source::BuiltIn)
+}
+
+/// The template `operation` is rendered with, and where it comes from.
+pub fn template(
+    root: &Path,
+    pack: Option<&Path>,
+    kind: Kind,
+    operation: &Operation,
+) -> Result<Option<(Source, String)>, GenError> {
+    let Some(source) = source(root, pack, kind, operation) else {
+        return Ok(None);
+    };
+    let template = match source.path() {
+        Some(path) => fs::read_to_string(path)?,
+        None => kind.built_in(operation).unwrap_or_default().to_owned(),
+    };
+    Ok(Some((source, template)))
+}
+
+/// Copies the template of `operation` that the root does not have its own
+/// of, its pack's or the built-in one, into the root's templates, where
+/// generation picks it up, and returns its path. An existing one is only
+/// replaced with `force`.
+pub fn eject(
+    root: &Path,
+    pack: Option<&Path>,
+    kind: Kind,
+    operation: &Operation,
+    force: bool,
+) -> Result<PathBuf, GenError> {
+    let what = match kind.down {
+        true => "a rollback template of this operation",
+        false => "a template of this operation",
+    };
+    let path = kind.path(root, operation);
+    let packed = pack
+        .map(|x| kind.path_in(x, operation))
+        .filter(|x| x.is_file());
+    let template = match (packed, kind.built_in(operation)) {
+        (Some(packed), _) => fs::read_to_string(packed)?,
+        (None, Some(template)) => template.to_owned(),
+        (None, None) => return Err(GenError::Unsupported(kind.name(), what)),
+    };
+    fs::create_dir_all(path.parent().unwrap())?;
+    let mut options = OpenOptions::new();
+    match force {
+        true => options.write(true).create(true).truncate(true),
+        false => options.write(true).create_new(true),
+    };
+    let mut file = options.open(&path).map_err(|error| match error.kind() {
+        ErrorKind::AlreadyExists => GenError::AlreadyExists(path.clone()),
+        _ => error.into(),
+    })?;
+    file.write_all(template.as_bytes())?;
+    Ok(path)
+}
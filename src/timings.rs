@@ -0,0 +1,44 @@
+use std::time::{Duration, Instant};
+
+/// Collects how long each phase of a run took, for `--timings`.
+pub struct Timings {
+    enabled: bool,
+    started: Instant,
+    phases: Vec<(&'static str, Duration)>,
+}
+
+impl Timings {
+    pub fn new(enabled: bool) -> Self {
+        Timings {
+            enabled,
+            started: Instant::now(),
+            phases: Vec::new(),
+        }
+    }
+
+    pub fn time<T>(&mut self, phase: &'static str, f: impl FnOnce() -> T) -> T {
+        let started = Instant::now();
+        let result = f();
+        self.phases.push((phase, started.elapsed()));
+        result
+    }
+
+    /// Prints the collected phases to stderr, so stdout stays usable by scripts.
+    pub fn report(&self) {
+        if !self.enabled {
+            return;
+        }
+        let width = self
+            .phases
+            .iter()
+            .map(|x| x.0.len())
+            .max()
+            .unwrap_or(0)
+            .max(5);
+        eprintln!("timings:");
+        for (phase, duration) in &self.phases {
+            eprintln!("  {phase:<width$}  {duration:>10.3?}");
+        }
+        eprintln!("  {:<width$}  {:>10.3?}", "total", self.started.elapsed());
+    }
+}
@@ -0,0 +1,85 @@
+//! When the triggers of `create-trigger` fire. MySQL and SQLite fire a
+//! trigger on one event, the other dialects on any of several.
+
+use crate::dialect::Dialect;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Timing {
+    Before,
+    #[default]
+    After,
+    /// Instead of the statement, for triggers on views.
+    InsteadOf,
+}
+
+impl Timing {
+    /// The name of the timing on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Timing::Before => "before",
+            Timing::After => "after",
+            Timing::InsteadOf => "instead-of",
+        }
+    }
+
+    pub fn keyword(self) -> &'static str {
+        match self {
+            Timing::Before => "BEFORE",
+            Timing::After => "AFTER",
+            Timing::InsteadOf => "INSTEAD OF",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Event {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl Event {
+    pub const ALL: [Event; 3] = [Event::Insert, Event::Update, Event::Delete];
+
+    /// The name of the event on the command line.
+    pub fn name(self) -> &'static str {
+        match self {
+            Event::Insert => "insert",
+            Event::Update => "update",
+            Event::Delete => "delete",
+        }
+    }
+
+    pub fn keyword(self) -> &'static str {
+        match self {
+            Event::Insert => "INSERT",
+            Event::Update => "UPDATE",
+            Event::Delete => "DELETE",
+        }
+    }
+}
+
+/// Whether triggers of `dialect` can fire on more than one event.
+pub fn several_events(dialect: Dialect) -> bool {
+    !matches!(dialect, Dialect::Mysql | Dialect::Sqlite)
+}
+
+/// The events a trigger fires on as `dialect` lists them, e.g. `INSERT OR
+/// UPDATE`. Without any, a trigger fires on every event where it can, and
+/// on inserts where it cannot.
+pub(crate) fn events(dialect: Dialect, events: &[Event]) -> String {
+    let events = match events {
+        [] if several_events(dialect) => &Event::ALL[..],
+        [] => &Event::ALL[..1],
+        events => events,
+    };
+    let separator = match dialect {
+        Dialect::Mssql => ", ",
+        _ => " OR ",
+    };
+    let keywords = events.iter().map(|x| x.keyword()).collect::<Vec<_>>();
+    keywords.join(separator)
+}
@@ -0,0 +1,345 @@
+//! Problems in the set of migrations that generation alone cannot prevent,
+//! such as two branches each adding the same prefix.
+
+use crate::config::{Config, Numbering};
+use crate::error::GenError;
+use crate::index::MigrationIndex;
+use crate::manifest::{checksum, relative_path, Manifest};
+use crate::naming::parse_file_name;
+use crate::renumber;
+use chrono::NaiveDate;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt::{self, Write};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Problem {
+    /// Migrations numbered in the same space with the same date and index.
+    Collision(Vec<PathBuf>),
+    /// A migration dated after today, which blocks numbering.
+    FutureDated(PathBuf),
+    /// A `.sql` file in the output directory that does not follow the naming
+    /// scheme.
+    Misnamed(PathBuf),
+    /// A `.sql` file in the output directory whose name is not UTF-8.
+    NotUtf8(PathBuf),
+    /// A migration or rollback with nothing but whitespace in it.
+    Empty(PathBuf),
+    /// A migration whose content is not what the manifest recorded.
+    Modified(PathBuf),
+    /// A migration the manifest does not list.
+    Unrecorded(PathBuf),
+    /// A migration the manifest lists that is gone.
+    Missing(PathBuf),
+    /// A migration the branch added that has to be renumbered to follow the
+    /// ones already on its base.
+    OutOfOrder(PathBuf),
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::Collision(paths) => {
+                write!(f, "same date and index:")?;
+                for path in paths {
+                    write!(f, " {path:?}")?;
+                }
+                Ok(())
+            }
+            Problem::FutureDated(path) => write!(f, "dated in the future: {path:?}"),
+            Problem::Misnamed(path) => {
+                write!(f, "not named YYYYMMDDNN - description.sql: {path:?}")
+            }
+            Problem::NotUtf8(path) => write!(f, "name is not UTF-8: {path:?}"),
+            Problem::Empty(path) => write!(f, "empty: {path:?}"),
+            Problem::Modified(path) => {
+                write!(
+                    f,
+                    "changed since the manifest recorded it, run lock: {path:?}"
+                )
+            }
+            Problem::Unrecorded(path) => write!(f, "not in the manifest, run lock: {path:?}"),
+            Problem::Missing(path) => write!(f, "in the manifest but gone: {path:?}"),
+            Problem::OutOfOrder(path) => {
+                write!(
+                    f,
+                    "out of order with the base's migrations, run renumber: {path:?}"
+                )
+            }
+        }
+    }
+}
+
+impl Problem {
+    /// The rule the problem breaks.
+    pub fn rule(&self) -> Rule {
+        match self {
+            Problem::Collision(_) => Rule::UniquePrefixes,
+            Problem::FutureDated(_) => Rule::NotFutureDated,
+            Problem::Misnamed(_) => Rule::Named,
+            Problem::NotUtf8(_) => Rule::Utf8Names,
+            Problem::Empty(_) => Rule::NotEmpty,
+            Problem::Modified(_) | Problem::Unrecorded(_) | Problem::Missing(_) => Rule::Checksums,
+            Problem::OutOfOrder(_) => Rule::InOrder,
+        }
+    }
+}
+
+/// What `verify` and `ci-check` check, a test case each in their JUnit
+/// reports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rule {
+    UniquePrefixes,
+    NotFutureDated,
+    Named,
+    Utf8Names,
+    NotEmpty,
+    InOrder,
+    Checksums,
+}
+
+impl Rule {
+    /// The rules of `verify`.
+    pub const VERIFY: [Rule; 5] = [
+        Rule::UniquePrefixes,
+        Rule::NotFutureDated,
+        Rule::Named,
+        Rule::Utf8Names,
+        Rule::NotEmpty,
+    ];
+    /// The rules of `verify_branch`, those of `verify` and the order.
+    pub const BRANCH: [Rule; 6] = [
+        Rule::UniquePrefixes,
+        Rule::NotFutureDated,
+        Rule::Named,
+        Rule::Utf8Names,
+        Rule::NotEmpty,
+        Rule::InOrder,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Rule::UniquePrefixes => "unique date and index",
+            Rule::NotFutureDated => "not dated in the future",
+            Rule::Named => "named YYYYMMDDNN - description",
+            Rule::Utf8Names => "UTF-8 names",
+            Rule::NotEmpty => "not empty",
+            Rule::InOrder => "in order with the base",
+            Rule::Checksums => "checksums match the manifest",
+        }
+    }
+}
+
+/// `problems` as a JUnit report for CI, the suite `suite` with a test case
+/// per rule of `rules` that fails with the problems breaking it.
+pub fn junit(suite: &str, rules: &[Rule], problems: &[Problem]) -> String {
+    let failures = rules
+        .iter()
+        .filter(|x| problems.iter().any(|problem| problem.rule() == **x))
+        .count();
+    let mut report = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    writeln!(
+        report,
+        "<testsuite name=\"{}\" tests=\"{}\" failures=\"{failures}\">",
+        escape_xml(suite),
+        rules.len()
+    )
+    .unwrap();
+    for rule in rules {
+        let broken = problems
+            .iter()
+            .filter(|x| x.rule() == *rule)
+            .map(ToString::to_string)
+            .collect::<Vec<_>>();
+        let name = format!(
+            "name=\"{}\" classname=\"{}\"",
+            rule.name(),
+            escape_xml(suite)
+        );
+        if broken.is_empty() {
+            writeln!(report, "  <testcase {name}/>").unwrap();
+            continue;
+        }
+        writeln!(report, "  <testcase {name}>").unwrap();
+        let message = match broken.len() {
+            1 => "1 problem".to_owned(),
+            count => format!("{count} problems"),
+        };
+        writeln!(
+            report,
+            "    <failure message=\"{message}\">{}</failure>",
+            escape_xml(&broken.join("\n"))
+        )
+        .unwrap();
+        writeln!(report, "  </testcase>").unwrap();
+    }
+    report.push_str("</testsuite>\n");
+    report
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Whether the file at `path` holds nothing but whitespace. Only short
+/// files are read to tell.
+fn is_empty(path: &Path) -> bool {
+    match fs::metadata(path) {
+        Ok(metadata) if metadata.len() == 0 => true,
+        Ok(metadata) if metadata.len() <= 1024 => {
+            fs::read(path).is_ok_and(|x| x.iter().all(u8::is_ascii_whitespace))
+        }
+        _ => false,
+    }
+}
+
+/// Checks every migration in `index` against the rules of `config`. Paths in
+/// the problems are relative to `root`.
+pub fn verify(
+    root: &Path,
+    config: &Config,
+    index: &MigrationIndex,
+    today: NaiveDate,
+) -> Vec<Problem> {
+    let output_dir = match &config.output_dir {
+        Some(output_dir) => root.join(output_dir),
+        None => root.to_path_buf(),
+    };
+
+    let mut problems = Vec::new();
+    let mut numbered = BTreeMap::<_, Vec<PathBuf>>::new();
+    for path in index.files() {
+        let relative = PathBuf::from(relative_path(root, path));
+        if is_empty(path) {
+            problems.push(Problem::Empty(relative.clone()));
+        }
+        // Rollbacks share their forward migration's name by design.
+        if config.is_rollback(root, path) {
+            continue;
+        }
+        let Some(file_name) = path.file_name().and_then(|x| x.to_str()) else {
+            if path.starts_with(&output_dir) {
+                problems.push(Problem::NotUtf8(relative));
+            }
+            continue;
+        };
+        let Some(name) = parse_file_name(file_name) else {
+            if path.starts_with(&output_dir) {
+                problems.push(Problem::Misnamed(relative));
+            }
+            continue;
+        };
+        if name.date > today {
+            problems.push(Problem::FutureDated(relative.clone()));
+        }
+        let scope = scope(root, config, path);
+        let service = name.service.map(ToOwned::to_owned);
+        numbered
+            .entry((scope, service, name.date, name.index))
+            .or_default()
+            .push(relative);
+    }
+
+    problems.extend(
+        numbered
+            .into_values()
+            .filter(|x| x.len() > 1)
+            .map(Problem::Collision),
+    );
+    problems
+}
+
+/// Checks the migrations in `index` against the checksums the manifest
+/// recorded for them, for edits to migrations that may have been applied.
+pub fn verify_checksums(
+    root: &Path,
+    config: &Config,
+    index: &MigrationIndex,
+) -> Result<Vec<Problem>, GenError> {
+    let manifest = Manifest::load(root)?;
+    let mut problems = Vec::new();
+    for path in index.files() {
+        if config.is_rollback(root, path) {
+            continue;
+        }
+        let is_migration = path
+            .file_name()
+            .and_then(|x| x.to_str())
+            .and_then(parse_file_name)
+            .is_some();
+        let relative = relative_path(root, path);
+        match manifest.migrations.iter().find(|x| x.path == relative) {
+            Some(entry) if entry.checksum != checksum(&fs::read(path)?) => {
+                problems.push(Problem::Modified(relative.into()));
+            }
+            Some(_) => {}
+            None if is_migration => problems.push(Problem::Unrecorded(relative.into())),
+            None => {}
+        }
+    }
+    for entry in &manifest.migrations {
+        if !root.join(&entry.path).exists() {
+            problems.push(Problem::Missing(entry.path.clone().into()));
+        }
+    }
+    Ok(problems)
+}
+
+/// Checks the migrations in `branch_files`, the ones a branch added, as they
+/// would land on its base: the problems of `verify` that involve them, and any
+/// of them `renumber` would move to follow the base's migrations.
+pub fn verify_branch(
+    root: &Path,
+    config: &Config,
+    index: &MigrationIndex,
+    today: NaiveDate,
+    branch_files: &BTreeSet<PathBuf>,
+) -> Vec<Problem> {
+    let added = branch_files
+        .iter()
+        .map(|x| PathBuf::from(relative_path(root, x)))
+        .collect::<BTreeSet<_>>();
+    let mut problems = verify(root, config, index, today);
+    problems.retain(|x| match x {
+        Problem::Collision(paths) => paths.iter().any(|x| added.contains(x)),
+        Problem::FutureDated(path)
+        | Problem::Misnamed(path)
+        | Problem::NotUtf8(path)
+        | Problem::Empty(path)
+        | Problem::Modified(path)
+        | Problem::Unrecorded(path)
+        | Problem::Missing(path)
+        | Problem::OutOfOrder(path) => added.contains(path),
+    });
+    problems.extend(
+        renumber::plan(root, config, index, branch_files)
+            .into_iter()
+            .map(|x| Problem::OutOfOrder(relative_path(root, &x.from).into())),
+    );
+    problems
+}
+
+/// The numbering space `path` belongs to: the whole root unless services or
+/// schemas are numbered on their own, in which case migrations only collide
+/// within their directory.
+pub(crate) fn scope(root: &Path, config: &Config, path: &Path) -> PathBuf {
+    if config.numbering == Numbering::Root && config.services.is_empty() {
+        return PathBuf::new();
+    }
+    let parent = path.parent().unwrap_or(root);
+    let parent = parent.strip_prefix(root).unwrap_or(parent);
+    // Date subdirectories do not start a numbering space of their own.
+    parent
+        .components()
+        .filter(|x| match x {
+            Component::Normal(x) => !x
+                .to_str()
+                .is_some_and(|x| x.bytes().all(|x| x.is_ascii_digit())),
+            _ => false,
+        })
+        .collect()
+}
@@ -0,0 +1,48 @@
+//! Browser bindings for previewing a migration without touching a filesystem.
+
+use crate::{compute_filename, render_operation, Operation};
+use chrono::NaiveDate;
+use clap::ValueEnum;
+use wasm_bindgen::prelude::*;
+
+fn parse_operation(operation: &str) -> Result<Operation, JsError> {
+    Operation::from_str(operation, false)
+        .map_err(|_| JsError::new(&format!("unknown operation {operation:?}")))
+}
+
+/// Returns the file name a migration would get on `date` (`YYYY-MM-DD`) with
+/// the given daily `index`.
+#[wasm_bindgen(js_name = previewFileName)]
+pub fn preview_file_name(
+    operation: &str,
+    name: &str,
+    column: Option<String>,
+    date: &str,
+    index: i32,
+) -> Result<String, JsError> {
+    let day = NaiveDate::parse_from_str(date, "%Y-%m-%d")?;
+    Ok(compute_filename(
+        &parse_operation(operation)?,
+        name,
+        column.as_deref(),
+        day,
+        index,
+    )?)
+}
+
+/// Returns the rendered body of a migration, or `undefined` for operations
+/// without a template.
+#[wasm_bindgen(js_name = previewContent)]
+pub fn preview_content(
+    operation: &str,
+    name: &str,
+    schema: Option<String>,
+    column: Option<String>,
+) -> Result<Option<String>, JsError> {
+    Ok(render_operation(
+        &parse_operation(operation)?,
+        name,
+        schema.as_deref(),
+        column.as_deref(),
+    )?)
+}
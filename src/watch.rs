@@ -0,0 +1,157 @@
+use chrono::NaiveDate;
+use log::{info, warn};
+use notify::{RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Deserialize;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use thing::config::Timezone;
+use thing::index::MigrationIndex;
+use thing::Generator;
+
+/// Where the day migrations are dated comes from.
+#[derive(Debug, Clone, Copy)]
+pub struct Clock {
+    /// The day `--now` fixed.
+    pub now: Option<NaiveDate>,
+    pub timezone: Timezone,
+}
+
+/// The day `--now` fixed, or else the current one in the clock's time zone.
+pub fn today(clock: Clock) -> NaiveDate {
+    clock.now.unwrap_or_else(|| clock.timezone.now().date())
+}
+
+/// What the next prefix is numbered for, the root's defaults where unset.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct Scope {
+    pub schema: Option<String>,
+    pub service: Option<String>,
+    /// Relative to the root; defaults to its configured output directory.
+    pub output_dir: Option<PathBuf>,
+}
+
+/// The next `YYYYMMDDNN` prefix on `today` in `scope`, `None` with migrate
+/// names, which are stamped with the time instead, or the error generation
+/// would fail with.
+pub fn next_prefix(
+    index: &MigrationIndex,
+    today: NaiveDate,
+    scope: &Scope,
+) -> Result<Option<String>, thing::GenError> {
+    let root = index.root();
+    let mut generator = Generator::new(root).today(today);
+    if let Some(schema) = &scope.schema {
+        generator = generator.schema(schema);
+    }
+    if let Some(service) = &scope.service {
+        generator = generator.service(service);
+    }
+    if let Some(output_dir) = &scope.output_dir {
+        generator = generator.output_dir(root.join(output_dir));
+    }
+    generator.next_prefix(index)
+}
+
+/// The line reported to clients.
+fn status(index: &MigrationIndex, clock: Clock, scope: &Scope) -> String {
+    match next_prefix(index, today(clock), scope) {
+        Ok(Some(prefix)) => prefix,
+        Ok(None) => "no index: migrations are stamped YYYYMMDDHHMMSS".to_owned(),
+        Err(error) => format!("error: {error}"),
+    }
+}
+
+/// Keeps `index` in line with the filesystem under `root` for as long as the
+/// returned watcher is alive, signalling `changed` after every update.
+pub fn keep_current(
+    root: &Path,
+    index: Arc<Mutex<MigrationIndex>>,
+    changed: mpsc::Sender<()>,
+) -> anyhow::Result<RecommendedWatcher> {
+    let mut watcher = notify::recommended_watcher(move |event: notify::Result<notify::Event>| {
+        match event {
+            Ok(event) => {
+                let mut index = index.lock().unwrap();
+                for path in &event.paths {
+                    index.refresh(path);
+                }
+            }
+            Err(error) => warn!("watch error: {error}"),
+        }
+        let _ = changed.send(());
+    })?;
+    watcher.watch(root, RecursiveMode::Recursive)?;
+    info!("watching {:?}", root);
+    Ok(watcher)
+}
+
+/// Watches `root` and prints the next available number to stdout every time it
+/// changes. When `socket` is given, every connection to it is answered with the
+/// same line.
+pub fn watch(root: &Path, socket: Option<&Path>, clock: Clock, scope: Scope) -> anyhow::Result<()> {
+    let index = Arc::new(Mutex::new(MigrationIndex::scan(root)));
+
+    if let Some(socket) = socket {
+        serve(socket, Arc::clone(&index), clock, scope.clone())?;
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let _watcher = keep_current(root, Arc::clone(&index), tx)?;
+
+    let mut last_status = None;
+    loop {
+        let status = status(&index.lock().unwrap(), clock, &scope);
+        if last_status.as_ref() != Some(&status) {
+            let mut stdout = std::io::stdout().lock();
+            writeln!(stdout, "{status}")?;
+            stdout.flush()?;
+            last_status = Some(status);
+        }
+
+        // The timeout also lets the reported day roll over at midnight.
+        match rx.recv_timeout(Duration::from_secs(1)) {
+            Ok(()) | Err(mpsc::RecvTimeoutError::Timeout) => {}
+            Err(mpsc::RecvTimeoutError::Disconnected) => return Ok(()),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn serve(
+    socket: &Path,
+    index: Arc<Mutex<MigrationIndex>>,
+    clock: Clock,
+    scope: Scope,
+) -> anyhow::Result<()> {
+    use std::os::unix::net::UnixListener;
+
+    if socket.exists() {
+        std::fs::remove_file(socket)?;
+    }
+    let listener = UnixListener::bind(socket)?;
+    info!("listening on {:?}", socket);
+
+    std::thread::spawn(move || {
+        for stream in listener.incoming() {
+            let status = status(&index.lock().unwrap(), clock, &scope);
+            if let Err(error) = stream.and_then(|mut x| writeln!(x, "{status}")) {
+                warn!("socket error: {error}");
+            }
+        }
+    });
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn serve(
+    _socket: &Path,
+    _index: Arc<Mutex<MigrationIndex>>,
+    _clock: Clock,
+    _scope: Scope,
+) -> anyhow::Result<()> {
+    Err(anyhow::anyhow!("--socket is only supported on unix"))
+}
@@ -0,0 +1,132 @@
+use crate::Cli;
+use clap::error::{ContextKind, ContextValue, ErrorKind};
+use clap::{CommandFactory, Parser, ValueEnum};
+use std::ffi::OsString;
+use std::io::{IsTerminal, Write};
+use thing::Operation;
+
+/// Parses the command line, asking on the terminal for what it lacks instead
+/// of failing: the operation to generate when no command was given, and the
+/// value of every required argument left out. Without a terminal, or when
+/// the question is not answered, it fails as clap would.
+pub fn parse() -> Cli {
+    let mut args = std::env::args_os().collect::<Vec<_>>();
+    loop {
+        let error = match Cli::try_parse_from(&args) {
+            Ok(parsed) => return parsed,
+            Err(error) => error,
+        };
+        if !std::io::stdin().is_terminal() || !complete(&mut args, &error) {
+            error.exit();
+        }
+    }
+}
+
+/// Adds to `args` what `error` says they lack, as answered on the terminal,
+/// and returns whether it did.
+fn complete(args: &mut Vec<OsString>, error: &clap::Error) -> bool {
+    match error.kind() {
+        ErrorKind::MissingSubcommand | ErrorKind::DisplayHelpOnMissingArgumentOrSubcommand => {
+            match ask_operation() {
+                Some(operation) => {
+                    args.push(operation.into());
+                    true
+                }
+                None => false,
+            }
+        }
+        ErrorKind::MissingRequiredArgument => {
+            let Some(ContextValue::Strings(missing)) = error.get(ContextKind::InvalidArg) else {
+                return false;
+            };
+            let mut command = Cli::command();
+            command.build();
+            // The subcommand is the first argument naming one.
+            let subcommand = args.iter().skip(1).find_map(|x| {
+                let name = x.to_str()?;
+                command.find_subcommand(name)
+            });
+            let Some(subcommand) = subcommand else {
+                return false;
+            };
+            for missing in missing {
+                let Some(arg) = subcommand
+                    .get_arguments()
+                    .find(|x| brackets_off(&x.to_string()) == brackets_off(missing))
+                else {
+                    return false;
+                };
+                let help = arg.get_help().map(ToString::to_string).unwrap_or_default();
+                let name = arg
+                    .get_value_names()
+                    .and_then(|x| x.first())
+                    .map_or_else(|| arg.get_id().to_string(), |x| x.to_lowercase());
+                let Some(value) = ask(&name, &help) else {
+                    return false;
+                };
+                match arg.get_long() {
+                    Some(long) if !arg.is_positional() => {
+                        args.push(format!("--{long}={value}").into())
+                    }
+                    _ => args.push(value.into()),
+                }
+            }
+            true
+        }
+        _ => false,
+    }
+}
+
+/// `arg` as clap writes it with its brackets dropped, as errors write
+/// optional positionals `<NAME>` that `Arg` writes `[NAME]`.
+fn brackets_off(arg: &str) -> String {
+    arg.replace(['<', '>', '[', ']'], "")
+}
+
+/// Asks which operation to generate, by number or name, listing them all.
+fn ask_operation() -> Option<String> {
+    let command = Cli::command();
+    let operations = Operation::value_variants()
+        .iter()
+        .filter_map(|x| command.find_subcommand(x.to_possible_value()?.get_name()))
+        .collect::<Vec<_>>();
+    for (number, operation) in operations.iter().enumerate() {
+        let about = operation
+            .get_about()
+            .map(ToString::to_string)
+            .unwrap_or_default();
+        eprintln!("{:>3}) {:<28} {about}", number + 1, operation.get_name());
+    }
+    loop {
+        let answer = ask("operation", "its number or name")?;
+        let chosen = match answer.parse::<usize>() {
+            Ok(number) => number.checked_sub(1).and_then(|x| operations.get(x)),
+            Err(_) => operations.iter().find(|x| x.get_name() == answer),
+        };
+        match chosen {
+            Some(operation) => return Some(operation.get_name().to_owned()),
+            None => eprintln!("{answer:?} is none of them"),
+        }
+    }
+}
+
+/// Asks for `name` on the terminal until given something, and gives `None`
+/// once stdin ends.
+fn ask(name: &str, help: &str) -> Option<String> {
+    loop {
+        match help {
+            "" => eprint!("{name}: "),
+            help => eprint!("{name} ({}): ", help.trim_end_matches('.')),
+        }
+        std::io::stderr().flush().ok()?;
+        let mut value = String::new();
+        if std::io::stdin().read_line(&mut value).ok()? == 0 {
+            eprintln!();
+            return None;
+        }
+        let value = value.trim();
+        if !value.is_empty() {
+            return Some(value.to_owned());
+        }
+    }
+}